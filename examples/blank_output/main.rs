@@ -1,5 +1,25 @@
 //#![windows_subsystem = "windows"]	// Specify the "windows" subsystem on Windows to hide the console window.
+use glam::*;
+use mithrilengine::GameBuilder;
+use mithrilengine::component::{ self, ui, camera::Camera };
+
 fn main()
 {
-	mithrilengine::run_game("daigennki", "Untitled Game Project");
+	GameBuilder::new("daigennki", "Untitled Game Project")
+		.with_startup_system(|world, render_ctx| {
+			// add some 3D entities for testing
+			world.add_unique(Camera::new(render_ctx, [ 1.0, 3.0, 3.0 ].into(), [ 0.0, 0.0, 0.0 ].into())?)?;
+
+			world.add_entity(component::new_triangle(render_ctx, [ 0.0, 0.0, 0.0 ].into(), Vec3::ONE, [ 0.1, 0.0, 0.0, 0.8 ].into())?);
+			world.add_entity(component::new_triangle(render_ctx, [ 0.2, 0.0, 0.2 ].into(), Vec3::ONE, [ 0.0, 0.1, 0.0, 0.8 ].into())?);
+			world.add_entity(component::new_triangle(render_ctx, [ 0.4, 0.0, 0.4 ].into(), Vec3::ONE, [ 0.0, 0.0, 0.1, 0.8 ].into())?);
+			world.add_entity(component::new_triangle(render_ctx, [ 0.6, 0.0, 0.6 ].into(), Vec3::ONE, [ 0.1, 0.1, 0.0, 0.8 ].into())?);
+
+			// add some UI entities for testing
+			world.add_entity(ui::new_image(render_ctx, "test_image.png", [ 0, 0 ].into())?);
+			world.add_entity(ui::new_text(render_ctx, "Hello World!", 32.0, [ -200, -200 ].into())?);
+
+			Ok(())
+		})
+		.run();
 }
@@ -0,0 +1,509 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ BufWriter, Read, Seek, SeekFrom, Write };
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use crate::error::EngineError;
+use crate::vfs::VfsSource;
+
+/// File extension of asset archives.
+pub const ARCHIVE_EXTENSION: &str = "mepk";
+
+const ARCHIVE_MAGIC: &[u8; 4] = b"MEPK";
+
+/// Version of the archive format.
+const FORMAT_VERSION: u32 = 1;
+
+/// magic (4 bytes) + format version (u32) + entry count (u32) + TOC offset (u64) + TOC length (u64) + TOC CRC32 (u32)
+const HEADER_LEN: usize = 4 + 4 + 4 + 8 + 8 + 4;
+
+/// How the data of an entry is stored in the archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression
+{
+	/// Stored as it is.
+	None,
+
+	/// Compressed with raw DEFLATE.
+	Deflate
+}
+impl Compression
+{
+	fn from_u8(value: u8) -> Option<Compression>
+	{
+		match value {
+			0 => Some(Compression::None),
+			1 => Some(Compression::Deflate),
+			_ => None
+		}
+	}
+
+	fn to_u8(self) -> u8
+	{
+		match self {
+			Compression::None => 0,
+			Compression::Deflate => 1
+		}
+	}
+}
+
+/// A file in the archive's table of contents.
+#[derive(Clone, Debug)]
+pub struct ArchiveEntry
+{
+	/// Offset of the stored data from the beginning of the archive.
+	pub offset: u64,
+
+	/// Size of the data as it's stored in the archive, which is smaller than `size` if it's compressed.
+	pub stored_size: u64,
+
+	/// Size of the data once it's decompressed.
+	pub size: u64,
+
+	pub compression: Compression,
+
+	/// CRC32 of the decompressed data.
+	pub checksum: u32
+}
+
+/// Reader for asset archives, which can be mounted into the virtual filesystem.
+///
+/// Archives ("*.mepk") consist of a header, followed by the data of each file, followed by a table of contents listing
+/// the logical path, location, size, compression, and CRC32 of each file. The table of contents is read and checked
+/// against the CRC32 in the header when the archive is opened, and each file is checked against its own CRC32 when it
+/// gets read, so that damaged or modified archives are detected without having to read the whole archive upfront.
+/// Files are read directly from their location in the archive, without unpacking anything else.
+pub struct Archive
+{
+	path: PathBuf,
+	file: Mutex<File>,
+	entries: HashMap<String, ArchiveEntry>
+}
+impl Archive
+{
+	/// Open the archive and read its table of contents.
+	pub fn open(path: &Path) -> Result<Archive, EngineError>
+	{
+		let mut file = File::open(path).map_err(|e| EngineError::io(path, e))?;
+
+		let mut header = [0; HEADER_LEN];
+		file.read_exact(&mut header).map_err(|e| EngineError::io(path, e))?;
+		if &header[0..4] != ARCHIVE_MAGIC {
+			return Err(EngineError::decode(path, "not an asset archive"))
+		}
+		let format_version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+		if format_version != FORMAT_VERSION {
+			return Err(EngineError::decode(path, format!("unknown archive format version {}", format_version)))
+		}
+		let entry_count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+		let toc_offset = u64::from_le_bytes(header[12..20].try_into().unwrap());
+		let toc_len = u64::from_le_bytes(header[20..28].try_into().unwrap());
+		let toc_checksum = u32::from_le_bytes(header[28..32].try_into().unwrap());
+
+		let file_len = file.metadata().map_err(|e| EngineError::io(path, e))?.len();
+		if toc_offset.checked_add(toc_len) != Some(file_len) {
+			return Err(EngineError::decode(path, "archive is truncated or has trailing data"))
+		}
+
+		let mut toc = vec![0; toc_len as usize];
+		file.seek(SeekFrom::Start(toc_offset))
+			.and_then(|_| file.read_exact(&mut toc))
+			.map_err(|e| EngineError::io(path, e))?;
+		if crc32fast::hash(&toc) != toc_checksum {
+			return Err(EngineError::decode(path, "table of contents checksum mismatch"))
+		}
+
+		let entries = parse_toc(&toc, entry_count, toc_offset).map_err(|e| EngineError::decode(path, e))?;
+		log::info!("Opened archive '{}' with {} file(s)", path.display(), entries.len());
+
+		Ok(Archive{ path: path.to_path_buf(), file: Mutex::new(file), entries: entries })
+	}
+
+	/// Get the entry for the file at the logical path, if the archive has it.
+	pub fn entry(&self, logical_path: &str) -> Option<&ArchiveEntry>
+	{
+		self.entries.get(logical_path)
+	}
+
+	/// Iterate over the logical paths and entries of all files in the archive, in no particular order.
+	pub fn entries(&self) -> impl Iterator<Item = (&str, &ArchiveEntry)>
+	{
+		self.entries.iter().map(|(path, entry)| (path.as_str(), entry))
+	}
+
+	/// Read and decompress the file at the logical path, checking it against its CRC32.
+	pub fn read_file(&self, logical_path: &str) -> Option<std::io::Result<Vec<u8>>>
+	{
+		self.entries.get(logical_path).map(|entry| self.read_entry(logical_path, entry))
+	}
+
+	/// Read every file in the archive to check them against their CRC32s, returning the logical paths of the files that
+	/// are damaged.
+	pub fn verify(&self) -> Vec<String>
+	{
+		let mut damaged: Vec<String> = self.entries.iter()
+			.filter(|(path, entry)| self.read_entry(path, entry).is_err())
+			.map(|(path, _)| path.clone())
+			.collect();
+		damaged.sort();
+		damaged
+	}
+
+	fn read_entry(&self, logical_path: &str, entry: &ArchiveEntry) -> std::io::Result<Vec<u8>>
+	{
+		let mut stored = vec![0; entry.stored_size as usize];
+		{
+			let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+			file.seek(SeekFrom::Start(entry.offset))?;
+			file.read_exact(&mut stored)?;
+		}
+
+		let data = match entry.compression {
+			Compression::None => stored,
+			Compression::Deflate => {
+				// don't decompress more than the expected size, in case the archive has been tampered with
+				let mut data = Vec::new();
+				DeflateDecoder::new(&stored[..]).take(entry.size).read_to_end(&mut data)?;
+				data
+			}
+		};
+
+		if data.len() as u64 != entry.size || crc32fast::hash(&data) != entry.checksum {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("'{}' in archive '{}' is damaged (checksum mismatch)", logical_path, self.path.display())
+			))
+		}
+		Ok(data)
+	}
+}
+impl VfsSource for Archive
+{
+	fn read(&self, logical_path: &str) -> Option<std::io::Result<Vec<u8>>>
+	{
+		self.read_file(logical_path)
+	}
+
+	fn exists(&self, logical_path: &str) -> bool
+	{
+		self.entries.contains_key(logical_path)
+	}
+
+	fn describe(&self) -> String
+	{
+		format!("archive '{}'", self.path.display())
+	}
+}
+
+/// Parse the table of contents. Each entry consists of the path length (u16), the path in UTF-8, offset (u64), stored
+/// size (u64), size (u64), compression (u8), and CRC32 (u32).
+fn parse_toc(toc: &[u8], entry_count: u32, data_end: u64) -> Result<HashMap<String, ArchiveEntry>, String>
+{
+	let mut entries = HashMap::new();
+	let mut rest = toc;
+	for i in 0..entry_count {
+		let truncated = || format!("table of contents is truncated at entry {}", i);
+		let path_len = u16::from_le_bytes(take(&mut rest, 2).ok_or_else(truncated)?.try_into().unwrap()) as usize;
+		let path_bytes = take(&mut rest, path_len).ok_or_else(truncated)?;
+		let path = std::str::from_utf8(path_bytes).map_err(|e| format!("invalid path for entry {}: {}", i, e))?;
+		let fields = take(&mut rest, 8 + 8 + 8 + 1 + 4).ok_or_else(truncated)?;
+
+		let entry = ArchiveEntry{
+			offset: u64::from_le_bytes(fields[0..8].try_into().unwrap()),
+			stored_size: u64::from_le_bytes(fields[8..16].try_into().unwrap()),
+			size: u64::from_le_bytes(fields[16..24].try_into().unwrap()),
+			compression: Compression::from_u8(fields[24])
+				.ok_or_else(|| format!("unknown compression {} for '{}'", fields[24], path))?,
+			checksum: u32::from_le_bytes(fields[25..29].try_into().unwrap())
+		};
+		if entry.offset < HEADER_LEN as u64 || entry.offset.checked_add(entry.stored_size).map_or(true, |end| end > data_end) {
+			return Err(format!("data for '{}' is out of bounds", path))
+		}
+		if entries.insert(path.to_string(), entry).is_some() {
+			return Err(format!("'{}' is listed more than once", path))
+		}
+	}
+	if !rest.is_empty() {
+		return Err("table of contents has trailing data".to_string())
+	}
+	Ok(entries)
+}
+
+// Split off the first `len` bytes of `bytes`, or return `None` if there aren't enough.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]>
+{
+	if bytes.len() < len {
+		return None
+	}
+	let (taken, rest) = bytes.split_at(len);
+	*bytes = rest;
+	Some(taken)
+}
+
+/// Statistics about an archive built with `pack_directory`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PackStats
+{
+	pub file_count: usize,
+	pub compressed_count: usize,
+	pub total_size: u64,
+	pub stored_size: u64
+}
+
+/// Build an archive at `archive_path` from all files in `dir`, using their paths relative to `dir` as their logical
+/// paths. If `compress` is true, each file gets compressed unless that doesn't make it smaller.
+pub fn pack_directory(dir: &Path, archive_path: &Path, compress: bool) -> Result<PackStats, EngineError>
+{
+	let mut files = Vec::new();
+	collect_files(dir, dir, &mut files)?;
+	files.sort();
+
+	let file = File::create(archive_path).map_err(|e| EngineError::io(archive_path, e))?;
+	let mut writer = BufWriter::new(file);
+	let io_err = |e| EngineError::io(archive_path, e);
+
+	// the header gets written once the location of the table of contents is known
+	writer.write_all(&[0; HEADER_LEN]).map_err(io_err)?;
+
+	let mut stats = PackStats::default();
+	let mut toc = Vec::new();
+	let mut offset = HEADER_LEN as u64;
+	for (logical_path, real_path) in &files {
+		let data = std::fs::read(real_path).map_err(|e| EngineError::io(real_path, e))?;
+		let deflated = match compress {
+			true => Some(deflate(&data).map_err(|e| EngineError::io(real_path, e))?),
+			false => None
+		};
+		let (compression, stored) = match &deflated {
+			Some(d) if d.len() < data.len() => (Compression::Deflate, d.as_slice()),
+			_ => (Compression::None, data.as_slice())
+		};
+		writer.write_all(stored).map_err(io_err)?;
+
+		let path_len: u16 = logical_path.len().try_into()
+			.map_err(|_| EngineError::decode(real_path, "path is too long to be stored in an archive"))?;
+		toc.extend_from_slice(&path_len.to_le_bytes());
+		toc.extend_from_slice(logical_path.as_bytes());
+		toc.extend_from_slice(&offset.to_le_bytes());
+		toc.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+		toc.extend_from_slice(&(data.len() as u64).to_le_bytes());
+		toc.push(compression.to_u8());
+		toc.extend_from_slice(&crc32fast::hash(&data).to_le_bytes());
+
+		log::debug!("Packed '{}' ({:?}, {} -> {} bytes)", logical_path, compression, data.len(), stored.len());
+		offset += stored.len() as u64;
+		stats.file_count += 1;
+		stats.total_size += data.len() as u64;
+		stats.stored_size += stored.len() as u64;
+		if compression != Compression::None {
+			stats.compressed_count += 1;
+		}
+	}
+	writer.write_all(&toc).map_err(io_err)?;
+
+	let entry_count: u32 = files.len().try_into()
+		.map_err(|_| EngineError::decode(dir, "too many files to be stored in an archive"))?;
+	let mut header = Vec::with_capacity(HEADER_LEN);
+	header.extend_from_slice(ARCHIVE_MAGIC);
+	header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+	header.extend_from_slice(&entry_count.to_le_bytes());
+	header.extend_from_slice(&offset.to_le_bytes());
+	header.extend_from_slice(&(toc.len() as u64).to_le_bytes());
+	header.extend_from_slice(&crc32fast::hash(&toc).to_le_bytes());
+
+	let mut file = writer.into_inner().map_err(|e| io_err(e.into_error()))?;
+	file.seek(SeekFrom::Start(0))
+		.and_then(|_| file.write_all(&header))
+		.and_then(|_| file.sync_all())
+		.map_err(io_err)?;
+
+	Ok(stats)
+}
+
+fn deflate(data: &[u8]) -> std::io::Result<Vec<u8>>
+{
+	let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+	encoder.write_all(data)?;
+	encoder.finish()
+}
+
+// Recursively collect the files in `dir`, along with their logical paths relative to `root`.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<(), EngineError>
+{
+	for entry in std::fs::read_dir(dir).map_err(|e| EngineError::io(dir, e))? {
+		let path = entry.map_err(|e| EngineError::io(dir, e))?.path();
+		if path.is_dir() {
+			collect_files(root, &path, files)?;
+		} else {
+			let relative = path.strip_prefix(root).unwrap_or(&path);
+			let logical_path = relative.iter()
+				.map(|c| c.to_str())
+				.collect::<Option<Vec<_>>>()
+				.ok_or_else(|| EngineError::decode(&path, "path is not valid UTF-8"))?
+				.join("/");
+			files.push((logical_path, path));
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	// an empty directory for the test to put files in
+	fn test_dir(name: &str) -> PathBuf
+	{
+		let dir = std::env::temp_dir().join(format!("mithrilengine-archive-test-{}-{}", std::process::id(), name));
+		std::fs::remove_dir_all(&dir).ok();
+		std::fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+
+	// pack a directory with a few files into an archive next to it
+	fn pack_test_files(dir: &Path, compress: bool) -> PathBuf
+	{
+		let src_dir = dir.join("assets");
+		std::fs::create_dir_all(src_dir.join("maps")).unwrap();
+		std::fs::write(src_dir.join("a.txt"), b"short").unwrap();
+		std::fs::write(src_dir.join("maps/test.yaml"), "entities: []\n".repeat(100)).unwrap();
+
+		let archive_path = dir.join("test.mepk");
+		let stats = pack_directory(&src_dir, &archive_path, compress).unwrap();
+		assert_eq!(stats.file_count, 2);
+		archive_path
+	}
+
+	fn open_err(path: &Path) -> String
+	{
+		match Archive::open(path) {
+			Ok(_) => panic!("archive opened despite being damaged"),
+			Err(e) => e.to_string()
+		}
+	}
+
+	// a table of contents entry for a file stored without compression
+	fn toc_entry(toc: &mut Vec<u8>, path: &str, offset: u64, size: u64)
+	{
+		toc.extend_from_slice(&(path.len() as u16).to_le_bytes());
+		toc.extend_from_slice(path.as_bytes());
+		toc.extend_from_slice(&offset.to_le_bytes());
+		toc.extend_from_slice(&size.to_le_bytes());
+		toc.extend_from_slice(&size.to_le_bytes());
+		toc.push(Compression::None.to_u8());
+		toc.extend_from_slice(&0u32.to_le_bytes());
+	}
+
+	#[test]
+	fn round_trip()
+	{
+		for compress in [ false, true ] {
+			let dir = test_dir(&format!("round_trip_{}", compress));
+			let archive = Archive::open(&pack_test_files(&dir, compress)).unwrap();
+
+			assert_eq!(archive.entries().count(), 2);
+			assert_eq!(archive.read_file("a.txt").unwrap().unwrap(), b"short");
+			assert_eq!(archive.read_file("maps/test.yaml").unwrap().unwrap(), "entities: []\n".repeat(100).as_bytes());
+			assert!(archive.read_file("missing.txt").is_none());
+
+			// only files that get smaller are compressed
+			let expected = match compress {
+				true => Compression::Deflate,
+				false => Compression::None
+			};
+			assert_eq!(archive.entry("maps/test.yaml").unwrap().compression, expected);
+			assert_eq!(archive.entry("a.txt").unwrap().compression, Compression::None);
+			assert!(archive.verify().is_empty());
+			std::fs::remove_dir_all(&dir).ok();
+		}
+	}
+
+	#[test]
+	fn damaged_toc()
+	{
+		let dir = test_dir("damaged_toc");
+		let archive_path = pack_test_files(&dir, true);
+		let mut archive_data = std::fs::read(&archive_path).unwrap();
+		*archive_data.last_mut().unwrap() ^= 0xff;
+		std::fs::write(&archive_path, &archive_data).unwrap();
+
+		let err = open_err(&archive_path);
+		assert!(err.contains("table of contents checksum mismatch"), "{}", err);
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn damaged_entry()
+	{
+		let dir = test_dir("damaged_entry");
+		let archive_path = pack_test_files(&dir, false);
+		let mut archive_data = std::fs::read(&archive_path).unwrap();
+
+		// "a.txt" comes first, since the files are packed in order of their paths
+		archive_data[HEADER_LEN] ^= 0xff;
+		std::fs::write(&archive_path, &archive_data).unwrap();
+
+		let archive = Archive::open(&archive_path).unwrap();
+		assert_eq!(archive.verify(), vec![ "a.txt".to_string() ]);
+		assert!(archive.read_file("a.txt").unwrap().is_err());
+		assert!(archive.read_file("maps/test.yaml").unwrap().is_ok());
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn truncated_archive()
+	{
+		let dir = test_dir("truncated_archive");
+		let archive_path = pack_test_files(&dir, false);
+		let archive_data = std::fs::read(&archive_path).unwrap();
+		std::fs::write(&archive_path, &archive_data[..archive_data.len() - 1]).unwrap();
+
+		let err = open_err(&archive_path);
+		assert!(err.contains("truncated"), "{}", err);
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn entry_out_of_bounds()
+	{
+		let data_end = HEADER_LEN as u64 + 10;
+		let mut toc = Vec::new();
+		toc_entry(&mut toc, "a.txt", HEADER_LEN as u64, 10);
+		assert!(parse_toc(&toc, 1, data_end).is_ok());
+
+		for (offset, size) in [ (HEADER_LEN as u64, 11), (0, 5), (u64::MAX, 2) ] {
+			let mut toc = Vec::new();
+			toc_entry(&mut toc, "a.txt", offset, size);
+			let err = parse_toc(&toc, 1, data_end).unwrap_err();
+			assert!(err.contains("out of bounds"), "{}", err);
+		}
+	}
+
+	#[test]
+	fn duplicate_path()
+	{
+		let mut toc = Vec::new();
+		toc_entry(&mut toc, "a.txt", HEADER_LEN as u64, 5);
+		toc_entry(&mut toc, "a.txt", HEADER_LEN as u64 + 5, 5);
+		let err = parse_toc(&toc, 2, HEADER_LEN as u64 + 10).unwrap_err();
+		assert!(err.contains("more than once"), "{}", err);
+	}
+
+	#[test]
+	fn truncated_toc()
+	{
+		let mut toc = Vec::new();
+		toc_entry(&mut toc, "a.txt", HEADER_LEN as u64, 5);
+		let err = parse_toc(&toc[..toc.len() - 1], 1, HEADER_LEN as u64 + 5).unwrap_err();
+		assert!(err.contains("truncated"), "{}", err);
+		assert!(parse_toc(&toc, 0, HEADER_LEN as u64 + 5).is_err());
+	}
+}
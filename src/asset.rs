@@ -0,0 +1,380 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex, MutexGuard, RwLock };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::mpsc::{ Sender, Receiver };
+use std::thread::JoinHandle;
+use crate::error::EngineError;
+
+/// Something that can be cached by `AssetCache`, such as a texture or a font.
+pub trait Asset: Send + Sync + 'static
+{
+	/// The approximate amount of memory used by this asset in bytes, for asset reports.
+	fn memory_size(&self) -> u64;
+}
+
+/// The state of an asset behind a `Handle`.
+pub enum AssetState<T>
+{
+	/// The asset is being loaded in the background.
+	Pending,
+
+	Loaded(Arc<T>),
+
+	/// The asset couldn't be loaded, for the reason given.
+	Failed(String)
+}
+impl<T> Clone for AssetState<T>
+{
+	fn clone(&self) -> Self
+	{
+		match self {
+			AssetState::Pending => AssetState::Pending,
+			AssetState::Loaded(asset) => AssetState::Loaded(asset.clone()),
+			AssetState::Failed(message) => AssetState::Failed(message.clone())
+		}
+	}
+}
+
+/// Reference-counted handle to an asset, which may still be loading in the background. Cloning the handle doesn't copy
+/// the asset, and the asset can be evicted from the cache it was loaded into once all of its handles have been dropped.
+pub struct Handle<T>
+{
+	inner: Arc<HandleInner<T>>
+}
+struct HandleInner<T>
+{
+	path: Option<String>,
+	state: RwLock<AssetState<T>>,
+	generation: AtomicU64
+}
+impl<T> Handle<T>
+{
+	/// Create a handle to an asset that isn't loaded from a file, such as a texture generated at runtime. It won't be
+	/// in any cache.
+	pub fn new(asset: T) -> Handle<T>
+	{
+		Self::with_state(None, AssetState::Loaded(Arc::new(asset)))
+	}
+
+	fn with_state(path: Option<String>, state: AssetState<T>) -> Handle<T>
+	{
+		Handle{ inner: Arc::new(HandleInner{ path: path, state: RwLock::new(state), generation: AtomicU64::new(0) }) }
+	}
+
+	/// The logical path the asset was loaded from, or `None` if it was created with `new`.
+	pub fn path(&self) -> Option<&str>
+	{
+		self.inner.path.as_deref()
+	}
+
+	pub fn state(&self) -> AssetState<T>
+	{
+		self.inner.state.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+	}
+
+	/// Get the asset if it has been loaded.
+	pub fn get(&self) -> Option<Arc<T>>
+	{
+		match self.state() {
+			AssetState::Loaded(asset) => Some(asset),
+			_ => None
+		}
+	}
+
+	pub fn is_pending(&self) -> bool
+	{
+		matches!(self.state(), AssetState::Pending)
+	}
+
+	pub fn is_loaded(&self) -> bool
+	{
+		matches!(self.state(), AssetState::Loaded(_))
+	}
+
+	pub fn is_failed(&self) -> bool
+	{
+		matches!(self.state(), AssetState::Failed(_))
+	}
+
+	/// A number that changes every time the state of the asset changes. Compare this with the generation from when
+	/// something was created using the asset (such as a descriptor set) to check if it needs to be recreated.
+	pub fn generation(&self) -> u64
+	{
+		self.inner.generation.load(Ordering::Acquire)
+	}
+
+	pub(crate) fn set_state(&self, state: AssetState<T>)
+	{
+		*self.inner.state.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = state;
+		self.inner.generation.fetch_add(1, Ordering::AcqRel);
+	}
+
+	/// The number of handles to this asset, including the one held by the cache.
+	pub fn ref_count(&self) -> usize
+	{
+		Arc::strong_count(&self.inner)
+	}
+
+	/// Check if both handles refer to the same asset.
+	pub fn ptr_eq(a: &Handle<T>, b: &Handle<T>) -> bool
+	{
+		Arc::ptr_eq(&a.inner, &b.inner)
+	}
+}
+impl<T> Clone for Handle<T>
+{
+	fn clone(&self) -> Self
+	{
+		Handle{ inner: self.inner.clone() }
+	}
+}
+
+/// An entry in an asset report.
+#[derive(Clone, Debug)]
+pub struct AssetInfo
+{
+	/// The kind of asset, such as "texture".
+	pub kind: &'static str,
+
+	pub path: String,
+	pub memory_size: u64,
+
+	/// The number of handles to the asset outside of the cache. The asset will be evicted if this is zero when
+	/// `evict_unused` gets called.
+	pub ref_count: usize
+}
+
+/// Cache of assets of one kind, keyed by logical path, which gives out handles to the assets it has loaded. Loading
+/// the same path again returns a handle to the asset that was already loaded, even while it's still being loaded in
+/// the background.
+pub struct AssetCache<T>
+{
+	kind: &'static str,
+	entries: Mutex<HashMap<String, Handle<T>>>
+}
+impl<T: Asset> AssetCache<T>
+{
+	/// Create an empty cache. `kind` describes the kind of asset in log messages and reports, such as "texture".
+	pub fn new(kind: &'static str) -> AssetCache<T>
+	{
+		AssetCache{ kind: kind, entries: Mutex::new(HashMap::new()) }
+	}
+
+	/// Get a handle to the asset at the logical path, calling `load` to load it if it isn't in the cache yet, or if it
+	/// previously failed to load. If the asset is being loaded in the background, the returned handle will be pending.
+	/// If `load` fails, nothing is cached, so the next call will try to load it again.
+	pub fn get_or_load<F>(&self, path: &str, load: F) -> Result<Handle<T>, EngineError>
+		where F: FnOnce() -> Result<T, EngineError>
+	{
+		let failed_handle = match self.lock_entries().get(path) {
+			Some(handle) if handle.is_failed() => Some(handle.clone()),
+			Some(handle) => return Ok(handle.clone()),
+			None => None
+		};
+
+		// the lock isn't held while loading, so that `load` can load other assets from this cache
+		let asset = load()?;
+		log::debug!("Loaded {} '{}' ({} bytes)", self.kind, path, asset.memory_size());
+
+		// reuse the handle of the failed asset, so that things already holding the handle get the new asset
+		let handle = failed_handle.unwrap_or_else(|| Handle::with_state(Some(path.to_string()), AssetState::Pending));
+		handle.set_state(AssetState::Loaded(Arc::new(asset)));
+		self.lock_entries().insert(path.to_string(), handle.clone());
+		Ok(handle)
+	}
+
+	/// Get a handle to the asset at the logical path, or insert a pending handle if it isn't in the cache yet, or if it
+	/// previously failed to load. Returns the handle along with whether it needs to be loaded by the caller, in which
+	/// case the caller should set its state once it's done.
+	pub(crate) fn get_or_insert_pending(&self, path: &str) -> (Handle<T>, bool)
+	{
+		let mut entries = self.lock_entries();
+		match entries.get(path) {
+			Some(handle) if handle.is_failed() => {
+				handle.set_state(AssetState::Pending);
+				(handle.clone(), true)
+			}
+			Some(handle) => (handle.clone(), false),
+			None => {
+				let handle = Handle::with_state(Some(path.to_string()), AssetState::Pending);
+				entries.insert(path.to_string(), handle.clone());
+				(handle, true)
+			}
+		}
+	}
+
+	/// Get a handle to the asset at the logical path if it's in the cache.
+	pub fn get(&self, path: &str) -> Option<Handle<T>>
+	{
+		self.lock_entries().get(path).cloned()
+	}
+
+	/// Remove assets that don't have any handles outside of the cache, returning how many were removed. Assets still
+	/// being loaded are kept, since the loader holds a handle to them.
+	pub fn evict_unused(&self) -> usize
+	{
+		let mut entries = self.lock_entries();
+		let count_before = entries.len();
+		entries.retain(|path, handle| {
+			let unused = handle.ref_count() == 1;
+			if unused {
+				log::debug!("Evicting unused {} '{}'", self.kind, path);
+			}
+			!unused
+		});
+		count_before - entries.len()
+	}
+
+	/// List the loaded assets, sorted by path.
+	pub fn report(&self) -> Vec<AssetInfo>
+	{
+		let mut report: Vec<AssetInfo> = self.lock_entries().iter()
+			.filter_map(|(path, handle)| handle.get().map(|asset| AssetInfo{
+				kind: self.kind,
+				path: path.clone(),
+				memory_size: asset.memory_size(),
+				ref_count: handle.ref_count() - 1
+			}))
+			.collect();
+		report.sort_by(|a, b| a.path.cmp(&b.path));
+		report
+	}
+
+	// Loading doesn't happen while the lock is held, so the entries are still consistent if another thread panicked.
+	fn lock_entries(&self) -> MutexGuard<HashMap<String, Handle<T>>>
+	{
+		self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+}
+
+/// Log the assets in the report along with their total memory size.
+pub fn log_report(report: &[AssetInfo])
+{
+	let total: u64 = report.iter().map(|info| info.memory_size).sum();
+	log::info!("{} asset(s) loaded, {} KiB total:", report.len(), (total + 1023) / 1024);
+	for info in report {
+		log::info!(
+			"{} '{}': {} KiB, {} reference(s)",
+			info.kind, info.path, (info.memory_size + 1023) / 1024, info.ref_count
+		);
+	}
+}
+
+/// Progress of the assets requested to be loaded in the background, for loading screens. The counts start over when
+/// an asset is requested after all of the previously requested ones have finished.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadingProgress
+{
+	pub requested: usize,
+
+	/// The number of requested assets that have finished loading, including the ones that failed.
+	pub finished: usize,
+
+	pub failed: usize
+}
+impl LoadingProgress
+{
+	/// The fraction of requested assets that have finished loading, from 0.0 to 1.0.
+	pub fn fraction(&self) -> f32
+	{
+		match self.requested {
+			0 => 1.0,
+			requested => self.finished as f32 / requested as f32
+		}
+	}
+
+	pub fn is_done(&self) -> bool
+	{
+		self.finished == self.requested
+	}
+
+	pub(crate) fn add_request(&mut self)
+	{
+		if self.is_done() {
+			*self = LoadingProgress::default();
+		}
+		self.requested += 1;
+	}
+
+	pub(crate) fn add_finished(&mut self, failed: bool)
+	{
+		self.finished += 1;
+		if failed {
+			self.failed += 1;
+		}
+	}
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Pool of worker threads that run jobs in the order they were submitted, used to decode assets in the background.
+pub(crate) struct WorkerPool
+{
+	sender: Option<Sender<Job>>,
+	workers: Vec<JoinHandle<()>>
+}
+impl WorkerPool
+{
+	/// Start a pool with one worker for each CPU core except the one the main thread runs on, up to `max_workers`.
+	pub fn new(max_workers: usize) -> WorkerPool
+	{
+		let worker_count = std::thread::available_parallelism()
+			.map(|n| n.get().saturating_sub(1))
+			.unwrap_or(1)
+			.clamp(1, max_workers.max(1));
+		let (sender, receiver) = std::sync::mpsc::channel::<Job>();
+		let receiver = Arc::new(Mutex::new(receiver));
+
+		let mut workers = Vec::with_capacity(worker_count);
+		for i in 0..worker_count {
+			let receiver = receiver.clone();
+			let spawn_result = std::thread::Builder::new()
+				.name(format!("asset worker {}", i))
+				.spawn(move || worker_loop(&receiver));
+			match spawn_result {
+				Ok(worker) => workers.push(worker),
+				Err(e) => log::error!("Failed to start asset worker thread: {}", e)
+			}
+		}
+		log::debug!("Started {} asset worker thread(s)", workers.len());
+
+		WorkerPool{ sender: Some(sender), workers: workers }
+	}
+
+	/// Run the job on one of the worker threads.
+	pub fn spawn(&self, job: impl FnOnce() + Send + 'static)
+	{
+		match self.sender.as_ref().map(|s| s.send(Box::new(job))) {
+			Some(Ok(())) => (),
+			_ => log::error!("Asset worker threads have stopped, job will not be run")
+		}
+	}
+}
+impl Drop for WorkerPool
+{
+	// let the workers finish the jobs that were already submitted, then wait for them to exit
+	fn drop(&mut self)
+	{
+		self.sender = None;
+		for worker in self.workers.drain(..) {
+			worker.join().ok();
+		}
+	}
+}
+
+fn worker_loop(receiver: &Mutex<Receiver<Job>>)
+{
+	loop {
+		// the lock is only held while waiting for a job, so other workers can take jobs while this one runs
+		let job = receiver.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+		match job {
+			Ok(job) => job(),
+			Err(_) => break	// the pool has been dropped
+		}
+	}
+}
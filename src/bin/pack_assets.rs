@@ -0,0 +1,80 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+//! Builds an asset archive from a directory, for mounting with `GameBuilder::with_mount`.
+use std::path::PathBuf;
+use mithrilengine::archive::{ self, Archive };
+
+const USAGE: &str = "\
+Usage: pack_assets [options] <input directory> <output archive>
+
+Options:
+  --store     Store files without compressing them
+  --verify    Read back every file in the archive after building it to check it
+  -h, --help  Print this message and exit";
+
+fn main()
+{
+	let mut compress = true;
+	let mut verify = false;
+	let mut paths = Vec::new();
+	for arg in std::env::args().skip(1) {
+		match arg.as_str() {
+			"--store" => compress = false,
+			"--verify" => verify = true,
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				return
+			}
+			_ if arg.starts_with("--") => {
+				eprintln!("Unknown option '{}'\n\n{}", arg, USAGE);
+				std::process::exit(2)
+			}
+			_ => paths.push(PathBuf::from(arg))
+		}
+	}
+	let (input_dir, mut output_path) = match <[PathBuf; 2]>::try_from(paths) {
+		Ok([input_dir, output_path]) => (input_dir, output_path),
+		Err(_) => {
+			eprintln!("{}", USAGE);
+			std::process::exit(2)
+		}
+	};
+	if output_path.extension().is_none() {
+		output_path.set_extension(archive::ARCHIVE_EXTENSION);
+	}
+
+	let stats = match archive::pack_directory(&input_dir, &output_path, compress) {
+		Ok(s) => s,
+		Err(e) => {
+			eprintln!("Failed to build archive: {}", e);
+			std::process::exit(1)
+		}
+	};
+	println!(
+		"Packed {} file(s) ({} compressed) from '{}' into '{}': {} -> {} bytes",
+		stats.file_count, stats.compressed_count, input_dir.display(), output_path.display(),
+		stats.total_size, stats.stored_size
+	);
+
+	if verify {
+		let damaged = match Archive::open(&output_path) {
+			Ok(a) => a.verify(),
+			Err(e) => {
+				eprintln!("Failed to open archive for verification: {}", e);
+				std::process::exit(1)
+			}
+		};
+		match damaged.is_empty() {
+			true => println!("Verified all files in the archive"),
+			false => {
+				for path in &damaged {
+					eprintln!("Damaged file in archive: {}", path);
+				}
+				std::process::exit(1)
+			}
+		}
+	}
+}
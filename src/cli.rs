@@ -0,0 +1,265 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use log::LevelFilter;
+use crate::render::DeviceSelection;
+use crate::settings::WindowMode;
+
+pub const USAGE: &str = "\
+Options:
+  --map <path>          Load the given map upon startup
+  --windowed            Run in a window, overriding the window mode setting
+  --fullscreen          Run in fullscreen, overriding the window mode setting
+  --borderless          Run in a borderless window, overriding the window mode setting
+  --device <selection>  Select the physical device by index, or by part of its name
+  --log-level <level>   Set the log level for both the terminal and the log file
+                        (off, error, warn, info, debug, trace)
+  --term-log-level <level>
+                        Set the log level for the terminal only
+  --file-log-level <level>
+                        Set the log level for the log file only
+  --log-filter <module>=<level>
+                        Set the log level for a module and its submodules, such as 'vulkano=warn'
+  --headless            Run without a window, rendering offscreen
+  --frames <count>      Number of frames to render with --headless (default: 1)
+  +<name> <value>       Set the console variable <name> to <value>
+  -h, --help            Show this help";
+
+/// Engine options given on the command line. Available to systems as a unique.
+#[derive(Clone, Debug, Default)]
+pub struct EngineOptions
+{
+	pub map: Option<PathBuf>,
+	pub window_mode: Option<WindowMode>,
+	pub device: Option<DeviceSelection>,
+	pub term_log_level: Option<LevelFilter>,
+	pub file_log_level: Option<LevelFilter>,
+	pub log_filters: Vec<(String, LevelFilter)>,
+	pub headless: bool,
+	pub headless_frames: Option<u32>,
+	cvars: BTreeMap<String, String>
+}
+impl EngineOptions
+{
+	/// Parse engine options from the command line arguments of this process.
+	pub fn from_env() -> Result<EngineOptions, CliError>
+	{
+		Self::parse(std::env::args().skip(1))
+	}
+
+	/// Parse engine options from the given arguments, which should not include the program name.
+	pub fn parse<I, S>(args: I) -> Result<EngineOptions, CliError>
+		where
+			I: IntoIterator<Item = S>,
+			S: Into<String>
+	{
+		let mut options = EngineOptions::default();
+		let mut args = args.into_iter().map(|a| a.into());
+		let mut window_mode_arg = None;	// the option that set the window mode, for reporting conflicts
+
+		while let Some(arg) = args.next() {
+			match arg.as_str() {
+				"-h" | "--help" => return Err(CliError::HelpRequested),
+				"--map" => options.map = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+				"--windowed" => set_window_mode(&mut options, &mut window_mode_arg, arg, WindowMode::Windowed)?,
+				"--fullscreen" => set_window_mode(&mut options, &mut window_mode_arg, arg, WindowMode::Fullscreen)?,
+				"--borderless" => set_window_mode(&mut options, &mut window_mode_arg, arg, WindowMode::Borderless)?,
+				"--device" => options.device = Some(DeviceSelection::parse(&next_value(&mut args, &arg)?)),
+				"--log-level" => {
+					let level = parse_level(&arg, next_value(&mut args, &arg)?)?;
+					options.term_log_level = Some(level);
+					options.file_log_level = Some(level);
+				}
+				"--term-log-level" => options.term_log_level = Some(parse_level(&arg, next_value(&mut args, &arg)?)?),
+				"--file-log-level" => options.file_log_level = Some(parse_level(&arg, next_value(&mut args, &arg)?)?),
+				"--log-filter" => {
+					let value = next_value(&mut args, &arg)?;
+					let (module, level_str) = value.split_once('=')
+						.ok_or_else(|| CliError::InvalidValue{ option: arg.clone(), value: value.clone(), reason: "expected <module>=<level>" })?;
+					let level = parse_level(&arg, level_str.to_string())?;
+					options.log_filters.push((module.to_string(), level));
+				}
+				"--headless" => options.headless = true,
+				"--frames" => {
+					let value = next_value(&mut args, &arg)?;
+					let frames = value.parse()
+						.map_err(|_| CliError::InvalidValue{ option: arg.clone(), value: value, reason: "not a number" })?;
+					options.headless_frames = Some(frames);
+				}
+				_ => match arg.strip_prefix('+') {
+					Some(name) if !name.is_empty() => {
+						let value = next_value(&mut args, &arg)?;
+						options.cvars.insert(name.to_string(), value);
+					}
+					_ => return Err(CliError::UnknownOption(arg.clone()))
+				}
+			}
+		}
+
+		if options.headless_frames.is_some() && !options.headless {
+			return Err(CliError::RequiresOption{ option: "--frames".to_string(), required: "--headless" })
+		}
+
+		Ok(options)
+	}
+
+	/// Get the value of a console variable set with `+<name> <value>`.
+	pub fn cvar(&self, name: &str) -> Option<&str>
+	{
+		self.cvars.get(name).map(|v| v.as_str())
+	}
+
+	/// Get the value of a console variable, parsed as `T`. Returns `None` if the variable isn't set or can't be parsed.
+	pub fn cvar_parsed<T: FromStr>(&self, name: &str) -> Option<T>
+	{
+		self.cvar(name).and_then(|v| v.parse().ok())
+	}
+
+	/// Iterate over all console variables, in alphabetical order.
+	pub fn cvars(&self) -> impl Iterator<Item = (&str, &str)>
+	{
+		self.cvars.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+	}
+}
+
+fn set_window_mode(options: &mut EngineOptions, window_mode_arg: &mut Option<String>, arg: String, mode: WindowMode)
+	-> Result<(), CliError>
+{
+	match window_mode_arg {
+		Some(prev) if *prev != arg => return Err(CliError::ConflictingOptions(prev.clone(), arg)),
+		_ => ()
+	}
+	options.window_mode = Some(mode);
+	*window_mode_arg = Some(arg);
+	Ok(())
+}
+
+fn parse_level(option: &str, value: String) -> Result<LevelFilter, CliError>
+{
+	LevelFilter::from_str(&value)
+		.map_err(|_| CliError::InvalidValue{ option: option.to_string(), value: value, reason: "not a log level" })
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, CliError>
+{
+	args.next().ok_or_else(|| CliError::MissingValue(option.to_string()))
+}
+
+#[derive(Debug)]
+pub enum CliError
+{
+	HelpRequested,
+	UnknownOption(String),
+	MissingValue(String),
+	InvalidValue{ option: String, value: String, reason: &'static str },
+	ConflictingOptions(String, String),
+	RequiresOption{ option: String, required: &'static str }
+}
+impl std::error::Error for CliError {}
+impl std::fmt::Display for CliError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			CliError::HelpRequested => write!(f, "{}", USAGE),
+			CliError::UnknownOption(o) => write!(f, "unknown option '{}'\n\n{}", o, USAGE),
+			CliError::MissingValue(o) => write!(f, "option '{}' requires a value\n\n{}", o, USAGE),
+			CliError::InvalidValue{ option, value, reason } => {
+				write!(f, "invalid value '{}' for option '{}': {}", value, option, reason)
+			}
+			CliError::ConflictingOptions(a, b) => write!(f, "options '{}' and '{}' can't be used together", a, b),
+			CliError::RequiresOption{ option, required } => {
+				write!(f, "option '{}' can only be used along with '{}'", option, required)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn cvars()
+	{
+		let options = EngineOptions::parse([ "+sv_gravity", "800", "--windowed", "+player_name", "two words" ]).unwrap();
+		assert_eq!(options.cvar("sv_gravity"), Some("800"));
+		assert_eq!(options.cvar_parsed::<u32>("sv_gravity"), Some(800));
+		assert_eq!(options.cvar("player_name"), Some("two words"));
+		assert_eq!(options.cvar_parsed::<u32>("player_name"), None);
+		assert_eq!(options.cvar("unset"), None);
+		assert_eq!(options.cvars().count(), 2);
+	}
+
+	#[test]
+	fn missing_values()
+	{
+		for option in [ "--map", "--device", "--log-level", "+cvar" ] {
+			match EngineOptions::parse([ option ]) {
+				Err(CliError::MissingValue(o)) => assert_eq!(o, option),
+				other => panic!("expected missing value for '{}', got {:?}", option, other)
+			}
+		}
+	}
+
+	#[test]
+	fn unknown_option_shows_usage()
+	{
+		let err = EngineOptions::parse([ "--map", "maps/test.yaml", "--fast" ]).unwrap_err();
+		assert!(matches!(&err, CliError::UnknownOption(o) if o == "--fast"));
+		assert!(err.to_string().contains(USAGE));
+
+		assert!(matches!(EngineOptions::parse([ "+" ]), Err(CliError::UnknownOption(_))));
+	}
+
+	#[test]
+	fn invalid_values()
+	{
+		assert!(matches!(EngineOptions::parse([ "--log-level", "loud" ]), Err(CliError::InvalidValue{ .. })));
+		assert!(matches!(EngineOptions::parse([ "--log-filter", "vulkano" ]), Err(CliError::InvalidValue{ .. })));
+		assert!(matches!(EngineOptions::parse([ "--headless", "--frames", "ten" ]), Err(CliError::InvalidValue{ .. })));
+	}
+
+	#[test]
+	fn window_modes()
+	{
+		let options = EngineOptions::parse([ "--borderless" ]).unwrap();
+		assert_eq!(options.window_mode, Some(WindowMode::Borderless));
+
+		// repeating the same one is harmless
+		let options = EngineOptions::parse([ "--fullscreen", "--fullscreen" ]).unwrap();
+		assert_eq!(options.window_mode, Some(WindowMode::Fullscreen));
+
+		match EngineOptions::parse([ "--windowed", "--fullscreen" ]) {
+			Err(CliError::ConflictingOptions(a, b)) => {
+				assert_eq!(a, "--windowed");
+				assert_eq!(b, "--fullscreen");
+			}
+			other => panic!("expected conflicting options, got {:?}", other)
+		}
+	}
+
+	#[test]
+	fn frames_requires_headless()
+	{
+		assert!(matches!(EngineOptions::parse([ "--frames", "10" ]), Err(CliError::RequiresOption{ .. })));
+
+		let options = EngineOptions::parse([ "--frames", "10", "--headless" ]).unwrap();
+		assert!(options.headless);
+		assert_eq!(options.headless_frames, Some(10));
+	}
+
+	#[test]
+	fn log_levels()
+	{
+		let args = [ "--log-level", "warn", "--file-log-level", "trace", "--log-filter", "vulkano=error" ];
+		let options = EngineOptions::parse(args).unwrap();
+		assert_eq!(options.term_log_level, Some(LevelFilter::Warn));
+		assert_eq!(options.file_log_level, Some(LevelFilter::Trace));
+		assert_eq!(options.log_filters, vec![ ("vulkano".to_string(), LevelFilter::Error) ]);
+	}
+}
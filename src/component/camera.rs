@@ -3,43 +3,86 @@
 
 	Copyright (c) 2021-2022, daigennki (@daigennki)
 ----------------------------------------------------------------------------- */
-use std::sync::Arc;
 use glam::*;
-use vulkano::descriptor_set::persistent::PersistentDescriptorSet;
-use vulkano::descriptor_set::WriteDescriptorSet;
-use vulkano::buffer::BufferUsage;
+use serde::Deserialize;
 use crate::render::RenderContext;
+use crate::render::uniform_ring::UniformRing;
+use crate::error::EngineError;
+
+/// What a camera placed in a map looks at.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum CameraTarget
+{
+	/// A fixed position.
+	Position(Vec3),
+
+	/// The entity with the given name, such as "client" for the player.
+	Entity(String)
+}
+
+/// A camera placed in a map, which the game can switch the active `Camera` to.
+pub struct MapCamera
+{
+	pub pos: Vec3,
+	pub target: CameraTarget
+}
 
 pub struct Camera
 {
-	descriptor_set: Arc<PersistentDescriptorSet>,
+	buf: UniformRing,
+	view: Mat4,
 	projview: Mat4
 }
 impl Camera
 {
-	pub fn new(render_ctx: &mut RenderContext, pos: Vec3, target: Vec3) -> Result<Camera, Box<dyn std::error::Error>>
+	pub fn new(render_ctx: &mut RenderContext, pos: Vec3, target: Vec3) -> Result<Camera, EngineError>
 	{
-		// Create a camera facing `target` from `pos` with 1 radians vertical FOV.
-		// TODO: use actual window aspect ratio rather than a constant
-		// TODO: adjust near/far values to be more sensible
-		let proj = Mat4::perspective_lh(1.0, 16.0 / 9.0, 0.01, 1000.0);
+		// Create a camera facing `target` from `pos`, with the aspect ratio of the window.
 		let view = Mat4::look_at_lh(pos, target, Vec3::Z);
-		let projview = proj * view;
-		let projview_buf = render_ctx.new_buffer(projview.to_cols_array(), BufferUsage::uniform_buffer())?;
+		let projview = create_projview(render_ctx, view);
 
 		Ok(Camera{
-			descriptor_set: render_ctx.new_descriptor_set("World", 1, [
-				WriteDescriptorSet::buffer(0, projview_buf.clone())
-			])?,
+			buf: UniformRing::new(render_ctx, &projview.to_cols_array(), "World", 1, 0)?,
+			view: view,
 			projview: projview
 		})
 	}
 
+	/// Move the camera to `pos`, facing `target`. This can be called every frame, such as to follow the player.
+	pub fn look_at(&mut self, render_ctx: &RenderContext, pos: Vec3, target: Vec3) -> Result<(), EngineError>
+	{
+		self.view = Mat4::look_at_lh(pos, target, Vec3::Z);
+		self.update_projview(render_ctx)
+	}
+
+	/// Update the projection to match the aspect ratio of the window. This gets called for the active camera when the
+	/// window gets resized.
+	pub fn update_aspect_ratio(&mut self, render_ctx: &RenderContext) -> Result<(), EngineError>
+	{
+		self.update_projview(render_ctx)
+	}
+
+	fn update_projview(&mut self, render_ctx: &RenderContext) -> Result<(), EngineError>
+	{
+		self.projview = create_projview(render_ctx, self.view);
+		self.buf.set_data(&self.projview.to_cols_array())
+	}
+
 	/// Bind this camera's projection and view matrices so they can be used in shaders.
-	pub fn bind(&self, render_ctx: &mut RenderContext) -> Result<(), crate::render::PipelineNotLoaded>
+	pub fn bind(&mut self, render_ctx: &mut RenderContext) -> Result<(), EngineError>
 	{
 		// this must be bound as descriptor set 1
-		render_ctx.bind_descriptor_set(1, self.descriptor_set.clone())
+		self.buf.bind(render_ctx, 1)
 	}
 }
 
+// Create the combined projection and view matrix with 1 radian vertical FOV.
+fn create_projview(render_ctx: &RenderContext, view: Mat4) -> Mat4
+{
+	// TODO: adjust near/far values to be more sensible
+	let dim = render_ctx.swapchain_dimensions();
+	let aspect_ratio = dim[0] as f32 / dim[1].max(1) as f32;
+	let proj = Mat4::perspective_lh(1.0, aspect_ratio, 0.01, 1000.0);
+	proj * view
+}
@@ -0,0 +1,41 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use glam::*;
+
+/// Light that shines in one direction from infinitely far away, such as the sun.
+pub struct DirectionalLight
+{
+	/// Rotation on the X, Y, and Z axes in degrees.
+	pub angle: Vec3,
+
+	/// Color in RGB, with intensity in the alpha component.
+	pub color: Vec4
+}
+
+/// Light that shines in all directions from the position of its entity's `Transform`.
+pub struct PointLight
+{
+	/// Color in RGB, with intensity in the alpha component.
+	pub color: Vec4,
+	pub range: f32
+}
+
+/// Light that shines in a cone from the position of its entity's `Transform`.
+pub struct SpotLight
+{
+	/// Rotation on the X, Y, and Z axes in degrees.
+	pub angle: Vec3,
+
+	/// Color in RGB, with intensity in the alpha component.
+	pub color: Vec4,
+	pub range: f32,
+
+	/// Angle in degrees from the center of the cone where the light starts to fade out.
+	pub inner_cutoff: f32,
+
+	/// Angle in degrees from the center of the cone where the light fades out completely.
+	pub outer_cutoff: f32
+}
@@ -5,10 +5,12 @@
 ----------------------------------------------------------------------------- */
 use std::sync::Arc;
 use glam::*;
-use vulkano::buffer::{ ImmutableBuffer, BufferUsage };
+use vulkano::buffer::{ ImmutableBuffer, BufferUsage, TypedBufferAccess };
 use vulkano::descriptor_set::{ WriteDescriptorSet, PersistentDescriptorSet };
 use crate::vertex::*;
 use crate::render::RenderContext;
+use crate::error::EngineError;
+use crate::asset::Asset;
 
 pub struct Mesh
 {
@@ -21,7 +23,7 @@ pub struct Mesh
 impl Mesh
 {
 	// TODO: set material
-	pub fn new(render_ctx: &mut RenderContext, color: Vec4) -> Result<Mesh, Box<dyn std::error::Error>>
+	pub fn new(render_ctx: &mut RenderContext, color: Vec4) -> Result<Mesh, EngineError>
 	{
 		let pos_verts = vec![ 
 			Vertex3::new(-1.0, -0.5, 0.0),
@@ -52,7 +54,7 @@ impl Mesh
 		})
 	}
 
-	pub fn draw(&self, render_ctx: &mut RenderContext) -> Result<(), Box<dyn std::error::Error>>
+	pub fn draw(&self, render_ctx: &mut RenderContext) -> Result<(), EngineError>
 	{
 		render_ctx.bind_descriptor_set(2, self.mat_set.clone())?;
 		render_ctx.bind_vertex_buffers(0, (self.pos_vert_buf.clone(), self.uv_vert_buf.clone()));
@@ -61,3 +63,14 @@ impl Mesh
 		Ok(())
 	}
 }
+impl Asset for Mesh
+{
+	// the size of the vertex and index buffers
+	fn memory_size(&self) -> u64
+	{
+		let pos_size = self.pos_vert_buf.len() * std::mem::size_of::<Vertex3>() as u64;
+		let uv_size = self.uv_vert_buf.len() * std::mem::size_of::<Vertex2>() as u64;
+		let index_size = self.index_buf.len() * std::mem::size_of::<u32>() as u64;
+		pos_size + uv_size + index_size
+	}
+}
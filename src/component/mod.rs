@@ -6,27 +6,26 @@
 pub mod ui;
 pub mod mesh;
 pub mod camera;
+pub mod light;
+pub mod model;
 
-use std::sync::Arc;
 use glam::*;
-use vulkano::buffer::{ /*ImmutableBuffer,*/ BufferUsage, cpu_access::CpuAccessibleBuffer };
-use vulkano::descriptor_set::persistent::PersistentDescriptorSet;
-use vulkano::descriptor_set::WriteDescriptorSet;
 use crate::render::RenderContext;
+use crate::render::uniform_ring::UniformRing;
+use crate::error::EngineError;
 
 pub struct Transform
 {
 	// TODO: parent-child relationship
 	// TODO: maybe we should use immutable buffers but only for static objects...
-	buf: Arc<CpuAccessibleBuffer<[f32]>>,
-	descriptor_set: Arc<PersistentDescriptorSet>,
+	buf: UniformRing,
 	pos: Vec3,
 	scale: Vec3,
 	rot: Vec3	// rotation on X, Y, and Z axes
 }
 impl Transform
 {
-	pub fn new(render_ctx: &mut RenderContext, pos: Vec3, scale: Vec3) -> Result<Transform, Box<dyn std::error::Error>>
+	pub fn new(render_ctx: &mut RenderContext, pos: Vec3, scale: Vec3) -> Result<Transform, EngineError>
 	{
 		let rot = Vec3::ZERO;
 		let rot_quat = Quat::from_euler(EulerRot::XYZ, rot.x, rot.y, rot.z);
@@ -35,20 +34,16 @@ impl Transform
 			rot_quat,
 			pos
 		);
-		let buf = render_ctx.new_cpu_buffer(transform_mat.to_cols_array(), BufferUsage::uniform_buffer())?;
 
 		Ok(Transform{ 
-			buf: buf.clone(),
-			descriptor_set: render_ctx.new_descriptor_set("World", 0, [
-				WriteDescriptorSet::buffer(0, buf)
-			])?, 
+			buf: UniformRing::new(render_ctx, &transform_mat.to_cols_array(), "World", 0, 0)?,
 			pos: pos, 
 			scale: scale,
 			rot: rot
 		})
 	}
 
-	fn update_buffer(&mut self) -> Result<(), Box<dyn std::error::Error>>
+	fn update_buffer(&mut self) -> Result<(), EngineError>
 	{
 		let transform_mat = Mat4::from_scale_rotation_translation(
 			self.scale,
@@ -56,26 +51,24 @@ impl Transform
 			self.pos
 		);
 
-		self.buf.write()?.clone_from_slice(&transform_mat.to_cols_array());
-			
-		Ok(())
+		self.buf.set_data(&transform_mat.to_cols_array())
 	}
 
-	pub fn set_pos(&mut self, pos: Vec3) -> Result<(), Box<dyn std::error::Error>>
+	pub fn set_pos(&mut self, pos: Vec3) -> Result<(), EngineError>
 	{
 		self.pos = pos;
 		self.update_buffer()
 	}
 
-	pub fn bind_descriptor_set(&self, render_ctx: &mut RenderContext) -> Result<(), crate::render::PipelineNotLoaded>
+	pub fn bind_descriptor_set(&mut self, render_ctx: &mut RenderContext) -> Result<(), EngineError>
 	{
-		render_ctx.bind_descriptor_set(0, self.descriptor_set.clone())
+		self.buf.bind(render_ctx, 0)
 	}
 }
 
 /// Convenience function: create a tuple of `Transform` and `Mesh` to display a simple triangle.
 pub fn new_triangle(render_ctx: &mut RenderContext, pos: Vec3, scale: Vec3, color: Vec4)
-	-> Result<(Transform, mesh::Mesh), Box<dyn std::error::Error>>
+	-> Result<(Transform, mesh::Mesh), EngineError>
 {
 	let tri_transform = Transform::new(render_ctx, pos, scale)?;
 	let tri_mesh = mesh::Mesh::new(render_ctx, color)?;
@@ -0,0 +1,25 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::path::PathBuf;
+
+/// Reference to a model file to be displayed at the position of the entity's `Transform`.
+/// TODO: load IQM models into `Mesh` components
+pub struct Model
+{
+	file: PathBuf
+}
+impl Model
+{
+	pub fn new(file: PathBuf) -> Model
+	{
+		Model{ file: file }
+	}
+
+	pub fn file(&self) -> &std::path::Path
+	{
+		&self.file
+	}
+}
@@ -6,6 +6,7 @@
 use std::collections::LinkedList;
 use shipyard::EntityId;
 use glam::*;
+use crate::error::EngineError;
 
 pub struct Canvas
 {
@@ -14,13 +15,16 @@ pub struct Canvas
 }
 impl Canvas
 {
-	pub fn new(width: u32, height: u32) -> Result<Canvas, Box<dyn std::error::Error>>
+	pub fn new(width: u32, height: u32) -> Result<Canvas, EngineError>
 	{
-		let half_width = width as f32 / 2.0;
-		let half_height = height as f32 / 2.0;
-		let projection = Mat4::orthographic_lh(-half_width, half_width, -half_height, half_height, 0.0, 1.0);
+		Ok(Canvas{ children: LinkedList::new(), projection: create_projection(width, height) })
+	}
 
-		Ok(Canvas{ children: LinkedList::new(), projection: projection })
+	/// Change the dimensions of the canvas, such as when the window gets resized. The projection of UI `Transform`
+	/// components must be updated afterwards.
+	pub fn resize(&mut self, width: u32, height: u32)
+	{
+		self.projection = create_projection(width, height);
 	}
 
 	pub fn projection(&self) -> Mat4
@@ -33,3 +37,11 @@ impl Canvas
 		self.children.push_back(eid);
 	}
 }
+
+// Create an orthographic projection with one unit per pixel, with the origin at the center of the canvas.
+fn create_projection(width: u32, height: u32) -> Mat4
+{
+	let half_width = width as f32 / 2.0;
+	let half_height = height as f32 / 2.0;
+	Mat4::orthographic_lh(-half_width, half_width, -half_height, half_height, 0.0, 1.0)
+}
@@ -7,10 +7,13 @@ use std::sync::Arc;
 use vulkano::buffer::{ ImmutableBuffer, BufferUsage };
 use vulkano::descriptor_set::persistent::PersistentDescriptorSet;
 use vulkano::descriptor_set::WriteDescriptorSet;
+use vulkano::image::ImageDimensions;
 use glam::*;
 use crate::vertex::*;
 use crate::render::texture::Texture;
+use crate::asset::Handle;
 use crate::render::RenderContext;
+use crate::error::EngineError;
 
 /// UI component that renders to a mesh, such as a quad, or a background frame mesh.
 pub struct Mesh
@@ -18,42 +21,28 @@ pub struct Mesh
 	pos_vert_buf: Arc<ImmutableBuffer<[Vertex2]>>,
 	uv_vert_buf: Arc<ImmutableBuffer<[Vertex2]>>,
 	descriptor_set: Arc<PersistentDescriptorSet>,
+
+	// held so that the texture doesn't get evicted from the cache while it's being displayed
+	texture: Handle<Texture>,
+
+	// generation of `texture` that the descriptor set was created with
+	texture_generation: u64,
+
+	// whether the quad gets resized to the dimensions of the texture, as with `new`
+	fit_to_texture: bool
 }
 impl Mesh
 {
-	pub fn new(render_ctx: &mut RenderContext, tex: Texture) -> Result<Mesh, Box<dyn std::error::Error>>
+	/// Create a quad with the same size as the texture. If the texture is still loading, the placeholder texture is
+	/// shown until `update_texture` gets called after it has loaded.
+	pub fn new(render_ctx: &mut RenderContext, tex: Handle<Texture>) -> Result<Mesh, EngineError>
 	{
-		// vertex data
-		let mut pos_verts = [
-			Vertex2::new(0.0, 0.0),
-			Vertex2::new(1.0, 0.0),
-			Vertex2::new(0.0, 1.0),
-			Vertex2::new(1.0, 1.0)
-		];
-		let uv_verts = pos_verts;
-
-		// resize position vertices according to texture dimensions
-		let dimensions_uvec2: UVec2 = tex.dimensions().width_height().into();
-		let dimensions = dimensions_uvec2.as_vec2();
-		let half_dimensions = dimensions / 2.0;
-		for pos in &mut pos_verts {
-			let pos_clone = pos.clone();
-			let x = pos_clone.x * dimensions.x - half_dimensions.x;
-			let y = pos_clone.y * dimensions.y - half_dimensions.y;
-			*pos = Vertex2::new(x, y);
-		}
-
-		Ok(Mesh{
-			descriptor_set: render_ctx.new_descriptor_set(
-				"UI", 1, [ WriteDescriptorSet::image_view(0, tex.view()) ]
-			)?,
-			pos_vert_buf: render_ctx.new_buffer(pos_verts, BufferUsage::vertex_buffer())?,
-			uv_vert_buf: render_ctx.new_buffer(uv_verts, BufferUsage::vertex_buffer())?,
-		})
+		let pos_verts = fit_to_dimensions(render_ctx.texture_or_placeholder(&tex).dimensions());
+		Self::new_common(render_ctx, pos_verts, tex, true)
 	}
 
-	pub fn new_from_corners(render_ctx: &mut RenderContext, top_left: Vec2, bottom_right: Vec2, tex: Texture)
-		-> Result<Mesh, Box<dyn std::error::Error>>
+	pub fn new_from_corners(render_ctx: &mut RenderContext, top_left: Vec2, bottom_right: Vec2, tex: Handle<Texture>)
+		-> Result<Mesh, EngineError>
 	{
 		// vertex data
 		let pos_verts: [Vertex2; 4] = [
@@ -62,23 +51,65 @@ impl Mesh
 			Vertex2::new(top_left.x, bottom_right.y),
 			Vertex2::new_from_vec2(bottom_right)
 		];
+		Self::new_common(render_ctx, pos_verts, tex, false)
+	}
+
+	fn new_common(render_ctx: &mut RenderContext, pos_verts: [Vertex2; 4], tex: Handle<Texture>, fit_to_texture: bool)
+		-> Result<Mesh, EngineError>
+	{
 		let uv_verts = [
 			Vertex2::new(0.0, 0.0),
 			Vertex2::new(1.0, 0.0),
 			Vertex2::new(0.0, 1.0),
 			Vertex2::new(1.0, 1.0)
 		];
+		let texture_generation = tex.generation();
+		let current_tex = render_ctx.texture_or_placeholder(&tex);
 
 		Ok(Mesh{
 			descriptor_set: render_ctx.new_descriptor_set(
-				"UI", 1, [ WriteDescriptorSet::image_view(0, tex.view()) ]
+				"UI", 1, [ WriteDescriptorSet::image_view(0, current_tex.view()) ]
 			)?,
 			pos_vert_buf: render_ctx.new_buffer(pos_verts, BufferUsage::vertex_buffer())?,
 			uv_vert_buf: render_ctx.new_buffer(uv_verts, BufferUsage::vertex_buffer())?,
+			texture: tex,
+			texture_generation: texture_generation,
+			fit_to_texture: fit_to_texture
 		})
 	}
 
-	pub fn draw(&self, render_ctx: &mut RenderContext) -> Result<(), Box<dyn std::error::Error>>
+	pub fn texture(&self) -> &Handle<Texture>
+	{
+		&self.texture
+	}
+
+	/// Recreate the descriptor set if the texture has changed state since it was created, such as by finishing loading
+	/// in the background. This gets called for every UI mesh once every frame.
+	pub fn update_texture(&mut self, render_ctx: &mut RenderContext) -> Result<(), EngineError>
+	{
+		let texture_generation = self.texture.generation();
+		if texture_generation == self.texture_generation {
+			return Ok(())
+		}
+
+		// the old descriptor set and vertex buffer may still be in use by a frame in flight
+		let current_tex = render_ctx.texture_or_placeholder(&self.texture);
+		let new_descriptor_set = render_ctx.new_descriptor_set(
+			"UI", 1, [ WriteDescriptorSet::image_view(0, current_tex.view()) ]
+		)?;
+		let old_descriptor_set = std::mem::replace(&mut self.descriptor_set, new_descriptor_set);
+		render_ctx.destroy_after_frame(old_descriptor_set);
+		if self.fit_to_texture {
+			let pos_verts = fit_to_dimensions(current_tex.dimensions());
+			let new_pos_vert_buf = render_ctx.new_buffer(pos_verts, BufferUsage::vertex_buffer())?;
+			let old_pos_vert_buf = std::mem::replace(&mut self.pos_vert_buf, new_pos_vert_buf);
+			render_ctx.destroy_after_frame(old_pos_vert_buf);
+		}
+		self.texture_generation = texture_generation;
+		Ok(())
+	}
+
+	pub fn draw(&self, render_ctx: &mut RenderContext) -> Result<(), EngineError>
 	{
 		render_ctx.bind_descriptor_set(1, self.descriptor_set.clone())?;
 		render_ctx.bind_vertex_buffers(0, (self.pos_vert_buf.clone(), self.uv_vert_buf.clone()));
@@ -86,3 +117,26 @@ impl Mesh
 		Ok(())
 	}
 }
+
+/// Get the vertex positions for a quad centered on the origin with the given texture dimensions.
+fn fit_to_dimensions(texture_dimensions: ImageDimensions) -> [Vertex2; 4]
+{
+	let mut pos_verts = [
+		Vertex2::new(0.0, 0.0),
+		Vertex2::new(1.0, 0.0),
+		Vertex2::new(0.0, 1.0),
+		Vertex2::new(1.0, 1.0)
+	];
+
+	// resize position vertices according to texture dimensions
+	let dimensions_uvec2: UVec2 = texture_dimensions.width_height().into();
+	let dimensions = dimensions_uvec2.as_vec2();
+	let half_dimensions = dimensions / 2.0;
+	for pos in &mut pos_verts {
+		let pos_clone = pos.clone();
+		let x = pos_clone.x * dimensions.x - half_dimensions.x;
+		let y = pos_clone.y * dimensions.y - half_dimensions.y;
+		*pos = Vertex2::new(x, y);
+	}
+	pos_verts
+}
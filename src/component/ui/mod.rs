@@ -13,6 +13,8 @@ use vulkano::descriptor_set::persistent::PersistentDescriptorSet;
 use vulkano::descriptor_set::WriteDescriptorSet;
 use glam::*;
 use crate::render::RenderContext;
+use crate::localization::Localization;
+use crate::error::EngineError;
 
 pub struct Transform
 {
@@ -29,16 +31,16 @@ impl Transform
 		Transform{ descriptor_set: None, proj: None, pos: pos, scale: scale }
 	}
 
-	pub fn bind_descriptor_set(&self, render_ctx: &mut RenderContext) -> Result<(), Box<dyn std::error::Error>>
+	pub fn bind_descriptor_set(&self, render_ctx: &mut RenderContext) -> Result<(), EngineError>
 	{
 		let descriptor_set_ref = self.descriptor_set.as_ref()
-			.ok_or("ui::Transform descriptor set bound before it was set up!")?;
+			.ok_or_else(|| EngineError::ecs("ui::Transform", "descriptor set bound before it was set up"))?;
 		render_ctx.bind_descriptor_set(0, descriptor_set_ref.clone())?;
 		Ok(())
 	}
 
 	pub fn update_projection(&mut self, render_ctx: &mut RenderContext, proj: Mat4)
-		-> Result<(), Box<dyn std::error::Error>>
+		-> Result<(), EngineError>
 	{
 		self.proj = Some(proj);
 		self.descriptor_set = Some(update_matrix(render_ctx, proj, self.pos, self.scale)?);
@@ -47,7 +49,7 @@ impl Transform
 }
 
 fn update_matrix(render_ctx: &mut RenderContext, proj: Mat4, pos: IVec2, scale: Vec2) 
-	-> Result<Arc<PersistentDescriptorSet>, Box<dyn std::error::Error>>
+	-> Result<Arc<PersistentDescriptorSet>, EngineError>
 {
 	let projected = proj * Mat4::from_scale_rotation_translation(
 		scale.extend(0.0), 
@@ -63,11 +65,12 @@ fn update_matrix(render_ctx: &mut RenderContext, proj: Mat4, pos: IVec2, scale:
 }
 
 /// Convenience function: create a tuple of `Transform` and `Mesh` to display an image loaded from a file on the UI.
+/// The image gets loaded in the background, with the placeholder texture shown until it has loaded.
 pub fn new_image(render_ctx: &mut RenderContext, path: &str, pos: IVec2) 
-	-> Result<(Transform, mesh::Mesh), Box<dyn std::error::Error>>
+	-> Result<(Transform, mesh::Mesh), EngineError>
 {
 	let img_transform = Transform::new(pos, [ 1.0, 1.0 ].into());
-	let img_tex = render_ctx.new_texture(std::path::Path::new(path))?;
+	let img_tex = render_ctx.load_texture_async(std::path::Path::new(path))?;
 	let img_mesh = mesh::Mesh::new(render_ctx, img_tex)?;
 
 	Ok((img_transform, img_mesh))
@@ -75,10 +78,26 @@ pub fn new_image(render_ctx: &mut RenderContext, path: &str, pos: IVec2)
 
 /// Convenience function: create a tuple of `Transform` and `Text` to display text.
 pub fn new_text(render_ctx: &mut RenderContext, text_str: &str, size: f32, pos: IVec2) 
-	-> Result<(Transform, text::Text), Box<dyn std::error::Error>>
+	-> Result<(Transform, text::Text), EngineError>
 {
 	let text_transform = Transform::new(pos, [ 1.0, 1.0 ].into());
 	let text_mesh = text::Text::new(render_ctx, text_str, size)?;
 
 	Ok((text_transform, text_mesh))
 }
+
+/// Convenience function: create a tuple of `Transform` and `Text` to display a localized string.
+pub fn new_localized_text(
+	render_ctx: &mut RenderContext, 
+	localization: &Localization, 
+	key: &str, 
+	args: &[(&str, &str)], 
+	size: f32, 
+	pos: IVec2
+) -> Result<(Transform, text::Text), EngineError>
+{
+	let text_transform = Transform::new(pos, [ 1.0, 1.0 ].into());
+	let text_mesh = text::Text::new_localized(render_ctx, localization, key, args, size)?;
+
+	Ok((text_transform, text_mesh))
+}
@@ -3,13 +3,20 @@
 
 	Copyright (c) 2021-2022, daigennki (@daigennki)
 ----------------------------------------------------------------------------- */
+use std::path::Path;
 use vulkano::format::Format;
 use vulkano::image::{ ImageDimensions, MipmapsCount };
 use image::{ DynamicImage, Rgba };
-use rusttype::{ point, Font, Scale };
+use rusttype::{ point, Scale };
 use glam::*;
 use super::mesh::Mesh;
 use crate::render::RenderContext;
+use crate::localization::Localization;
+use crate::error::EngineError;
+use crate::asset::Handle;
+
+// logical path of the font in the engine's data, which games can replace by providing a font at the same path
+const FONT_PATH: &str = "fonts/mplus-1m-medium.ttf";
 
 /// UI component that rasterizes fonts into textures.
 pub struct Text
@@ -19,15 +26,17 @@ pub struct Text
 }
 impl Text
 {
-	pub fn new(render_ctx: &mut RenderContext, text_str: &str, size: f32) -> Result<Text, Box<dyn std::error::Error>>
+	pub fn new(render_ctx: &mut RenderContext, text_str: &str, size: f32) -> Result<Text, EngineError>
 	{
 		if text_str.is_empty() {
 			return Ok(Text{ quad: None, cur_str: text_str.to_string() })
 		}
 
 		// TODO: preload fonts
-		let font_data = include_bytes!("../../../examples/ui_menu/resource/mplus-1m-medium.ttf");
-		let font = Font::try_from_bytes(font_data as &[u8]).ok_or("Error constructing font")?;
+		let font_path = Path::new(FONT_PATH);
+		let font_asset = render_ctx.load_font(font_path)?.get()
+			.ok_or_else(|| EngineError::decode(font_path, "font is not loaded"))?;
+		let font = font_asset.font();
 
 		let scale_uniform = Scale::uniform(size);
 		let color = (255, 255, 255);
@@ -38,10 +47,9 @@ impl Text
 
 		// work out the layout size
 		let glyphs_height = (v_metrics.ascent - v_metrics.descent).ceil() as u32;
-		let min_x = glyphs.first().ok_or("there were no glyphs for the string!")?
-			.pixel_bounding_box().ok_or("pixel_bounding_box was `None`!")?.min.x;
-		let max_x = glyphs.last().ok_or("there were no glyphs for the string!")?
-			.pixel_bounding_box().ok_or("pixel_bounding_box was `None`!")?.max.x;
+		let no_glyphs = || EngineError::decode(font_path, format!("no glyphs with a bounding box for '{}'", text_str));
+		let min_x = glyphs.first().and_then(|g| g.pixel_bounding_box()).ok_or_else(no_glyphs)?.min.x;
+		let max_x = glyphs.last().and_then(|g| g.pixel_bounding_box()).ok_or_else(no_glyphs)?.max.x;
 		let glyphs_width = (max_x - min_x) as u32;
 		
 		// Create a new rgba image
@@ -72,7 +80,7 @@ impl Text
 		}
 
 		let img_dim = ImageDimensions::Dim2d{ width: image.width(), height: image.height(), array_layers: 1 };
-		let tex = render_ctx.new_texture_from_iter(image.into_raw(), Format::R8G8B8A8_SRGB, img_dim, MipmapsCount::One)?;
+		let tex = Handle::new(render_ctx.new_texture_from_iter(image.into_raw(), Format::R8G8B8A8_SRGB, img_dim, MipmapsCount::One)?);
 
 		let mesh_top_left = Vec2::new(img_dim.width() as f32 / -2.0, -v_metrics.ascent - 1.0);
 		let mesh_bottom_right = Vec2::new(img_dim.width() as f32 / 2.0, -v_metrics.descent + 1.0);
@@ -83,13 +91,26 @@ impl Text
 		})
 	}
 
+	/// Create text from the localized string for `key`, with each `{name}` in it replaced by the value for `name` in
+	/// `args`.
+	pub fn new_localized(
+		render_ctx: &mut RenderContext, 
+		localization: &Localization, 
+		key: &str, 
+		args: &[(&str, &str)], 
+		size: f32
+	) -> Result<Text, EngineError>
+	{
+		Self::new(render_ctx, &localization.format(key, args), size)
+	}
+
 	/// Obtain the currently displayed string.
 	pub fn cur_str(&self) -> String
 	{
 		self.cur_str.clone()
 	}
 
-	pub fn draw(&self, render_ctx: &mut RenderContext) -> Result<(), Box<dyn std::error::Error>>
+	pub fn draw(&self, render_ctx: &mut RenderContext) -> Result<(), EngineError>
 	{
 		match self.quad.as_ref() {
 			Some(q) => q.draw(render_ctx),
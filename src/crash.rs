@@ -0,0 +1,179 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::VecDeque;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex, MutexGuard };
+use std::time::{ SystemTime, UNIX_EPOCH };
+use log::{ LevelFilter, Log, Metadata, Record };
+use simplelog::{ Config, SharedLogger };
+
+/// Number of log lines to keep in memory for crash reports.
+const RECENT_LOG_LINES: usize = 200;
+
+/// The most recent lines written to the log, kept in memory so they can be included in crash reports.
+#[derive(Clone)]
+pub(crate) struct RecentLogLines
+{
+	lines: Arc<Mutex<VecDeque<String>>>
+}
+impl RecentLogLines
+{
+	pub fn new() -> RecentLogLines
+	{
+		RecentLogLines{ lines: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_LOG_LINES))) }
+	}
+
+	fn push(&self, line: String)
+	{
+		let mut lines = lock_ignoring_poison(&self.lines);
+		if lines.len() == RECENT_LOG_LINES {
+			lines.pop_front();
+		}
+		lines.push_back(line);
+	}
+
+	fn snapshot(&self) -> Vec<String>
+	{
+		lock_ignoring_poison(&self.lines).iter().cloned().collect()
+	}
+}
+
+/// Logger that writes into `RecentLogLines`, to be combined with the terminal and file loggers.
+pub(crate) struct RecentLinesLogger
+{
+	level: LevelFilter,
+	lines: RecentLogLines
+}
+impl RecentLinesLogger
+{
+	pub fn new(level: LevelFilter, lines: RecentLogLines) -> Box<RecentLinesLogger>
+	{
+		Box::new(RecentLinesLogger{ level: level, lines: lines })
+	}
+}
+impl Log for RecentLinesLogger
+{
+	fn enabled(&self, metadata: &Metadata) -> bool
+	{
+		metadata.level() <= self.level
+	}
+
+	fn log(&self, record: &Record)
+	{
+		if self.enabled(record.metadata()) {
+			self.lines.push(format!("[{}] ({}) {}", record.level(), record.target(), record.args()));
+		}
+	}
+
+	fn flush(&self) {}
+}
+impl SharedLogger for RecentLinesLogger
+{
+	fn level(&self) -> LevelFilter
+	{
+		self.level
+	}
+
+	fn config(&self) -> Option<&Config>
+	{
+		None
+	}
+
+	fn as_log(self: Box<Self>) -> Box<dyn Log>
+	{
+		Box::new(*self)
+	}
+}
+
+/// Information gathered for crash reports, shared with the panic hook.
+pub(crate) struct CrashReporter
+{
+	pref_path: PathBuf,
+	game_name: String,
+	headless: bool,
+	physical_device: Mutex<Option<String>>,
+	log_lines: RecentLogLines
+}
+impl CrashReporter
+{
+	pub fn new(pref_path: &Path, game_name: &str, headless: bool, log_lines: RecentLogLines) -> Arc<CrashReporter>
+	{
+		Arc::new(CrashReporter{
+			pref_path: pref_path.to_path_buf(),
+			game_name: game_name.to_string(),
+			headless: headless,
+			physical_device: Mutex::new(None),
+			log_lines: log_lines
+		})
+	}
+
+	/// Set the description of the physical device being rendered with, once it has been selected.
+	pub fn set_physical_device(&self, description: String)
+	{
+		*lock_ignoring_poison(&self.physical_device) = Some(description);
+	}
+
+	/// Install a panic hook that writes a crash report into the preferences directory, then notifies the user with a
+	/// message box, or on stderr if running headless.
+	pub fn install_panic_hook(self: &Arc<Self>)
+	{
+		let reporter = self.clone();
+		let default_hook = std::panic::take_hook();
+		std::panic::set_hook(Box::new(move |info| {
+			default_hook(info);
+
+			let backtrace = std::backtrace::Backtrace::force_capture();
+			let panic_message = info.to_string();
+			log::error!("{}", panic_message);
+
+			let notice = match reporter.write_report(&panic_message, &backtrace) {
+				Ok(report_path) => format!(
+					"The game has crashed. A crash report has been saved to '{}'.\n\n{}",
+					report_path.display(), panic_message
+				),
+				Err(e) => format!("The game has crashed, and the crash report couldn't be saved ({}).\n\n{}", e, panic_message)
+			};
+
+			if reporter.headless {
+				eprintln!("{}", notice);
+			} else {
+				msgbox::create("Engine Error", &notice, msgbox::common::IconType::Error)
+					.unwrap_or_else(|mbe| eprintln!("Failed to create error message box: {}", mbe));
+			}
+		}));
+	}
+
+	fn write_report(&self, panic_message: &str, backtrace: &std::backtrace::Backtrace) -> std::io::Result<PathBuf>
+	{
+		let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+		let physical_device = lock_ignoring_poison(&self.physical_device).clone()
+			.unwrap_or("(not selected yet)".to_string());
+		let log_lines = self.log_lines.snapshot();
+
+		let mut report = String::new();
+		report += &format!("{} crash report\n", self.game_name);
+		report += &format!("Time: {} (seconds since UNIX epoch)\n", timestamp);
+		report += &format!("Engine version: MithrilEngine {}\n", env!("CARGO_PKG_VERSION"));
+		report += &format!("Physical device: {}\n", physical_device);
+		report += &format!("\n{}\n", panic_message);
+		report += &format!("\nBacktrace:\n{}\n", backtrace);
+		report += &format!("\nLast {} log lines:\n", log_lines.len());
+		for line in log_lines {
+			report += &line;
+			report += "\n";
+		}
+
+		let report_path = self.pref_path.join(format!("crash_{}.txt", timestamp));
+		std::fs::write(&report_path, report)?;
+		Ok(report_path)
+	}
+}
+
+// Crash reports should still be written if a thread panicked while holding one of these locks.
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<T>
+{
+	mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,218 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::path::{ Path, PathBuf };
+use vulkano::device::physical::SurfacePropertiesError;
+use vulkano::swapchain::{ AcquireError, SwapchainCreationError };
+use vulkano::sync::FlushError;
+use crate::map::MapError;
+use crate::save::SaveError;
+
+/// Errors that can occur in the engine. Match on these to handle failures that don't need to be fatal, such as by
+/// retrying after a swapchain error, or skipping a texture that couldn't be loaded.
+#[derive(Debug)]
+pub enum EngineError
+{
+	/// A file couldn't be read or written.
+	Io{ path: PathBuf, source: std::io::Error },
+
+	/// A file was read, but its contents couldn't be decoded (or encoded, when writing).
+	Decode{ path: PathBuf, message: String },
+
+	/// A Vulkan operation failed. `operation` describes what was being done, such as "creating logical device".
+	/// `source` is the error returned by vulkano, which can be downcast to its original type, or just a description
+	/// of the problem if there was no error returned.
+	Vulkan{ operation: &'static str, source: Box<dyn std::error::Error + Send + Sync> },
+
+	/// The pipeline with the given name (the pipeline definition or shader file) couldn't be created.
+	Pipeline{ name: String, message: String },
+
+	/// There is no pipeline loaded with the given name.
+	PipelineNotLoaded{ name: String },
+
+	/// A descriptor set was bound when there was no pipeline bound.
+	NoPipelineBound,
+
+	/// The swapchain couldn't be created, or an image couldn't be acquired from or presented to it. These are usually
+	/// caused by the window changing, so they can be retried in the next frame.
+	Swapchain(SwapchainError),
+
+	/// Something went wrong with the ECS world. `name` is the name of the unique, workload, or component involved.
+	Ecs{ name: String, message: String },
+
+	/// A map couldn't be loaded.
+	Map(MapError),
+
+	/// A save file couldn't be read or written.
+	Save(SaveError),
+
+	/// The engine couldn't be initialized, for a reason not covered by the other variants, such as the logger failing
+	/// to be set up.
+	Init{ message: String },
+
+	/// An error returned by one of the game's systems.
+	System(crate::SystemError)
+}
+impl EngineError
+{
+	pub(crate) fn io(path: &Path, source: std::io::Error) -> Self
+	{
+		EngineError::Io{ path: path.to_path_buf(), source: source }
+	}
+
+	pub(crate) fn decode(path: &Path, message: impl std::fmt::Display) -> Self
+	{
+		EngineError::Decode{ path: path.to_path_buf(), message: message.to_string() }
+	}
+
+	pub(crate) fn vulkan(operation: &'static str, source: impl Into<Box<dyn std::error::Error + Send + Sync>>)
+		-> Self
+	{
+		EngineError::Vulkan{ operation: operation, source: source.into() }
+	}
+
+	pub(crate) fn pipeline(name: &str, message: impl std::fmt::Display) -> Self
+	{
+		EngineError::Pipeline{ name: name.to_string(), message: message.to_string() }
+	}
+
+	pub(crate) fn swapchain(e: impl Into<SwapchainError>) -> Self
+	{
+		EngineError::Swapchain(e.into())
+	}
+
+	pub(crate) fn ecs(name: &str, message: impl std::fmt::Display) -> Self
+	{
+		EngineError::Ecs{ name: name.to_string(), message: message.to_string() }
+	}
+}
+impl std::error::Error for EngineError
+{
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+	{
+		match self {
+			EngineError::Io{ source, .. } => Some(source),
+			EngineError::Vulkan{ source, .. } => Some(source.as_ref()),
+			EngineError::Swapchain(e) => Some(e),
+			EngineError::Map(e) => Some(e),
+			EngineError::Save(e) => Some(e),
+			EngineError::System(e) => Some(e.as_ref()),
+			_ => None
+		}
+	}
+}
+impl std::fmt::Display for EngineError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			EngineError::Io{ path, source } => write!(f, "I/O error on '{}': {}", path.display(), source),
+			EngineError::Decode{ path, message } => write!(f, "could not decode '{}': {}", path.display(), message),
+			EngineError::Vulkan{ operation, source } => write!(f, "Vulkan error while {}: {}", operation, source),
+			EngineError::Pipeline{ name, message } => write!(f, "could not create pipeline '{}': {}", name, message),
+			EngineError::PipelineNotLoaded{ name } => write!(f, "the pipeline '{}' is not loaded", name),
+			EngineError::NoPipelineBound => write!(f, "a descriptor set was bound when there was no pipeline bound"),
+			EngineError::Swapchain(e) => write!(f, "swapchain error: {}", e),
+			EngineError::Ecs{ name, message } => write!(f, "ECS error on '{}': {}", name, message),
+			EngineError::Map(e) => e.fmt(f),
+			EngineError::Save(e) => e.fmt(f),
+			EngineError::Init{ message } => write!(f, "initialization failed: {}", message),
+			EngineError::System(e) => e.fmt(f)
+		}
+	}
+}
+
+impl From<MapError> for EngineError
+{
+	fn from(e: MapError) -> Self
+	{
+		EngineError::Map(e)
+	}
+}
+impl From<SaveError> for EngineError
+{
+	fn from(e: SaveError) -> Self
+	{
+		EngineError::Save(e)
+	}
+}
+impl From<shipyard::error::Run> for EngineError
+{
+	fn from(e: shipyard::error::Run) -> Self
+	{
+		EngineError::ecs("system", e)
+	}
+}
+
+/// The ways the swapchain can fail, keeping the error returned by vulkano so that specific failures such as
+/// `AcquireError::SurfaceLost` can be matched on.
+#[derive(Debug)]
+pub enum SwapchainError
+{
+	/// The swapchain couldn't be created or recreated.
+	Creation(SwapchainCreationError),
+
+	/// The capabilities or present modes of the window surface couldn't be queried.
+	SurfaceProperties(SurfacePropertiesError),
+
+	/// The next image couldn't be acquired from the swapchain.
+	Acquire(AcquireError),
+
+	/// Commands couldn't be submitted, or the image couldn't be presented.
+	Present(FlushError),
+
+	/// Commands were submitted for presenting without an image being acquired first.
+	NoImageAcquired
+}
+impl std::error::Error for SwapchainError
+{
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+	{
+		match self {
+			SwapchainError::Creation(e) => Some(e),
+			SwapchainError::SurfaceProperties(e) => Some(e),
+			SwapchainError::Acquire(e) => Some(e),
+			SwapchainError::Present(e) => Some(e),
+			SwapchainError::NoImageAcquired => None
+		}
+	}
+}
+impl std::fmt::Display for SwapchainError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			SwapchainError::Creation(e) => write!(f, "could not create swapchain: {}", e),
+			SwapchainError::SurfaceProperties(e) => write!(f, "could not query surface properties: {}", e),
+			SwapchainError::Acquire(e) => write!(f, "could not acquire swapchain image: {}", e),
+			SwapchainError::Present(e) => write!(f, "could not present swapchain image: {}", e),
+			SwapchainError::NoImageAcquired => write!(f, "command buffer submitted when no image was acquired")
+		}
+	}
+}
+impl From<SwapchainCreationError> for SwapchainError
+{
+	fn from(e: SwapchainCreationError) -> Self
+	{
+		SwapchainError::Creation(e)
+	}
+}
+impl From<SurfacePropertiesError> for SwapchainError
+{
+	fn from(e: SurfacePropertiesError) -> Self
+	{
+		SwapchainError::SurfaceProperties(e)
+	}
+}
+impl From<AcquireError> for SwapchainError
+{
+	fn from(e: AcquireError) -> Self
+	{
+		SwapchainError::Acquire(e)
+	}
+}
+impl From<FlushError> for SwapchainError
+{
+	fn from(e: FlushError) -> Self
+	{
+		SwapchainError::Present(e)
+	}
+}
@@ -0,0 +1,90 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::time::{ Duration, Instant, SystemTime };
+use crate::vfs::Vfs;
+
+/// How often the watched files get checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+struct WatchedFile
+{
+	real_path: Option<PathBuf>,
+	modified: Option<SystemTime>
+}
+
+/// Watches files in the virtual filesystem for changes by polling their modification times, so that assets can be
+/// reloaded while the game is running during development. Only files that are stored as they are on disk can be
+/// watched; files in archives never change.
+pub struct FileWatcher
+{
+	vfs: Vfs,
+	files: HashMap<String, WatchedFile>,
+	last_poll: Instant
+}
+impl FileWatcher
+{
+	pub fn new(vfs: Vfs) -> FileWatcher
+	{
+		FileWatcher{ vfs: vfs, files: HashMap::new(), last_poll: Instant::now() }
+	}
+
+	/// Start watching the file at the logical path. Does nothing if it's already being watched.
+	pub fn watch(&mut self, logical_path: &str)
+	{
+		if self.files.contains_key(logical_path) {
+			return
+		}
+		let watched = stat(&self.vfs, logical_path);
+		match &watched.real_path {
+			Some(p) => log::debug!("Watching '{}' for changes", p.display()),
+			None => log::debug!("'{}' isn't stored on disk, so it won't be watched for changes", logical_path)
+		}
+		self.files.insert(logical_path.to_string(), watched);
+	}
+
+	pub fn unwatch(&mut self, logical_path: &str)
+	{
+		self.files.remove(logical_path);
+	}
+
+	/// Get the logical paths of the watched files that have changed since the last poll. The files only actually get
+	/// checked once every `POLL_INTERVAL`, so this can be called every frame.
+	///
+	/// A file counts as changed if its modification time changed, or if it now resolves to a different file on disk,
+	/// such as when a mod overriding it was added. Files that have been deleted aren't reported until they come back.
+	pub fn poll(&mut self) -> Vec<String>
+	{
+		if self.last_poll.elapsed() < POLL_INTERVAL {
+			return Vec::new()
+		}
+		self.last_poll = Instant::now();
+
+		let mut changed = Vec::new();
+		for (logical_path, watched) in self.files.iter_mut() {
+			let current = stat(&self.vfs, logical_path);
+			if current.modified.is_none() {
+				continue
+			}
+			if current.real_path != watched.real_path || current.modified != watched.modified {
+				*watched = current;
+				changed.push(logical_path.clone());
+			}
+		}
+		changed
+	}
+}
+
+// Find the file on disk that the logical path currently resolves to, and when it was last modified.
+fn stat(vfs: &Vfs, logical_path: &str) -> WatchedFile
+{
+	let real_path = vfs.real_path(Path::new(logical_path));
+	let modified = real_path.as_ref()
+		.and_then(|p| std::fs::metadata(p).ok())
+		.and_then(|m| m.modified().ok());
+	WatchedFile{ real_path: real_path, modified: modified }
+}
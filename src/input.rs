@@ -0,0 +1,644 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::{ HashMap, HashSet };
+use std::hash::Hash;
+use std::path::Path;
+use winit::event::{ Event, WindowEvent, DeviceEvent, ElementState, KeyboardInput, MouseScrollDelta };
+pub use winit::event::{ VirtualKeyCode, MouseButton };
+pub use gilrs::{ Button as GamepadButton, Axis as GamepadAxis };
+use glam::*;
+use serde::{ Serialize, Deserialize };
+use crate::error::EngineError;
+use crate::vfs::Vfs;
+
+/// An input event, converted from window system or gamepad events. These can also be constructed directly to feed
+/// synthetic input into `InputState`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent
+{
+	KeyPressed(VirtualKeyCode),
+	KeyReleased(VirtualKeyCode),
+	MouseButtonPressed(MouseButton),
+	MouseButtonReleased(MouseButton),
+
+	/// Raw mouse motion, not affected by cursor acceleration or the cursor hitting the edge of the screen.
+	MouseMotion(DVec2),
+
+	/// The cursor moved to the given position in physical pixels, relative to the top left of the window.
+	CursorMoved(DVec2),
+
+	/// The mouse wheel scrolled by the given number of lines (or pixels, for devices that scroll by pixels).
+	MouseWheel(Vec2),
+
+	/// A button was pressed on the gamepad with the given ID. IDs are the `usize` values of gilrs `GamepadId`s.
+	GamepadButtonPressed(usize, GamepadButton),
+	GamepadButtonReleased(usize, GamepadButton),
+	GamepadAxisChanged(usize, GamepadAxis, f32),
+
+	/// The gamepad with the given ID was disconnected; everything currently held on it will be released.
+	GamepadDisconnected(usize),
+
+	/// The window lost focus; everything currently held will be released.
+	FocusLost
+}
+impl InputEvent
+{
+	/// Convert a winit event into an input event. Returns `None` if the event isn't related to input.
+	pub fn from_winit(event: &Event<()>) -> Option<InputEvent>
+	{
+		match event {
+			Event::WindowEvent{ event: window_event, .. } => match window_event {
+				WindowEvent::KeyboardInput{ input: KeyboardInput{ virtual_keycode: Some(key), state, .. }, .. } => {
+					Some(match state {
+						ElementState::Pressed => InputEvent::KeyPressed(*key),
+						ElementState::Released => InputEvent::KeyReleased(*key)
+					})
+				}
+				WindowEvent::MouseInput{ state, button, .. } => Some(match state {
+					ElementState::Pressed => InputEvent::MouseButtonPressed(*button),
+					ElementState::Released => InputEvent::MouseButtonReleased(*button)
+				}),
+				WindowEvent::CursorMoved{ position, .. } => Some(InputEvent::CursorMoved(DVec2::new(position.x, position.y))),
+				WindowEvent::MouseWheel{ delta, .. } => Some(InputEvent::MouseWheel(match delta {
+					MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y),
+					MouseScrollDelta::PixelDelta(p) => DVec2::new(p.x, p.y).as_vec2()
+				})),
+				WindowEvent::Focused(false) => Some(InputEvent::FocusLost),
+				_ => None
+			},
+			Event::DeviceEvent{ event: DeviceEvent::MouseMotion{ delta }, .. } => {
+				Some(InputEvent::MouseMotion(DVec2::new(delta.0, delta.1)))
+			}
+			_ => None
+		}
+	}
+
+	/// Convert a gilrs gamepad event into an input event. Returns `None` if the event isn't related to input.
+	pub fn from_gilrs(event: &gilrs::Event) -> Option<InputEvent>
+	{
+		let gamepad = usize::from(event.id);
+		match event.event {
+			gilrs::EventType::ButtonPressed(button, _) => Some(InputEvent::GamepadButtonPressed(gamepad, button)),
+			gilrs::EventType::ButtonReleased(button, _) => Some(InputEvent::GamepadButtonReleased(gamepad, button)),
+			gilrs::EventType::AxisChanged(axis, value, _) => Some(InputEvent::GamepadAxisChanged(gamepad, axis, value)),
+			gilrs::EventType::Disconnected => Some(InputEvent::GamepadDisconnected(gamepad)),
+			_ => None
+		}
+	}
+}
+
+/// Tracks which buttons of one kind are held, and which ones were pressed or released this frame.
+struct ButtonStates<T: Copy + Eq + Hash>
+{
+	held: HashSet<T>,
+	pressed: HashSet<T>,
+	released: HashSet<T>
+}
+impl<T: Copy + Eq + Hash> ButtonStates<T>
+{
+	fn new() -> Self
+	{
+		ButtonStates{ held: HashSet::new(), pressed: HashSet::new(), released: HashSet::new() }
+	}
+
+	fn press(&mut self, button: T)
+	{
+		// ignore key repeats
+		if self.held.insert(button) {
+			self.pressed.insert(button);
+		}
+	}
+
+	fn release(&mut self, button: T)
+	{
+		if self.held.remove(&button) {
+			self.released.insert(button);
+		}
+	}
+
+	fn release_all(&mut self)
+	{
+		self.released.extend(self.held.drain());
+	}
+
+	fn end_frame(&mut self)
+	{
+		self.pressed.clear();
+		self.released.clear();
+	}
+}
+
+/// The buttons and axes of one gamepad.
+struct GamepadState
+{
+	buttons: ButtonStates<GamepadButton>,
+	axes: HashMap<GamepadAxis, f32>,
+
+	/// Set when the gamepad disconnects, so that its state gets removed at the end of the frame.
+	disconnected: bool
+}
+impl GamepadState
+{
+	fn new() -> Self
+	{
+		GamepadState{ buttons: ButtonStates::new(), axes: HashMap::new(), disconnected: false }
+	}
+}
+
+/// A single input that can trigger an action.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Binding
+{
+	Key(VirtualKeyCode),
+	Mouse(MouseButton),
+	Gamepad(GamepadButton)
+}
+
+/// An input that contributes to the value of an axis.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum AxisBinding
+{
+	/// Two keys, one for each direction. Gives -1.0 when `negative` is held, and 1.0 when `positive` is held.
+	Keys{ negative: VirtualKeyCode, positive: VirtualKeyCode },
+
+	/// A gamepad axis, ignoring values with a magnitude less than `deadzone`.
+	GamepadAxis{ axis: GamepadAxis, #[serde(default)] deadzone: f32 },
+
+	/// Horizontal raw mouse motion this frame, multiplied by `scale`.
+	MouseX{ scale: f32 },
+
+	/// Vertical raw mouse motion this frame, multiplied by `scale`.
+	MouseY{ scale: f32 },
+
+	/// Vertical mouse wheel scrolling this frame, multiplied by `scale`.
+	MouseWheel{ scale: f32 }
+}
+
+/// Named actions and axes, mapped to the inputs that trigger them. This can be loaded from a YAML file like this:
+///
+/// ```yaml
+/// actions:
+///   jump:
+///   - Key: Space
+///   - Gamepad: South
+/// axes:
+///   move_x:
+///   - Keys: { negative: A, positive: D }
+///   - GamepadAxis: { axis: LeftStickX, deadzone: 0.15 }
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InputBindings
+{
+	#[serde(default)]
+	pub actions: HashMap<String, Vec<Binding>>,
+
+	#[serde(default)]
+	pub axes: HashMap<String, Vec<AxisBinding>>
+}
+impl InputBindings
+{
+	/// Load input bindings from a YAML file in the virtual filesystem.
+	pub fn load(vfs: &Vfs, path: &Path) -> Result<InputBindings, EngineError>
+	{
+		log::info!("Loading input bindings from '{}'...", path.display());
+		let yaml_string = vfs.read_to_string(path)?;
+		serde_yaml::from_str(&yaml_string).map_err(|e| EngineError::decode(path, e))
+	}
+}
+
+/// The state of keyboard, mouse, and gamepad input, available to systems as a unique.
+/// "Pressed" and "released" refer to the button changing state during the current frame, while "held" refers to the
+/// button currently being down.
+///
+/// Each gamepad's state is tracked separately. The `gamepad_*` functions without a gamepad ID combine all gamepads,
+/// so that any of them can be used in single player games, while the `*_on` functions check just one gamepad.
+pub struct InputState
+{
+	keys: ButtonStates<VirtualKeyCode>,
+	mouse_buttons: ButtonStates<MouseButton>,
+	gamepads: HashMap<usize, GamepadState>,
+	mouse_motion: DVec2,
+	cursor_pos: DVec2,
+	wheel: Vec2,
+	bindings: InputBindings
+}
+impl InputState
+{
+	pub fn new(bindings: InputBindings) -> InputState
+	{
+		InputState{
+			keys: ButtonStates::new(),
+			mouse_buttons: ButtonStates::new(),
+			gamepads: HashMap::new(),
+			mouse_motion: DVec2::ZERO,
+			cursor_pos: DVec2::ZERO,
+			wheel: Vec2::ZERO,
+			bindings: bindings
+		}
+	}
+
+	/// Update the input state with the given event.
+	pub fn process_event(&mut self, event: InputEvent)
+	{
+		match event {
+			InputEvent::KeyPressed(key) => self.keys.press(key),
+			InputEvent::KeyReleased(key) => self.keys.release(key),
+			InputEvent::MouseButtonPressed(button) => self.mouse_buttons.press(button),
+			InputEvent::MouseButtonReleased(button) => self.mouse_buttons.release(button),
+			InputEvent::MouseMotion(delta) => self.mouse_motion += delta,
+			InputEvent::CursorMoved(pos) => self.cursor_pos = pos,
+			InputEvent::MouseWheel(delta) => self.wheel += delta,
+			InputEvent::GamepadButtonPressed(gamepad, button) => self.gamepad_mut(gamepad).buttons.press(button),
+			InputEvent::GamepadButtonReleased(gamepad, button) => self.gamepad_mut(gamepad).buttons.release(button),
+			InputEvent::GamepadAxisChanged(gamepad, axis, value) => {
+				self.gamepad_mut(gamepad).axes.insert(axis, value);
+			}
+			InputEvent::GamepadDisconnected(gamepad) => {
+				// keep the released buttons until the end of the frame, so that they still count as released, then
+				// remove the gamepad entirely
+				let state = self.gamepad_mut(gamepad);
+				state.buttons.pressed.clear();
+				state.buttons.release_all();
+				state.axes.clear();
+				state.disconnected = true;
+			}
+			InputEvent::FocusLost => {
+				self.keys.release_all();
+				self.mouse_buttons.release_all();
+			}
+		}
+	}
+
+	/// Clear the per-frame state, such as which buttons were pressed or released, and mouse motion.
+	/// This should be called once at the end of every frame.
+	pub fn end_frame(&mut self)
+	{
+		self.keys.end_frame();
+		self.mouse_buttons.end_frame();
+		self.gamepads.retain(|_, state| !state.disconnected);
+		for state in self.gamepads.values_mut() {
+			state.buttons.end_frame();
+		}
+		self.mouse_motion = DVec2::ZERO;
+		self.wheel = Vec2::ZERO;
+	}
+
+	pub fn set_bindings(&mut self, bindings: InputBindings)
+	{
+		self.bindings = bindings;
+	}
+
+	pub fn bindings(&self) -> &InputBindings
+	{
+		&self.bindings
+	}
+
+	pub fn key_pressed(&self, key: VirtualKeyCode) -> bool
+	{
+		self.keys.pressed.contains(&key)
+	}
+	pub fn key_held(&self, key: VirtualKeyCode) -> bool
+	{
+		self.keys.held.contains(&key)
+	}
+	pub fn key_released(&self, key: VirtualKeyCode) -> bool
+	{
+		self.keys.released.contains(&key)
+	}
+
+	pub fn mouse_button_pressed(&self, button: MouseButton) -> bool
+	{
+		self.mouse_buttons.pressed.contains(&button)
+	}
+	pub fn mouse_button_held(&self, button: MouseButton) -> bool
+	{
+		self.mouse_buttons.held.contains(&button)
+	}
+	pub fn mouse_button_released(&self, button: MouseButton) -> bool
+	{
+		self.mouse_buttons.released.contains(&button)
+	}
+
+	fn gamepad_mut(&mut self, gamepad: usize) -> &mut GamepadState
+	{
+		// input from a gamepad that disconnected earlier in the frame means that it has reconnected
+		let state = self.gamepads.entry(gamepad).or_insert_with(GamepadState::new);
+		state.disconnected = false;
+		state
+	}
+
+	/// The IDs of the connected gamepads that have sent any input.
+	pub fn gamepads(&self) -> impl Iterator<Item = usize> + '_
+	{
+		self.gamepads.iter().filter(|(_, state)| !state.disconnected).map(|(id, _)| *id)
+	}
+
+	/// Check if the button was pressed on any gamepad this frame.
+	pub fn gamepad_button_pressed(&self, button: GamepadButton) -> bool
+	{
+		self.gamepads.values().any(|g| g.buttons.pressed.contains(&button))
+	}
+	/// Check if the button is held on any gamepad.
+	pub fn gamepad_button_held(&self, button: GamepadButton) -> bool
+	{
+		self.gamepads.values().any(|g| g.buttons.held.contains(&button))
+	}
+	/// Check if the button was released on any gamepad this frame.
+	pub fn gamepad_button_released(&self, button: GamepadButton) -> bool
+	{
+		self.gamepads.values().any(|g| g.buttons.released.contains(&button))
+	}
+
+	/// Get the last known value of the gamepad axis, from -1.0 to 1.0. If more than one gamepad is in use, the value
+	/// furthest from 0.0 is used.
+	pub fn gamepad_axis(&self, axis: GamepadAxis) -> f32
+	{
+		self.gamepads.values()
+			.filter_map(|g| g.axes.get(&axis).copied())
+			.fold(0.0, |a, b| if b.abs() > a.abs() { b } else { a })
+	}
+
+	pub fn gamepad_button_pressed_on(&self, gamepad: usize, button: GamepadButton) -> bool
+	{
+		self.gamepads.get(&gamepad).map_or(false, |g| g.buttons.pressed.contains(&button))
+	}
+	pub fn gamepad_button_held_on(&self, gamepad: usize, button: GamepadButton) -> bool
+	{
+		self.gamepads.get(&gamepad).map_or(false, |g| g.buttons.held.contains(&button))
+	}
+	pub fn gamepad_button_released_on(&self, gamepad: usize, button: GamepadButton) -> bool
+	{
+		self.gamepads.get(&gamepad).map_or(false, |g| g.buttons.released.contains(&button))
+	}
+
+	/// Get the last known value of the axis on the gamepad, from -1.0 to 1.0.
+	pub fn gamepad_axis_on(&self, gamepad: usize, axis: GamepadAxis) -> f32
+	{
+		self.gamepads.get(&gamepad).and_then(|g| g.axes.get(&axis).copied()).unwrap_or(0.0)
+	}
+
+	/// Raw mouse motion accumulated during this frame.
+	pub fn mouse_motion(&self) -> DVec2
+	{
+		self.mouse_motion
+	}
+
+	/// Cursor position in physical pixels, relative to the top left of the window.
+	pub fn cursor_pos(&self) -> DVec2
+	{
+		self.cursor_pos
+	}
+
+	/// Mouse wheel scrolling accumulated during this frame.
+	pub fn wheel(&self) -> Vec2
+	{
+		self.wheel
+	}
+
+	fn binding_pressed(&self, binding: &Binding) -> bool
+	{
+		match binding {
+			Binding::Key(k) => self.key_pressed(*k),
+			Binding::Mouse(b) => self.mouse_button_pressed(*b),
+			Binding::Gamepad(b) => self.gamepad_button_pressed(*b)
+		}
+	}
+	fn binding_held(&self, binding: &Binding) -> bool
+	{
+		match binding {
+			Binding::Key(k) => self.key_held(*k),
+			Binding::Mouse(b) => self.mouse_button_held(*b),
+			Binding::Gamepad(b) => self.gamepad_button_held(*b)
+		}
+	}
+	fn binding_released(&self, binding: &Binding) -> bool
+	{
+		match binding {
+			Binding::Key(k) => self.key_released(*k),
+			Binding::Mouse(b) => self.mouse_button_released(*b),
+			Binding::Gamepad(b) => self.gamepad_button_released(*b)
+		}
+	}
+
+	/// Check if any input bound to the action was pressed this frame. Returns `false` if the action isn't bound.
+	pub fn action_pressed(&self, action: &str) -> bool
+	{
+		self.bindings.actions.get(action).map_or(false, |b| b.iter().any(|b| self.binding_pressed(b)))
+	}
+
+	/// Check if any input bound to the action is held. Returns `false` if the action isn't bound.
+	pub fn action_held(&self, action: &str) -> bool
+	{
+		self.bindings.actions.get(action).map_or(false, |b| b.iter().any(|b| self.binding_held(b)))
+	}
+
+	/// Check if an input bound to the action was released this frame, with no other input bound to it still held.
+	/// Returns `false` if the action isn't bound.
+	pub fn action_released(&self, action: &str) -> bool
+	{
+		match self.bindings.actions.get(action) {
+			Some(b) => b.iter().any(|b| self.binding_released(b)) && !b.iter().any(|b| self.binding_held(b)),
+			None => false
+		}
+	}
+
+	/// Get the value of the axis, summed from all of its bindings. Key and gamepad bindings each contribute a value
+	/// from -1.0 to 1.0, while mouse bindings contribute unbounded values. Returns 0.0 if the axis isn't bound.
+	pub fn axis(&self, axis: &str) -> f32
+	{
+		let bindings = match self.bindings.axes.get(axis) {
+			Some(b) => b,
+			None => return 0.0
+		};
+
+		bindings.iter().map(|binding| match binding {
+			AxisBinding::Keys{ negative, positive } => {
+				let mut value = 0.0;
+				if self.key_held(*negative) {
+					value -= 1.0;
+				}
+				if self.key_held(*positive) {
+					value += 1.0;
+				}
+				value
+			}
+			AxisBinding::GamepadAxis{ axis, deadzone } => {
+				let value = self.gamepad_axis(*axis);
+				if value.abs() < *deadzone {
+					0.0
+				} else {
+					value.clamp(-1.0, 1.0)
+				}
+			}
+			AxisBinding::MouseX{ scale } => self.mouse_motion.x as f32 * scale,
+			AxisBinding::MouseY{ scale } => self.mouse_motion.y as f32 * scale,
+			AxisBinding::MouseWheel{ scale } => self.wheel.y * scale
+		}).sum()
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn bindings() -> InputBindings
+	{
+		let mut bindings = InputBindings::default();
+		bindings.actions.insert("jump".to_string(), vec![
+			Binding::Key(VirtualKeyCode::Space),
+			Binding::Gamepad(GamepadButton::South)
+		]);
+		bindings.axes.insert("move_x".to_string(), vec![
+			AxisBinding::Keys{ negative: VirtualKeyCode::A, positive: VirtualKeyCode::D }
+		]);
+		bindings.axes.insert("stick_x".to_string(), vec![
+			AxisBinding::GamepadAxis{ axis: GamepadAxis::LeftStickX, deadzone: 0.2 }
+		]);
+		bindings
+	}
+
+	#[test]
+	fn pressed_held_released_across_frames()
+	{
+		let mut input = InputState::new(InputBindings::default());
+		input.process_event(InputEvent::KeyPressed(VirtualKeyCode::W));
+		assert!(input.key_pressed(VirtualKeyCode::W));
+		assert!(input.key_held(VirtualKeyCode::W));
+		assert!(!input.key_released(VirtualKeyCode::W));
+
+		input.end_frame();
+		assert!(!input.key_pressed(VirtualKeyCode::W));
+		assert!(input.key_held(VirtualKeyCode::W));
+
+		input.process_event(InputEvent::KeyReleased(VirtualKeyCode::W));
+		assert!(!input.key_held(VirtualKeyCode::W));
+		assert!(input.key_released(VirtualKeyCode::W));
+
+		input.end_frame();
+		assert!(!input.key_released(VirtualKeyCode::W));
+	}
+
+	#[test]
+	fn key_repeat_does_not_press_again()
+	{
+		let mut input = InputState::new(InputBindings::default());
+		input.process_event(InputEvent::KeyPressed(VirtualKeyCode::W));
+		input.end_frame();
+		input.process_event(InputEvent::KeyPressed(VirtualKeyCode::W));
+		assert!(!input.key_pressed(VirtualKeyCode::W));
+		assert!(input.key_held(VirtualKeyCode::W));
+	}
+
+	#[test]
+	fn focus_lost_releases_held_buttons()
+	{
+		let mut input = InputState::new(InputBindings::default());
+		input.process_event(InputEvent::KeyPressed(VirtualKeyCode::W));
+		input.process_event(InputEvent::MouseButtonPressed(MouseButton::Left));
+		input.end_frame();
+
+		input.process_event(InputEvent::FocusLost);
+		assert!(!input.key_held(VirtualKeyCode::W));
+		assert!(input.key_released(VirtualKeyCode::W));
+		assert!(!input.mouse_button_held(MouseButton::Left));
+		assert!(input.mouse_button_released(MouseButton::Left));
+	}
+
+	#[test]
+	fn actions_use_bindings()
+	{
+		let mut input = InputState::new(bindings());
+		assert!(!input.action_pressed("jump"));
+		assert!(!input.action_pressed("unbound"));
+
+		input.process_event(InputEvent::KeyPressed(VirtualKeyCode::Space));
+		assert!(input.action_pressed("jump"));
+		assert!(input.action_held("jump"));
+		input.end_frame();
+
+		// still held through the gamepad, so it doesn't count as released yet
+		input.process_event(InputEvent::GamepadButtonPressed(0, GamepadButton::South));
+		input.process_event(InputEvent::KeyReleased(VirtualKeyCode::Space));
+		assert!(!input.action_released("jump"));
+		input.end_frame();
+
+		input.process_event(InputEvent::GamepadButtonReleased(0, GamepadButton::South));
+		assert!(input.action_released("jump"));
+		assert!(!input.action_held("jump"));
+	}
+
+	#[test]
+	fn key_axis()
+	{
+		let mut input = InputState::new(bindings());
+		input.process_event(InputEvent::KeyPressed(VirtualKeyCode::D));
+		assert_eq!(input.axis("move_x"), 1.0);
+		input.process_event(InputEvent::KeyPressed(VirtualKeyCode::A));
+		assert_eq!(input.axis("move_x"), 0.0);
+		input.process_event(InputEvent::KeyReleased(VirtualKeyCode::D));
+		assert_eq!(input.axis("move_x"), -1.0);
+		assert_eq!(input.axis("unbound"), 0.0);
+	}
+
+	#[test]
+	fn gamepad_axis_deadzone_and_clamping()
+	{
+		let mut input = InputState::new(bindings());
+		input.process_event(InputEvent::GamepadAxisChanged(0, GamepadAxis::LeftStickX, 0.1));
+		assert_eq!(input.axis("stick_x"), 0.0);
+		input.process_event(InputEvent::GamepadAxisChanged(0, GamepadAxis::LeftStickX, -0.5));
+		assert_eq!(input.axis("stick_x"), -0.5);
+		input.process_event(InputEvent::GamepadAxisChanged(0, GamepadAxis::LeftStickX, 1.5));
+		assert_eq!(input.axis("stick_x"), 1.0);
+	}
+
+	#[test]
+	fn gamepads_are_separate()
+	{
+		let mut input = InputState::new(InputBindings::default());
+		input.process_event(InputEvent::GamepadButtonPressed(0, GamepadButton::South));
+		input.process_event(InputEvent::GamepadButtonPressed(1, GamepadButton::South));
+		input.process_event(InputEvent::GamepadButtonReleased(1, GamepadButton::South));
+		assert!(input.gamepad_button_held_on(0, GamepadButton::South));
+		assert!(!input.gamepad_button_held_on(1, GamepadButton::South));
+		assert!(input.gamepad_button_held(GamepadButton::South));
+
+		input.process_event(InputEvent::GamepadAxisChanged(0, GamepadAxis::LeftStickY, 0.3));
+		input.process_event(InputEvent::GamepadAxisChanged(1, GamepadAxis::LeftStickY, -0.8));
+		assert_eq!(input.gamepad_axis(GamepadAxis::LeftStickY), -0.8);
+		assert_eq!(input.gamepad_axis_on(0, GamepadAxis::LeftStickY), 0.3);
+
+		input.process_event(InputEvent::GamepadDisconnected(0));
+		assert!(!input.gamepad_button_held(GamepadButton::South));
+		assert!(input.gamepad_button_released_on(0, GamepadButton::South));
+		assert_eq!(input.gamepad_axis(GamepadAxis::LeftStickY), -0.8);
+	}
+
+	#[test]
+	fn disconnected_gamepads_are_cleared()
+	{
+		let mut input = InputState::new(InputBindings::default());
+		input.process_event(InputEvent::GamepadButtonPressed(0, GamepadButton::South));
+		input.process_event(InputEvent::GamepadAxisChanged(0, GamepadAxis::LeftStickX, 0.5));
+		input.process_event(InputEvent::GamepadButtonPressed(1, GamepadButton::East));
+		input.end_frame();
+
+		input.process_event(InputEvent::GamepadDisconnected(0));
+		assert!(input.gamepad_button_released_on(0, GamepadButton::South));
+		assert_eq!(input.gamepad_axis_on(0, GamepadAxis::LeftStickX), 0.0);
+		assert_eq!(input.gamepads().collect::<Vec<_>>(), vec![ 1 ]);
+
+		input.end_frame();
+		assert!(!input.gamepad_button_released_on(0, GamepadButton::South));
+		assert_eq!(input.gamepads().collect::<Vec<_>>(), vec![ 1 ]);
+
+		// a gamepad reconnecting with the same ID starts out with nothing held
+		input.process_event(InputEvent::GamepadButtonPressed(0, GamepadButton::North));
+		assert!(input.gamepad_button_held_on(0, GamepadButton::North));
+		assert!(!input.gamepad_button_held_on(0, GamepadButton::South));
+	}
+}
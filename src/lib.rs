@@ -3,64 +3,378 @@
 
 	Copyright (c) 2021-2022, daigennki (@daigennki)
 ----------------------------------------------------------------------------- */
-mod render;
+pub mod render;
 pub mod component;
 pub mod vertex;
-
-use std::path::PathBuf;
+pub mod time;
+pub mod input;
+pub mod map;
+pub mod settings;
+pub mod localization;
+pub mod save;
+pub mod cli;
+pub mod error;
+pub mod vfs;
+pub mod archive;
+pub mod asset;
+pub mod hot_reload;
+mod crash;
+mod logging;
+
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
 use winit::event::{ Event, WindowEvent };
 use simplelog::*;
-use glam::*;
 use component::ui;
 use component::ui::{ canvas::Canvas };
 use component::camera::Camera;
-use shipyard::{ World, View, ViewMut, Get, UniqueViewMut };
+use time::{ Time, FrameClock, FrameLimiter, FIXED_UPDATE_WORKLOAD, UPDATE_WORKLOAD };
+use input::{ InputState, InputEvent, InputBindings };
+use settings::{ Settings, VideoSettings };
+use cli::{ EngineOptions, CliError };
+use error::EngineError;
+use vfs::Vfs;
+use shipyard::{ World, View, ViewMut, Get, UniqueView, UniqueViewMut, Workload, WorkloadBuilder };
 use shipyard::iter::{ IntoIter, IntoWithId };
 
+/// Error returned by startup and update systems. It has to be `Send` and `Sync` so that it can be wrapped in an
+/// `EngineError`, which gets passed between threads.
+pub type SystemError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A callback run once after the engine has been initialized, used to populate the `World` with entities and uniques.
+pub type StartupSystem = Box<dyn FnOnce(&mut World, &mut render::RenderContext) -> Result<(), SystemError>>;
+
+/// A callback run once every frame before drawing, after the update workload.
+pub type UpdateSystem = Box<dyn FnMut(&mut World, &mut render::RenderContext) -> Result<(), SystemError>>;
+
+/// Builder used to set up a game before running it.
+pub struct GameBuilder
+{
+	org_name: String,
+	game_name: String,
+	window_title: String,
+	window_size: [u32; 2],
+	headless: Option<(u32, Option<PathBuf>)>,
+	mounts: Vec<(PathBuf, i32)>,
+	device_selection: render::DeviceSelection,
+	fixed_timestep: Duration,
+	input_bindings_path: Option<PathBuf>,
+	localization_dir: Option<PathBuf>,
+	save_data_version: u32,
+	entity_classes: map::EntityClassRegistry,
+	fixed_update_workload: WorkloadBuilder,
+	update_workload: WorkloadBuilder,
+	startup_systems: Vec<StartupSystem>,
+	update_systems: Vec<UpdateSystem>
+}
+impl GameBuilder
+{
+	/// Create a new game builder. `org_name` and `game_name` are used to determine the preferences path, where the log,
+	/// settings, and save data files get saved. `game_name` is also used as the default window title.
+	pub fn new(org_name: &str, game_name: &str) -> GameBuilder
+	{
+		GameBuilder{
+			org_name: org_name.to_string(),
+			game_name: game_name.to_string(),
+			window_title: game_name.to_string(),
+			window_size: [ 1280, 720 ],
+			headless: None,
+			mounts: Vec::new(),
+			device_selection: render::DeviceSelection::Auto,
+			fixed_timestep: Duration::from_secs(1) / 60,
+			input_bindings_path: None,
+			localization_dir: None,
+			save_data_version: 0,
+			entity_classes: map::EntityClassRegistry::with_engine_classes(),
+			fixed_update_workload: Workload::builder(FIXED_UPDATE_WORKLOAD),
+			update_workload: Workload::builder(UPDATE_WORKLOAD),
+			startup_systems: Vec::new(),
+			update_systems: Vec::new()
+		}
+	}
+
+	pub fn with_window_title(mut self, title: &str) -> GameBuilder
+	{
+		self.window_title = title.to_string();
+		self
+	}
+
+	/// Set the default window size, used if the user hasn't set a resolution in the settings file.
+	pub fn with_window_size(mut self, width: u32, height: u32) -> GameBuilder
+	{
+		self.window_size = [ width, height ];
+		self
+	}
+
+	/// Run without a window, rendering offscreen at the window size for `frame_count` frames before exiting.
+	/// If `screenshot_path` is given, the last frame rendered will be saved there as a PNG file.
+	///
+	/// Frames are drawn the same way as in a window, so UI elements don't show up in them yet, since UI drawing is
+	/// currently disabled for both.
+	pub fn with_headless(mut self, frame_count: u32, screenshot_path: Option<&Path>) -> GameBuilder
+	{
+		self.headless = Some((frame_count, screenshot_path.map(|p| p.to_path_buf())));
+		self
+	}
+
+	/// Mount a directory or asset archive ("*.mepk") of game data into the virtual filesystem that assets are loaded
+	/// through, with a priority such as `vfs::PRIORITY_GAME`. Relative paths are resolved against the directory
+	/// containing the executable.
+	///
+	/// The directory containing the executable is always mounted with `vfs::PRIORITY_ENGINE`, and each directory or
+	/// asset archive in the "mods" directory of the preferences path is mounted with `vfs::PRIORITY_MOD`.
+	pub fn with_mount(mut self, root: &Path, priority: i32) -> GameBuilder
+	{
+		self.mounts.push((root.to_path_buf(), priority));
+		self
+	}
+
+	/// Select the physical device to render with. This can be overridden with the `physical_device` setting in the
+	/// settings file, the `MITHRIL_DEVICE` environment variable, or the `--device` command-line option, each of which
+	/// takes either a device index or part of a device name.
+	pub fn with_physical_device(mut self, selection: render::DeviceSelection) -> GameBuilder
+	{
+		self.device_selection = selection;
+		self
+	}
+
+	/// Set the time step between each run of the fixed update workload. The default is 1/60 of a second. Time steps
+	/// shorter than `time::MIN_FIXED_TIMESTEP` are raised to it when the game starts.
+	pub fn with_fixed_timestep(mut self, fixed_timestep: Duration) -> GameBuilder
+	{
+		self.fixed_timestep = fixed_timestep;
+		self
+	}
+
+	/// Load the named actions and axes for `input::InputState` from the given YAML file in the virtual filesystem.
+	pub fn with_input_bindings(mut self, path: &Path) -> GameBuilder
+	{
+		self.input_bindings_path = Some(path.to_path_buf());
+		self
+	}
+
+	/// Load localized string tables from "<language>.ini" files in the given directory of the virtual filesystem, making them available as the
+	/// `localization::Localization` unique. The language is selected from the settings, or from the system locale if
+	/// the user hasn't set one.
+	pub fn with_localization_dir(mut self, dir: &Path) -> GameBuilder
+	{
+		self.localization_dir = Some(dir.to_path_buf());
+		self
+	}
+
+	/// Set the current version of the game's save data, used by the `save::SaveManager` unique to detect saves that
+	/// need to be migrated. The default is 0.
+	pub fn with_save_data_version(mut self, version: u32) -> GameBuilder
+	{
+		self.save_data_version = version;
+		self
+	}
+
+	/// Register a constructor for an entity class that can be used in map files, replacing the engine's constructor if
+	/// the engine already has a class with the same name. The constructor receives the entity's fields from the map file.
+	pub fn with_entity_class<F>(mut self, class: &str, constructor: F) -> GameBuilder
+		where F: Fn(&mut World, &mut render::RenderContext, &serde_yaml::Mapping) 
+			-> Result<shipyard::EntityId, Box<dyn std::error::Error>> + Send + Sync + 'static
+	{
+		self.entity_classes.register(class, constructor);
+		self
+	}
+
+	/// Add systems to the fixed update workload, which gets run at a fixed rate set by `with_fixed_timestep`.
+	/// Use this for things that should run at the same rate regardless of frame rate, such as physics.
+	///
+	/// `add_systems` receives the workload builder, and should return it after adding systems to it.
+	pub fn with_fixed_update_systems<F>(mut self, add_systems: F) -> GameBuilder
+		where F: FnOnce(WorkloadBuilder) -> WorkloadBuilder
+	{
+		self.fixed_update_workload = add_systems(self.fixed_update_workload);
+		self
+	}
+
+	/// Add systems to the update workload, which gets run once every frame before drawing.
+	///
+	/// `add_systems` receives the workload builder, and should return it after adding systems to it.
+	pub fn with_update_systems<F>(mut self, add_systems: F) -> GameBuilder
+		where F: FnOnce(WorkloadBuilder) -> WorkloadBuilder
+	{
+		self.update_workload = add_systems(self.update_workload);
+		self
+	}
+
+	/// Add a system that gets run once after the engine has been initialized. Startup systems are run in the order
+	/// they were added.
+	pub fn with_startup_system<F>(mut self, system: F) -> GameBuilder
+		where F: FnOnce(&mut World, &mut render::RenderContext) -> Result<(), SystemError> + 'static
+	{
+		self.startup_systems.push(Box::new(system));
+		self
+	}
+
+	/// Add a system that gets run every frame before drawing, after the update workload. Unlike systems in the
+	/// workloads, these also get access to the `RenderContext`. Update systems are run in the order they were added.
+	pub fn with_update_system<F>(mut self, system: F) -> GameBuilder
+		where F: FnMut(&mut World, &mut render::RenderContext) -> Result<(), SystemError> + 'static
+	{
+		self.update_systems.push(Box::new(system));
+		self
+	}
+
+	/// Initialize the engine and run the game, with options from the command line. This only returns if initialization
+	/// fails, or if running headless.
+	pub fn run(mut self)
+	{
+		let options = match EngineOptions::from_env() {
+			Ok(o) => o,
+			Err(CliError::HelpRequested) => {
+				println!("{}", cli::USAGE);
+				return
+			}
+			Err(e) => {
+				log_error(&e);
+				return
+			}
+		};
+
+		if options.headless {
+			let screenshot_path = self.headless.take().and_then(|(_, p)| p);
+			self.headless = Some((options.headless_frames.unwrap_or(1), screenshot_path));
+		}
+
+		match self.headless.clone() {
+			Some((frame_count, screenshot_path)) => {
+				run_headless(self, options, frame_count, screenshot_path).unwrap_or_else(|e| log_error(&e))
+			}
+			None => self.run_windowed(options)
+		}
+	}
+
+	fn run_windowed(self, options: EngineOptions)
+	{
+		let event_loop = winit::event_loop::EventLoop::new();
+
+		match GameContext::new(self, options, Some(&event_loop)) {
+			Ok(mut gctx) => event_loop.run(move |event, _, control_flow| {
+				match event {
+					Event::WindowEvent{ event: WindowEvent::CloseRequested, .. } => {
+						*control_flow = winit::event_loop::ControlFlow::Exit;	// TODO: show exit confirmation dialog here
+					},
+					_ => (),
+				};
+				
+				gctx.handle_event(&event).unwrap_or_else(|e| {
+					log_error(&e);
+					*control_flow = winit::event_loop::ControlFlow::Exit;
+				});
+			}),	
+			Err(e) => log_error(&e)
+		}
+	}
+}
+
 struct GameContext
 {
 	//pref_path: String,
 	render_context: render::RenderContext,
-	world: World
+	world: World,
+	clock: FrameClock,
+	frame_limiter: FrameLimiter,
+	gilrs: Option<gilrs::Gilrs>,
+	update_systems: Vec<UpdateSystem>,
+
+	// dimensions that the canvas and camera projections were last updated for
+	dimensions: [u32; 2]
 }
 impl GameContext
 {
-	// game context "constructor"
-	pub fn new(org_name: &str, game_name: &str, event_loop: &winit::event_loop::EventLoop<()>) 
-		-> Result<GameContext, Box<dyn std::error::Error>>
+	// game context "constructor"; renders offscreen if `event_loop` is `None`
+	fn new(mut builder: GameBuilder, options: EngineOptions, event_loop: Option<&winit::event_loop::EventLoop<()>>) 
+		-> Result<GameContext, EngineError>
 	{
-		/*let pref_path =*/ setup_log(org_name, game_name)?;
+		let (pref_path, recent_log_lines) = setup_log(&builder.org_name, &builder.game_name, &options)?;
 
 		log::info!("--- Initializing MithrilEngine... ---");
 
-		// get command line arguments
-		// let args: Vec<String> = std::env::args().collect();
+		let crash_reporter = crash::CrashReporter::new(&pref_path, &builder.game_name, event_loop.is_none(), recent_log_lines);
+		crash_reporter.install_panic_hook();
 
-		let mut render_ctx = render::RenderContext::new(game_name, &event_loop)?;
+		if builder.fixed_timestep < time::MIN_FIXED_TIMESTEP {
+			log::warn!(
+				"Fixed timestep of {:?} is too short, using {:?} instead",
+				builder.fixed_timestep, time::MIN_FIXED_TIMESTEP
+			);
+			builder.fixed_timestep = time::MIN_FIXED_TIMESTEP;
+		}
 
-		let mut world = World::new();
+		let vfs = setup_vfs(&builder.mounts, &pref_path)?;
 
-		// add some 3D entities for testing
-		world.add_unique(Camera::new(&mut render_ctx, [ 1.0, 3.0, 3.0 ].into(), [ 0.0, 0.0, 0.0 ].into())?)?;
+		let mut settings = Settings::load(&pref_path, VideoSettings::with_resolution(builder.window_size));
+		match options.window_mode {
+			Some(m) => settings.override_window_mode(m),
+			None => ()
+		}
 
-		world.add_entity(component::new_triangle(&mut render_ctx, [ 0.0, 0.0, 0.0 ].into(), Vec3::ONE, [ 0.1, 0.0, 0.0, 0.8 ].into())?);
-		world.add_entity(component::new_triangle(&mut render_ctx, [ 0.2, 0.0, 0.2 ].into(), Vec3::ONE, [ 0.0, 0.1, 0.0, 0.8 ].into())?);
-		world.add_entity(component::new_triangle(&mut render_ctx, [ 0.4, 0.0, 0.4 ].into(), Vec3::ONE, [ 0.0, 0.0, 0.1, 0.8 ].into())?);
-		world.add_entity(component::new_triangle(&mut render_ctx, [ 0.6, 0.0, 0.6 ].into(), Vec3::ONE, [ 0.1, 0.1, 0.0, 0.8 ].into())?);
+		// the device selected on the command line takes priority over the one in the environment variable, which takes
+		// priority over the one in the settings file
+		let device_selection = match &options.device {
+			Some(d) => d.clone(),
+			None => match settings.physical_device() {
+				Some(selection_str) => {
+					log::info!("Using physical device selection '{}' from settings", selection_str);
+					render::DeviceSelection::parse(selection_str)
+				}
+				None => builder.device_selection.clone()
+			}.or_env_override()
+		};
+		let video = settings.effective_video();
+		let frame_limiter = FrameLimiter::new(video.frame_rate_limit);
+		let mut render_ctx = match event_loop {
+			Some(el) => render::RenderContext::new(
+				&builder.game_name, &builder.window_title, &video, &device_selection, el, vfs.clone()
+			)?,
+			None => render::RenderContext::new_offscreen(&builder.game_name, &video, &device_selection, vfs.clone())?
+		};
+		crash_reporter.set_physical_device(render_ctx.physical_device_description());
 
+		let mut world = World::new();
 
-		// add some UI entities for testing
-		world.add_unique(Canvas::new(1280, 720)?)?;
+		add_unique(&world, Time::new(builder.fixed_timestep))?;
 
-		world.add_entity(ui::new_image(&mut render_ctx, "test_image.png", [ 0, 0 ].into())?);
-		world.add_entity(ui::new_text(&mut render_ctx, "Hello World!", 32.0, [ -200, -200 ].into())?);
+		match &builder.localization_dir {
+			Some(dir) => add_unique(&world, localization::Localization::load(&vfs, dir, settings.language())?)?,
+			None => ()
+		}
+		add_unique(&world, settings)?;
+		add_unique(&world, save::SaveManager::new(pref_path.join("saves"), builder.save_data_version)?)?;
 
-		
+		let input_bindings = match &builder.input_bindings_path {
+			Some(p) => InputBindings::load(&vfs, p)?,
+			None => InputBindings::default()
+		};
+		add_unique(&world, InputState::new(input_bindings))?;
+		log::debug!("Registered entity classes: {}", builder.entity_classes.classes().collect::<Vec<_>>().join(", "));
+		add_unique(&world, builder.entity_classes)?;
+		add_unique(&world, vfs)?;
+		let gilrs = gilrs::Gilrs::new()
+			.map_err(|e| log::warn!("Failed to initialize gamepad input, gamepads won't be available: {}", e))
+			.ok();
+
+		builder.fixed_update_workload.add_to_world(&world)
+			.map_err(|e| EngineError::ecs(FIXED_UPDATE_WORKLOAD, e))?;
+		builder.update_workload.add_to_world(&world)
+			.map_err(|e| EngineError::ecs(UPDATE_WORKLOAD, e))?;
+
+		let dim = render_ctx.swapchain_dimensions();
+		add_unique(&world, Canvas::new(dim[0], dim[1])?)?;
+
+		// let the game populate the world
+		for system in builder.startup_systems {
+			system(&mut world, &mut render_ctx).map_err(EngineError::System)?;
+		}
 
 		// Update the projection matrix on UI `Transform` components.
 		// TODO: use tracking instead, when it gets implemented in shipyard stable
 		world.run(|mut canvas: UniqueViewMut<Canvas>, mut transforms: ViewMut<ui::Transform>| 
-			-> Result<(), Box<dyn std::error::Error>> 
+			-> Result<(), EngineError> 
 		{
 			for (eid, mut transform) in (&mut transforms).iter().with_id() {
 				transform.update_projection(&mut render_ctx, canvas.projection())?;
@@ -70,34 +384,185 @@ impl GameContext
 			Ok(())
 		})??;
 
+		match &options.map {
+			Some(map_path) => map::load_map(&mut world, &mut render_ctx, map_path)?,
+			None => ()
+		}
+		add_unique(&world, options)?;
+
 		let gctx = GameContext { 
 			//pref_path: pref_path,
 			render_context: render_ctx,
-			world: world
+			world: world,
+			clock: FrameClock::new(builder.fixed_timestep),
+			frame_limiter: frame_limiter,
+			gilrs: gilrs,
+			update_systems: builder.update_systems,
+			dimensions: dim
 		};
 
 		Ok(gctx)
 	}
 
-	pub fn handle_event(&mut self, event: &Event<()>) -> Result<(), Box<dyn std::error::Error>>
+	pub fn handle_event(&mut self, event: &Event<()>) -> Result<(), EngineError>
 	{
+		match InputEvent::from_winit(event) {
+			Some(input_event) => self.world.run(|mut input: UniqueViewMut<InputState>| input.process_event(input_event))?,
+			None => ()
+		}
+
 		match event {
-			Event::RedrawEventsCleared => self.draw_in_event_loop(),
+			Event::WindowEvent{ event: WindowEvent::Resized(_), .. } => {
+				self.render_context.window_resized();
+				Ok(())
+			}
+			Event::RedrawEventsCleared => {
+				self.frame_limiter.wait();
+				self.render_context.begin_frame()?;
+				let frame_time = self.clock.tick();
+				self.update(frame_time)?;
+				self.draw_in_event_loop()
+			},
 			_ => Ok(())
 		}
 	}
 
-	fn draw_in_event_loop(&mut self) -> Result<(), Box<dyn std::error::Error>>
+	/// Run the fixed update workload as many times as needed to catch up with the time accumulated in the frame
+	/// clock, then run the variable-rate update stage once.
+	fn update(&mut self, frame_time: Duration) -> Result<(), EngineError>
 	{
-		self.render_context.begin_main_render_pass()?;
+		self.world.run(|mut time: UniqueViewMut<Time>| time.begin_frame(frame_time))?;
+		self.poll_gamepads()?;
+
+		while self.clock.consume_fixed_step() {
+			self.world.run(|mut time: UniqueViewMut<Time>| time.begin_fixed_update())?;
+			self.world.run_workload(FIXED_UPDATE_WORKLOAD).map_err(|e| EngineError::ecs(FIXED_UPDATE_WORKLOAD, e))?;
+		}
+
+		let alpha = self.clock.alpha();
+		self.world.run(|mut time: UniqueViewMut<Time>| time.set_alpha(alpha))?;
+		self.world.run_workload(UPDATE_WORKLOAD).map_err(|e| EngineError::ecs(UPDATE_WORKLOAD, e))?;
+
+		for system in &mut self.update_systems {
+			system(&mut self.world, &mut self.render_context).map_err(EngineError::System)?;
+		}
+
+		// reload assets whose files were changed during development
+		for changed_path in self.render_context.poll_file_changes() {
+			self.reload_changed_file(&changed_path);
+		}
+
+		// upload textures that finished loading in the background, and show them in place of the placeholder texture
+		self.render_context.update_async_loads();
+		self.world.run_with_data(update_ui_textures, &mut self.render_context)??;
+
+		self.world.run(|mut input: UniqueViewMut<InputState>| input.end_frame())?;
+
+		self.apply_changed_settings()?;
 
-		// Draw the 3D stuff
-		self.render_context.bind_pipeline("World")?;
-		self.world.run(|camera: UniqueViewMut<Camera>|
+		Ok(())
+	}
+
+	/// Reload the changed file that the render context doesn't reload by itself. Errors are logged, and the currently
+	/// loaded version is kept.
+	fn reload_changed_file(&mut self, logical_path: &str)
+	{
+		let loaded_map_path = self.world.borrow::<UniqueView<map::LoadedMap>>()
+			.ok()
+			.map(|loaded_map| loaded_map.path().to_path_buf());
+		match loaded_map_path {
+			Some(map_path) if vfs::normalize(&map_path).map_or(false, |p| p == logical_path) => {
+				log::info!("'{}' changed, reloading map...", logical_path);
+				// the current map stays loaded if the new one fails, so the file can be fixed and saved again
+				match map::load_map(&mut self.world, &mut self.render_context, &map_path) {
+					Ok(()) => (),
+					Err(e) => log::error!("{}; keeping the current map", e)
+				}
+			}
+			_ => log::debug!("'{}' changed, but nothing that was loaded from it can be reloaded", logical_path)
+		}
+	}
+
+	/// Update the projections of the canvas, UI transforms, and active camera to match the dimensions of the window.
+	fn update_projections(&mut self) -> Result<(), EngineError>
+	{
+		let render_ctx = &mut self.render_context;
+		let dim = self.dimensions;
+		self.world.run(|mut canvas: UniqueViewMut<Canvas>, mut transforms: ViewMut<ui::Transform>| 
+			-> Result<(), EngineError>
 		{
-			camera.bind(&mut self.render_context)
+			canvas.resize(dim[0], dim[1]);
+			for mut transform in (&mut transforms).iter() {
+				transform.update_projection(render_ctx, canvas.projection())?;
+			}
+			Ok(())
 		})??;
-		self.world.run_with_data(draw_3d, &mut self.render_context)??;
+
+		match self.world.borrow::<UniqueViewMut<Camera>>() {
+			Ok(mut camera) => camera.update_aspect_ratio(render_ctx)?,
+			Err(_) => ()
+		}
+		Ok(())
+	}
+
+	/// Notify the renderer of settings that were changed during this frame, then save them.
+	fn apply_changed_settings(&mut self) -> Result<(), EngineError>
+	{
+		let render_ctx = &mut self.render_context;
+		let frame_limiter = &mut self.frame_limiter;
+		self.world.run(|mut settings: UniqueViewMut<Settings>| {
+			if settings.take_video_changed() {
+				let video = settings.effective_video();
+				render_ctx.apply_video_settings(&video)?;
+				frame_limiter.set_limit(video.frame_rate_limit);
+				settings.save().unwrap_or_else(|e| log::error!("{}", e));
+			}
+			Ok(())
+		})?
+	}
+
+	fn poll_gamepads(&mut self) -> Result<(), EngineError>
+	{
+		let gilrs = match self.gilrs.as_mut() {
+			Some(g) => g,
+			None => return Ok(())
+		};
+		while let Some(event) = gilrs.next_event() {
+			match InputEvent::from_gilrs(&event) {
+				Some(input_event) => {
+					self.world.run(|mut input: UniqueViewMut<InputState>| input.process_event(input_event))?
+				}
+				None => ()
+			}
+		}
+		Ok(())
+	}
+
+	fn draw_in_event_loop(&mut self) -> Result<(), EngineError>
+	{
+		// there's nothing to draw to while the window is minimized
+		if self.render_context.is_minimized() {
+			return Ok(())
+		}
+
+		self.render_context.begin_main_render_pass()?;
+
+		// the swapchain gets recreated when the render pass begins if the window was resized
+		let dimensions = self.render_context.swapchain_dimensions();
+		if dimensions != self.dimensions {
+			self.dimensions = dimensions;
+			self.update_projections()?;
+		}
+
+		// Draw the 3D stuff, if the game has set up a camera to view it with
+		match self.world.borrow::<UniqueViewMut<Camera>>() {
+			Ok(mut camera) => {
+				self.render_context.bind_pipeline("World")?;
+				camera.bind(&mut self.render_context)?;
+				self.world.run_with_data(draw_3d, &mut self.render_context)??;
+			}
+			Err(_) => ()
+		}
 
 		// Draw the UI element components.
 		//self.render_context.bind_pipeline("UI");
@@ -111,16 +576,55 @@ impl GameContext
 	}
 }
 
+/// Run the game for a fixed number of frames without a window. Only the 3D scene gets drawn, since
+/// `draw_in_event_loop` doesn't draw UI elements yet.
+fn run_headless(builder: GameBuilder, options: EngineOptions, frame_count: u32, screenshot_path: Option<PathBuf>)
+	-> Result<(), EngineError>
+{
+	let mut gctx = GameContext::new(builder, options, None)?;
+
+	// advance time by exactly one fixed timestep each frame, so that the output doesn't depend on how fast the
+	// machine renders
+	log::info!("Running headless for {} frame(s)...", frame_count);
+	for _ in 0..frame_count {
+		gctx.render_context.begin_frame()?;
+		gctx.render_context.wait_for_async_loads();
+		let frame_time = gctx.clock.advance(gctx.clock.fixed_timestep());
+		gctx.update(frame_time)?;
+		gctx.draw_in_event_loop()?;
+	}
+
+	match screenshot_path {
+		Some(p) => {
+			gctx.render_context.save_offscreen_png(&p)?;
+			log::info!("Saved last headless frame to '{}'", p.display());
+		}
+		None => ()
+	}
+
+	Ok(())
+}
+
+/// Recreate the descriptor sets of UI meshes whose textures have finished loading since the last frame.
+fn update_ui_textures(render_ctx: &mut render::RenderContext, mut meshes: ViewMut<ui::mesh::Mesh>)
+	-> Result<(), EngineError>
+{
+	for mut mesh in (&mut meshes).iter() {
+		mesh.update_texture(render_ctx)?;
+	}
+	Ok(())
+}
+
 /// Draw 3D objects.
 /// This will ignore anything without a `Transform` component, since it would be impossible to draw without one.
 fn draw_3d(
 	render_ctx: &mut render::RenderContext,
-	transforms: View<component::Transform>,
+	mut transforms: ViewMut<component::Transform>,
 	meshes: View<component::mesh::Mesh>
 )
-	-> Result<(), Box<dyn std::error::Error>>
+	-> Result<(), EngineError>
 {
-	for (eid, transform) in transforms.iter().with_id() {
+	for (eid, mut transform) in (&mut transforms).iter().with_id() {
 		transform.bind_descriptor_set(render_ctx)?;
 
 		// draw 3D meshes
@@ -144,7 +648,7 @@ fn draw_ui_elements(
 	meshes: View<ui::mesh::Mesh>,
 	texts: View<ui::text::Text>
 )
-	-> Result<(), Box<dyn std::error::Error>>
+	-> Result<(), EngineError>
 {	
 	for (eid, transform) in transforms.iter().with_id() {
 		transform.bind_descriptor_set(render_ctx)?;
@@ -164,39 +668,38 @@ fn draw_ui_elements(
 	Ok(())
 }
 
+// Add a unique to the world, naming the unique's type in the error if it fails.
+fn add_unique<T: Send + Sync + 'static>(world: &World, unique: T) -> Result<(), EngineError>
+{
+	world.add_unique(unique).map_err(|e| EngineError::ecs(std::any::type_name::<T>(), e))
+}
+
+/// Run a game with the default settings and nothing in the world. Use `GameBuilder` to set up the game instead.
 pub fn run_game(org_name: &str, game_name: &str)
 {
-	let event_loop = winit::event_loop::EventLoop::new();
-
-	match GameContext::new(org_name, game_name, &event_loop) {
-		Ok(mut gctx) => event_loop.run(move |event, _, control_flow| {
-			match event {
-				Event::WindowEvent{ event: WindowEvent::CloseRequested, .. } => {
-					*control_flow = winit::event_loop::ControlFlow::Exit;	// TODO: show exit confirmation dialog here
-				},
-				_ => (),
-			};
-			
-			gctx.handle_event(&event).unwrap_or_else(|e| {
-				log_error(e);
-				*control_flow = winit::event_loop::ControlFlow::Exit;
-			});
-		}),	
-		Err(e) => log_error(e)
-	}
+	GameBuilder::new(org_name, game_name).run()
 }
 
-// Get preferences path, set up logging, and return the preferences path.
-fn setup_log(org_name: &str, game_name: &str) -> Result<PathBuf, Box<dyn std::error::Error>>
+// Get preferences path, set up logging, and return the preferences path along with the recent log lines for crash
+// reports. Levels given on the command line override the ones in the settings file.
+fn setup_log(org_name: &str, game_name: &str, options: &EngineOptions) 
+	-> Result<(PathBuf, crash::RecentLogLines), EngineError>
 {
 	let pref_path = get_pref_path(org_name, game_name)?;	// log, config, and save data files will be saved here
 	println!("Using preferences path: {}", pref_path.display());
 
-	// open log file
-	let log_file_path = pref_path.join("game.log");
-	let log_file = std::fs::File::create(&log_file_path).or_else(|e| {
-		Err(format!("Failed to create log file '{}': {}", log_file_path.display(), e))
-	})?;
+	let log_settings = Settings::load_log_settings(&pref_path);
+	let term_log_level = options.term_log_level.unwrap_or(log_settings.terminal_level);
+	let file_log_level = options.file_log_level.unwrap_or(log_settings.file_level);
+	let mut module_levels: Vec<(String, LevelFilter)> = log_settings.module_levels.into_iter().collect();
+	for (module, level) in &options.log_filters {
+		module_levels.retain(|(m, _)| m != module);
+		module_levels.push((module.clone(), *level));
+	}
+
+	// open a new log file, keeping the ones from previous runs
+	let (log_file, log_file_path) = logging::create_rotated_log_file(&pref_path.join("logs"), log_settings.keep_files)?;
+	println!("Writing log to: {}", log_file_path.display());
 
 	// set up logger
 	let logger_config = ConfigBuilder::new()
@@ -206,23 +709,66 @@ fn setup_log(org_name: &str, game_name: &str) -> Result<PathBuf, Box<dyn std::er
 		})	// use time in time zone local to system
 		.set_time_format_rfc3339()	// use RFC 3339 format
 		.build();
-	let term_log_level;
-	#[cfg(debug_assertions)] 
+
+	// levels are filtered by `ModuleFilterLogger`, so the wrapped loggers let everything through
+	let term_logger = TermLogger::new(LevelFilter::Trace, logger_config.clone(), TerminalMode::Mixed, ColorChoice::Auto);
+	let write_logger = WriteLogger::new(LevelFilter::Trace, logger_config, log_file);
+	let recent_log_lines = crash::RecentLogLines::new();
+	let recent_lines_logger = crash::RecentLinesLogger::new(LevelFilter::Trace, recent_log_lines.clone());
+    CombinedLogger::init(vec![ 
+		logging::ModuleFilterLogger::new(term_logger, term_log_level, &module_levels),
+		logging::ModuleFilterLogger::new(write_logger, file_log_level, &module_levels),
+		logging::ModuleFilterLogger::new(recent_lines_logger, file_log_level, &module_levels)
+	])
+		.map_err(|e| EngineError::Init{ message: format!("failed to set up logger: {}", e) })?;
+
+	Ok((pref_path, recent_log_lines))
+}
+
+// Mount the engine's data, the game's data, and the user's mods into a new virtual filesystem.
+fn setup_vfs(game_mounts: &[(PathBuf, i32)], pref_path: &Path) -> Result<Vfs, EngineError>
+{
+	let vfs = Vfs::new();
+	vfs.mount_dir(&vfs::exe_dir(), vfs::PRIORITY_ENGINE);
+
+	// in debug builds, also use the data in the source tree, so that the examples can be run with `cargo run`
+	#[cfg(debug_assertions)]
 	{
-		term_log_level = LevelFilter::Debug;
+		let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+		vfs.mount_dir(manifest_dir, vfs::PRIORITY_ENGINE);
+		vfs.mount_dir(&manifest_dir.join("examples").join("common_assets"), vfs::PRIORITY_ENGINE);
+	}
+
+	for (root, priority) in game_mounts {
+		vfs.mount_path(root, *priority)?;
+	}
+
+	// mount each mod in alphabetical order, so that the order they override each other in is predictable. mods that
+	// can't be mounted are skipped, so that one broken mod doesn't prevent the game from starting.
+	let mods_path = pref_path.join("mods");
+	match std::fs::read_dir(&mods_path) {
+		Ok(read_dir) => {
+			let mut mod_paths: Vec<PathBuf> = read_dir
+				.filter_map(|entry| entry.ok())
+				.map(|entry| entry.path())
+				.filter(|path| path.is_dir() || vfs::is_archive(path))
+				.collect();
+			mod_paths.sort();
+			for mod_path in mod_paths {
+				vfs.mount_path(&mod_path, vfs::PRIORITY_MOD)
+					.unwrap_or_else(|e| log::error!("Failed to mount mod '{}': {}", mod_path.display(), e));
+			}
+		}
+		Err(e) => match e.kind() {
+			std::io::ErrorKind::NotFound => (),
+			_ => log::warn!("Failed to read mods directory '{}': {}", mods_path.display(), e)
+		}
 	}
-	#[cfg(not(debug_assertions))] 
-	{
-		term_log_level = LevelFilter::Info;
-	}	
-	let term_logger = TermLogger::new(term_log_level, logger_config.clone(), TerminalMode::Mixed, ColorChoice::Auto);
-	let write_logger = WriteLogger::new(term_log_level /*LevelFilter::Info*/, logger_config, log_file);
-    CombinedLogger::init(vec![ term_logger, write_logger ])?;
 
-	Ok(pref_path)
+	Ok(vfs)
 }
 
-fn log_error(e: Box<dyn std::error::Error>)
+fn log_error(e: &dyn std::error::Error)
 {
 	if log::log_enabled!(log::Level::Error) {
 		log::error!("{}", e);
@@ -233,9 +779,10 @@ fn log_error(e: Box<dyn std::error::Error>)
 		.unwrap_or_else(|mbe| log::error!("Failed to create error message box: {}", mbe));
 }
 
-fn get_pref_path(org_name: &str, game_name: &str) -> Result<PathBuf, String>
+fn get_pref_path(org_name: &str, game_name: &str) -> Result<PathBuf, EngineError>
 {
-	let prefix = dirs::data_dir().ok_or("Failed to get preferences path")?;
+	let prefix = dirs::data_dir()
+		.ok_or_else(|| EngineError::Init{ message: "failed to get preferences path".to_string() })?;
 	let pref_path = prefix.join(org_name).join(game_name);
 
 	// try to create the path if it doesn't exist
@@ -246,7 +793,7 @@ fn get_pref_path(org_name: &str, game_name: &str) -> Result<PathBuf, String>
 				println!("Preferences path already exists, skipping creation...");
 				Ok(pref_path)
 			},
-			_ => Err(format!("Failed to create preferences path: {}", e))
+			_ => Err(EngineError::io(&pref_path, e))
 		}
 	}
 }
@@ -0,0 +1,286 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::{ HashMap, HashSet };
+use std::path::Path;
+use std::sync::Mutex;
+use crate::error::EngineError;
+use crate::vfs::Vfs;
+
+/// The language used when the selected one doesn't have a string table, or when a key is missing from it.
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+/// Localized string tables, loaded from "<language>.ini" files in a localization directory. Available to systems as a
+/// unique.
+pub struct Localization
+{
+	language: String,
+	strings: HashMap<String, String>,
+	fallback_strings: HashMap<String, String>,
+
+	// keys that have already been warned about, so that strings shown every frame don't flood the log
+	missing_keys: Mutex<HashSet<String>>
+}
+impl Localization
+{
+	/// Load the string table for `language` from `dir` in the virtual filesystem, along with the English table to fall
+	/// back on for missing keys. If `language` is `None`, the language will be determined from the system locale.
+	pub fn load(vfs: &Vfs, dir: &Path, language: Option<&str>) -> Result<Localization, EngineError>
+	{
+		let requested = match language {
+			Some(l) => l.to_string(),
+			None => system_language()
+		};
+
+		let fallback_path = dir.join(format!("{}.ini", FALLBACK_LANGUAGE));
+		let fallback_strings = match vfs.exists(&fallback_path) {
+			true => load_ini(vfs, &fallback_path)?,
+			false => {
+				log::warn!("Fallback string table '{}' doesn't exist!", fallback_path.display());
+				HashMap::new()
+			}
+		};
+
+		let lang_path = dir.join(format!("{}.ini", requested));
+		let (language, strings) = if requested == FALLBACK_LANGUAGE {
+			(requested, HashMap::new())
+		} else if vfs.exists(&lang_path) {
+			let strings = load_ini(vfs, &lang_path)?;
+			(requested, strings)
+		} else {
+			log::warn!("No string table for language '{}', falling back to '{}'", requested, FALLBACK_LANGUAGE);
+			(FALLBACK_LANGUAGE.to_string(), HashMap::new())
+		};
+		log::info!("Using language '{}'", language);
+
+		Ok(Localization{
+			language: language,
+			strings: strings,
+			fallback_strings: fallback_strings,
+			missing_keys: Mutex::new(HashSet::new())
+		})
+	}
+
+	/// The language that strings are being retrieved in.
+	pub fn language(&self) -> &str
+	{
+		&self.language
+	}
+
+	/// Get the localized string for `key`, falling back to English if the selected language doesn't have it.
+	/// If English doesn't have it either, the key itself is returned so the missing string is easy to spot. A warning
+	/// is logged the first time each missing key is requested.
+	pub fn get<'a>(&'a self, key: &'a str) -> &'a str
+	{
+		match self.strings.get(key).or_else(|| self.fallback_strings.get(key)) {
+			Some(s) => s,
+			None => {
+				let first_time = match self.missing_keys.lock() {
+					Ok(mut missing_keys) => missing_keys.insert(key.to_string()),
+					Err(_) => true
+				};
+				if first_time {
+					log::warn!("Missing localized string '{}'", key);
+				}
+				key
+			}
+		}
+	}
+
+	/// Get the localized string for `key`, replacing each `{name}` in it with the value given for `name` in `args`.
+	/// Placeholders without a value in `args` are left as they are.
+	pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String
+	{
+		substitute_args(self.get(key), args)
+	}
+}
+
+/// Replace each `{name}` in `s` with the value given for `name` in `args`.
+pub fn substitute_args(s: &str, args: &[(&str, &str)]) -> String
+{
+	let mut out = String::with_capacity(s.len());
+	let mut rest = s;
+	while let Some(open) = rest.find('{') {
+		out.push_str(&rest[..open]);
+		let after_open = &rest[open + 1..];
+		match after_open.find('}') {
+			Some(close) => {
+				let name = &after_open[..close];
+				match args.iter().find(|(arg_name, _)| *arg_name == name) {
+					Some((_, value)) => out.push_str(value),
+					None => out.push_str(&rest[open..open + close + 2])
+				}
+				rest = &after_open[close + 1..];
+			}
+			None => {
+				out.push_str(&rest[open..]);
+				rest = "";
+			}
+		}
+	}
+	out.push_str(rest);
+	out
+}
+
+fn load_ini(vfs: &Vfs, path: &Path) -> Result<HashMap<String, String>, EngineError>
+{
+	log::info!("Loading string table '{}'...", path.display());
+	let ini_string = vfs.read_to_string(path)?;
+	parse_ini(&ini_string).map_err(|e| EngineError::decode(path, e))
+}
+
+/// Parse a string table in INI format. Lines beginning with ";" are comments, and a line with `[section]` prefixes the
+/// keys after it with "section.". The escape sequences `\n`, `\t`, and `\\` are recognized in values.
+pub fn parse_ini(ini_string: &str) -> Result<HashMap<String, String>, String>
+{
+	let mut strings = HashMap::new();
+	let mut section = String::new();
+
+	// skip the UTF-8 byte order mark, if there is one
+	let without_bom = ini_string.strip_prefix('\u{feff}').unwrap_or(ini_string);
+
+	for (i, line) in without_bom.lines().enumerate() {
+		let trimmed = line.trim();
+		if trimmed.is_empty() || trimmed.starts_with(';') {
+			continue;
+		}
+
+		if trimmed.starts_with('[') && trimmed.ends_with(']') {
+			section = format!("{}.", trimmed[1..trimmed.len() - 1].trim());
+			continue;
+		}
+
+		match trimmed.split_once('=') {
+			Some((key, value)) => {
+				strings.insert(format!("{}{}", section, key.trim()), unescape(value.trim()));
+			}
+			None => return Err(format!("line {} is not in `key=string` format", i + 1))
+		}
+	}
+
+	Ok(strings)
+}
+
+fn unescape(s: &str) -> String
+{
+	let mut out = String::with_capacity(s.len());
+	let mut chars = s.chars();
+	while let Some(c) = chars.next() {
+		if c == '\\' {
+			match chars.next() {
+				Some('n') => out.push('\n'),
+				Some('t') => out.push('\t'),
+				Some('\\') => out.push('\\'),
+				Some(other) => {
+					out.push('\\');
+					out.push(other);
+				}
+				None => out.push('\\')
+			}
+		} else {
+			out.push(c);
+		}
+	}
+	out
+}
+
+/// Determine the language code (e.g. "en", "ja") from the system locale, using the same environment variables as POSIX
+/// locales. Returns the fallback language if it can't be determined.
+fn system_language() -> String
+{
+	for var in [ "LC_ALL", "LC_MESSAGES", "LANG" ] {
+		match std::env::var(var) {
+			Ok(locale) => {
+				// "ja_JP.UTF-8" -> "ja"
+				let lang = locale.split(|c| c == '_' || c == '.' || c == '@' || c == '-').next().unwrap_or("");
+				if !lang.is_empty() && lang != "C" && lang != "POSIX" {
+					return lang.to_lowercase()
+				}
+			}
+			Err(_) => ()
+		}
+	}
+	FALLBACK_LANGUAGE.to_string()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn ini_comments_sections_and_bom()
+	{
+		let ini = concat!(
+			"\u{feff}; title screen\n",
+			"title = Mithril\n",
+			"\n",
+			"[menu]\n",
+			"  start=Start Game  \n",
+			"; quit=Quit\n",
+			"[ options ]\n",
+			"volume=Volume\n"
+		);
+		let strings = parse_ini(ini).unwrap();
+		assert_eq!(strings.len(), 3);
+		assert_eq!(strings["title"], "Mithril");
+		assert_eq!(strings["menu.start"], "Start Game");
+		assert_eq!(strings["options.volume"], "Volume");
+	}
+
+	#[test]
+	fn ini_escapes()
+	{
+		let strings = parse_ini(r"text=line 1\nline 2\tindented \\ backslash \q\").unwrap();
+		assert_eq!(strings["text"], "line 1\nline 2\tindented \\ backslash \\q\\");
+	}
+
+	#[test]
+	fn ini_duplicate_keys_use_the_last_one()
+	{
+		let strings = parse_ini("greeting=Hello\ngreeting=Hi\n[a]\nx=1\n[b]\nx=2").unwrap();
+		assert_eq!(strings["greeting"], "Hi");
+		assert_eq!(strings["a.x"], "1");
+		assert_eq!(strings["b.x"], "2");
+	}
+
+	#[test]
+	fn ini_invalid_line()
+	{
+		let err = parse_ini("title=Mithril\njust some text").unwrap_err();
+		assert!(err.contains("line 2"), "{}", err);
+	}
+
+	#[test]
+	fn args()
+	{
+		assert_eq!(substitute_args("{name} has {count} coins", &[ ("name", "Ann"), ("count", "5") ]), "Ann has 5 coins");
+		assert_eq!(substitute_args("{a}{a}", &[ ("a", "x") ]), "xx");
+
+		// placeholders without a value and unclosed braces are kept as they are
+		assert_eq!(substitute_args("{name} got {item}", &[ ("name", "Ann") ]), "Ann got {item}");
+		assert_eq!(substitute_args("{name} {unclosed", &[ ("name", "Ann") ]), "Ann {unclosed");
+		assert_eq!(substitute_args("no args", &[ ("unused", "x") ]), "no args");
+	}
+
+	#[test]
+	fn missing_keys()
+	{
+		let localization = Localization{
+			language: "ja".to_string(),
+			strings: [ ("title".to_string(), "ミスリル".to_string()) ].into_iter().collect(),
+			fallback_strings: [
+				("title".to_string(), "Mithril".to_string()),
+				("start".to_string(), "Start".to_string())
+			].into_iter().collect(),
+			missing_keys: Mutex::new(HashSet::new())
+		};
+		assert_eq!(localization.get("title"), "ミスリル");
+		assert_eq!(localization.get("start"), "Start");
+		assert_eq!(localization.get("quit"), "quit");
+		assert_eq!(localization.get("quit"), "quit");
+		assert_eq!(localization.missing_keys.lock().unwrap().len(), 1);
+	}
+}
@@ -0,0 +1,124 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::fs::File;
+use std::path::{ Path, PathBuf };
+use log::{ LevelFilter, Log, Metadata, Record };
+use simplelog::{ Config, SharedLogger };
+use crate::error::EngineError;
+
+/// Wraps a logger, filtering messages by the level set for the module they came from, or by `default_level` if there
+/// isn't one. The wrapped logger should be created with `LevelFilter::Trace` so that it doesn't filter anything itself.
+pub(crate) struct ModuleFilterLogger
+{
+	inner: Box<dyn SharedLogger>,
+	default_level: LevelFilter,
+	module_levels: Vec<(String, LevelFilter)>
+}
+impl ModuleFilterLogger
+{
+	pub fn new(inner: Box<dyn SharedLogger>, default_level: LevelFilter, module_levels: &[(String, LevelFilter)])
+		-> Box<ModuleFilterLogger>
+	{
+		// sort by descending length so that the most specific module gets matched first
+		let mut module_levels = module_levels.to_vec();
+		module_levels.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+
+		Box::new(ModuleFilterLogger{ inner: inner, default_level: default_level, module_levels: module_levels })
+	}
+
+	fn level_for(&self, target: &str) -> LevelFilter
+	{
+		self.module_levels.iter()
+			.find(|(module, _)| is_in_module(target, module))
+			.map(|(_, level)| *level)
+			.unwrap_or(self.default_level)
+	}
+}
+impl Log for ModuleFilterLogger
+{
+	fn enabled(&self, metadata: &Metadata) -> bool
+	{
+		metadata.level() <= self.level_for(metadata.target())
+	}
+
+	fn log(&self, record: &Record)
+	{
+		if self.enabled(record.metadata()) {
+			self.inner.log(record);
+		}
+	}
+
+	fn flush(&self)
+	{
+		self.inner.flush();
+	}
+}
+impl SharedLogger for ModuleFilterLogger
+{
+	// the highest level of any module, so that messages from verbose modules aren't discarded before reaching us
+	fn level(&self) -> LevelFilter
+	{
+		self.module_levels.iter().map(|(_, level)| *level).fold(self.default_level, std::cmp::max)
+	}
+
+	fn config(&self) -> Option<&Config>
+	{
+		self.inner.config()
+	}
+
+	fn as_log(self: Box<Self>) -> Box<dyn Log>
+	{
+		Box::new(*self)
+	}
+}
+
+// Check if `target` is `module` or one of its submodules.
+fn is_in_module(target: &str, module: &str) -> bool
+{
+	match target.strip_prefix(module) {
+		Some(rest) => rest.is_empty() || rest.starts_with("::"),
+		None => false
+	}
+}
+
+/// Create a new log file with a timestamp in its name in `logs_dir`, deleting the oldest log files so that at most
+/// `keep_files` remain including the new one.
+pub(crate) fn create_rotated_log_file(logs_dir: &Path, keep_files: usize) -> Result<(File, PathBuf), EngineError>
+{
+	std::fs::create_dir_all(logs_dir).map_err(|e| EngineError::io(logs_dir, e))?;
+
+	// the timestamp format sorts in chronological order, so the file names can be sorted directly
+	let mut old_logs: Vec<PathBuf> = std::fs::read_dir(logs_dir)
+		.map_err(|e| EngineError::io(logs_dir, e))?
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| {
+			let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+			file_name.starts_with("game_") && file_name.ends_with(".log")
+		})
+		.collect();
+	old_logs.sort();
+
+	let remove_count = (old_logs.len() + 1).saturating_sub(keep_files.max(1));
+	for old_log in old_logs.iter().take(remove_count) {
+		match std::fs::remove_file(old_log) {
+			Ok(()) => (),
+			Err(e) => println!("WARNING: Failed to remove old log file '{}': {}", old_log.display(), e)
+		}
+	}
+
+	let log_file_path = logs_dir.join(format!("game_{}.log", timestamp()));
+	let log_file = File::create(&log_file_path).map_err(|e| EngineError::io(&log_file_path, e))?;
+	Ok((log_file, log_file_path))
+}
+
+// Get the current local time (or UTC if the local time zone can't be determined) for use in file names.
+fn timestamp() -> String
+{
+	let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+	let format = time::macros::format_description!("[year][month][day]-[hour][minute][second]");
+	now.format(format).unwrap_or_else(|_| now.unix_timestamp().to_string())
+}
@@ -0,0 +1,195 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::path::PathBuf;
+use glam::*;
+use serde::Deserialize;
+use serde_yaml::Mapping;
+use shipyard::{ EntityId, World, UniqueView, UniqueViewMut };
+use crate::render::RenderContext;
+use crate::component::{ self, ui, light, model::Model };
+use crate::component::camera::{ CameraTarget, MapCamera };
+use crate::component::ui::canvas::Canvas;
+use super::{ parse_fields, EntityClassRegistry };
+
+/// Location where the player can save the game.
+pub struct SavePoint;
+
+/// Non-player character, with an optional script to run when the player interacts with it.
+pub struct Npc
+{
+	pub on_interact: Option<String>
+}
+
+/// UI button with scripts to run when it gets pressed or released.
+pub struct UiButton
+{
+	pub align: Vec2,
+	pub on_press: Option<String>,
+	pub on_release: Option<String>
+}
+
+/// Reference to a UI prefab file, which describes a group of UI elements.
+pub struct UiPrefab
+{
+	pub file: PathBuf
+}
+
+/// Register the entity classes built into the engine.
+pub fn register_engine_classes(registry: &mut EntityClassRegistry)
+{
+	registry.register("light_environment", new_light_environment);
+	registry.register("light", new_light);
+	registry.register("light_spot", new_light_spot);
+	registry.register("camera", new_camera);
+	registry.register("prop", new_prop);
+	registry.register("npc", new_npc);
+	registry.register("save_point", new_save_point);
+	registry.register("ui_button", new_ui_button);
+	registry.register("ui_prefab", new_ui_prefab);
+}
+
+type ConstructorResult = Result<EntityId, Box<dyn std::error::Error>>;
+
+fn new_light_environment(world: &mut World, _render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: LightEnvironmentDef = parse_fields(mapping)?;
+	Ok(world.add_entity((light::DirectionalLight{ angle: def.angle, color: def.color },)))
+}
+
+fn new_light(world: &mut World, render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: LightDef = parse_fields(mapping)?;
+	let transform = component::Transform::new(render_ctx, def.pos, Vec3::ONE)?;
+	Ok(world.add_entity((transform, light::PointLight{ color: def.color, range: def.range })))
+}
+
+fn new_light_spot(world: &mut World, render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: LightSpotDef = parse_fields(mapping)?;
+	let transform = component::Transform::new(render_ctx, def.pos, Vec3::ONE)?;
+	let spot_light = light::SpotLight{
+		angle: def.angle,
+		color: def.color,
+		range: def.range,
+		inner_cutoff: def.inner_cutoff,
+		outer_cutoff: def.outer_cutoff
+	};
+	Ok(world.add_entity((transform, spot_light)))
+}
+
+fn new_camera(world: &mut World, _render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: CameraDef = parse_fields(mapping)?;
+	Ok(world.add_entity((MapCamera{ pos: def.pos, target: def.target },)))
+}
+
+fn new_prop(world: &mut World, render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: PropDef = parse_fields(mapping)?;
+	let transform = component::Transform::new(render_ctx, def.pos, Vec3::ONE)?;
+	Ok(world.add_entity((transform, Model::new(def.file))))
+}
+
+fn new_npc(world: &mut World, render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: NpcDef = parse_fields(mapping)?;
+	let transform = component::Transform::new(render_ctx, def.pos, Vec3::ONE)?;
+	Ok(world.add_entity((transform, Model::new(def.mesh), Npc{ on_interact: def.on_interact })))
+}
+
+fn new_save_point(world: &mut World, render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: PosOnlyDef = parse_fields(mapping)?;
+	let transform = component::Transform::new(render_ctx, def.pos, Vec3::ONE)?;
+	Ok(world.add_entity((transform, SavePoint)))
+}
+
+fn new_ui_button(world: &mut World, render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: UiButtonDef = parse_fields(mapping)?;
+	let mut transform = ui::Transform::new(def.pos.truncate().as_ivec2(), Vec2::ONE);
+	match world.borrow::<UniqueView<Canvas>>() {
+		Ok(canvas) => transform.update_projection(render_ctx, canvas.projection())?,
+		Err(_) => ()
+	}
+	let button = UiButton{ align: def.align, on_press: def.on_press, on_release: def.on_release };
+	let eid = world.add_entity((transform, button));
+	match world.borrow::<UniqueViewMut<Canvas>>() {
+		Ok(mut canvas) => canvas.add_child(eid),
+		Err(_) => ()
+	}
+	Ok(eid)
+}
+
+fn new_ui_prefab(world: &mut World, _render_ctx: &mut RenderContext, mapping: &Mapping) -> ConstructorResult
+{
+	let def: UiPrefabDef = parse_fields(mapping)?;
+	Ok(world.add_entity((UiPrefab{ file: def.file },)))
+}
+
+#[derive(Deserialize)]
+struct LightEnvironmentDef
+{
+	angle: Vec3,
+	color: Vec4
+}
+#[derive(Deserialize)]
+struct LightDef
+{
+	pos: Vec3,
+	color: Vec4,
+	range: f32
+}
+#[derive(Deserialize)]
+struct LightSpotDef
+{
+	pos: Vec3,
+	angle: Vec3,
+	color: Vec4,
+	range: f32,
+	inner_cutoff: f32,
+	outer_cutoff: f32
+}
+#[derive(Deserialize)]
+struct CameraDef
+{
+	pos: Vec3,
+	target: CameraTarget
+}
+#[derive(Deserialize)]
+struct PropDef
+{
+	file: PathBuf,
+	pos: Vec3
+}
+#[derive(Deserialize)]
+struct NpcDef
+{
+	pos: Vec3,
+	mesh: PathBuf,
+	on_interact: Option<String>
+}
+#[derive(Deserialize)]
+struct PosOnlyDef
+{
+	pos: Vec3
+}
+#[derive(Deserialize)]
+struct UiButtonDef
+{
+	pos: Vec3,
+	#[serde(default)]
+	align: Vec2,
+	#[serde(rename = "onPress")]
+	on_press: Option<String>,
+	#[serde(rename = "onRelease")]
+	on_release: Option<String>
+}
+#[derive(Deserialize)]
+struct UiPrefabDef
+{
+	file: PathBuf
+}
@@ -0,0 +1,327 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+pub mod registry;
+pub mod engine_classes;
+
+use std::path::{ Path, PathBuf };
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_yaml::{ Mapping, Value };
+use shipyard::{ EntityId, World, View, ViewMut, Get, EntitiesViewMut, UniqueView, UniqueViewMut };
+use crate::render::RenderContext;
+use crate::error::EngineError;
+use crate::component::camera::{ Camera, CameraTarget, MapCamera };
+pub use registry::{ EntityClassRegistry, EntityConstructor };
+
+/// Component added to every entity spawned from a map, so it can be identified and removed when the map gets unloaded.
+pub struct MapEntity
+{
+	pub class: String,
+	pub name: Option<String>
+}
+
+/// Information about the currently loaded map, available as a unique while a map is loaded.
+pub struct LoadedMap
+{
+	path: PathBuf,
+	skybox: Option<String>,
+	entities: Vec<EntityId>
+}
+impl LoadedMap
+{
+	pub fn path(&self) -> &Path
+	{
+		&self.path
+	}
+
+	/// The skybox texture path pattern, where "%s" is to be replaced with the face suffix ("lf", "ft", "up", etc.).
+	pub fn skybox(&self) -> Option<&str>
+	{
+		self.skybox.as_deref()
+	}
+
+	pub fn entities(&self) -> &[EntityId]
+	{
+		&self.entities
+	}
+}
+
+#[derive(Deserialize)]
+struct MapFile
+{
+	skybox: Option<String>,
+
+	#[serde(default)]
+	entities: Vec<Value>
+}
+
+/// Load a map from a YAML file in the virtual filesystem, spawning its entities into the world. The currently loaded
+/// map, if any, only gets unloaded after all of the new map's entities have spawned. If any of them fail to spawn, the
+/// ones that were already spawned will be removed, and the current map stays loaded.
+///
+/// Entities are constructed using the `EntityClassRegistry` unique in the world. In debug builds, the map gets loaded
+/// again when its file changes.
+pub fn load_map(world: &mut World, render_ctx: &mut RenderContext, path: &Path) -> Result<(), MapError>
+{
+	log::info!("Loading map '{}'...", path.display());
+
+	// clone the registry so that the world can be mutably borrowed by the constructors
+	let registry = world.run(|registry: UniqueView<EntityClassRegistry>| EntityClassRegistry::clone(&registry))
+		.map_err(|e| MapError::new(path, format!("could not get entity class registry: {}", e)))?;
+
+	let yaml_string = render_ctx.vfs().read_to_string(path)
+		.map_err(|e| MapError::new(path, format!("could not read file: {}", e)))?;
+	let map_file: MapFile = serde_yaml::from_str(&yaml_string)
+		.map_err(|e| MapError::new(path, e.to_string()))?;
+
+	let spawned = spawn_entities(world, &map_file.entities, |world, class, mapping| {
+		registry.construct(class, world, render_ctx, mapping).map_err(|e| e.to_string())
+	}).map_err(|(i, class, e)| MapError{ path: path.to_path_buf(), entity_index: Some(i), class: class, message: e })?;
+	log::info!("Spawned {} map entities", spawned.len());
+
+	// make the first camera with a fixed target the active camera
+	let camera = match create_first_camera(world, render_ctx, &spawned) {
+		Ok(c) => c,
+		Err(e) => {
+			delete_entities(world, &spawned);
+			return Err(MapError::new(path, e.to_string()))
+		}
+	};
+
+	// the new map is ready, so the current one can be replaced now
+	let loaded_map = LoadedMap{ path: path.to_path_buf(), skybox: map_file.skybox, entities: spawned };
+	replace_loaded_map(world, loaded_map).map_err(|e| MapError::new(path, e.to_string()))?;
+	match camera {
+		Some(c) => set_active_camera(world, c).map_err(|e| MapError::new(path, e.to_string()))?,
+		None => ()
+	}
+
+	// reloaded when the file changes during development
+	match crate::vfs::normalize(path) {
+		Ok(logical_path) => render_ctx.watch_file(&logical_path),
+		Err(_) => ()
+	}
+
+	// assets only used by the previous map aren't referenced anymore, now that the new map has taken what it needs
+	render_ctx.evict_unused_assets();
+	if log::log_enabled!(log::Level::Debug) {
+		crate::asset::log_report(&render_ctx.asset_report());
+	}
+
+	Ok(())
+}
+
+/// Unload the currently loaded map, deleting all of the entities spawned from it. Does nothing if no map is loaded.
+pub fn unload_map(world: &mut World)
+{
+	match world.remove_unique::<LoadedMap>() {
+		Ok(loaded_map) => {
+			log::info!("Unloading map '{}'...", loaded_map.path.display());
+			for eid in loaded_map.entities {
+				world.delete_entity(eid);
+			}
+		}
+		Err(_) => ()
+	}
+}
+
+// Spawn all of the entities using `construct` to construct each one by its class. If any of them fail to spawn, the
+// ones that were already spawned get deleted, and the index and class (if it could be determined) of the entity that
+// failed are returned along with the message.
+fn spawn_entities<F>(world: &mut World, entity_values: &[Value], mut construct: F)
+	-> Result<Vec<EntityId>, (usize, Option<String>, String)>
+	where F: FnMut(&mut World, &str, &Mapping) -> Result<EntityId, String>
+{
+	let mut spawned = Vec::with_capacity(entity_values.len());
+	for (i, entity_value) in entity_values.iter().enumerate() {
+		match spawn_entity(world, &mut construct, entity_value) {
+			Ok(eid) => spawned.push(eid),
+			Err((class, e)) => {
+				delete_entities(world, &spawned);
+				return Err((i, class, e))
+			}
+		}
+	}
+	Ok(spawned)
+}
+
+fn delete_entities(world: &mut World, entities: &[EntityId])
+{
+	for eid in entities {
+		world.delete_entity(*eid);
+	}
+}
+
+/// Spawn a single map entity. Returns the class name (if it could be determined) along with the message on error.
+fn spawn_entity<F>(world: &mut World, construct: &mut F, entity_value: &Value)
+	-> Result<EntityId, (Option<String>, String)>
+	where F: FnMut(&mut World, &str, &Mapping) -> Result<EntityId, String>
+{
+	let mapping = entity_value.as_mapping().ok_or((None, "entity is not a mapping".to_string()))?;
+	let class = mapping.get(&Value::from("class")).and_then(|c| c.as_str())
+		.ok_or((None, "missing or invalid `class` field".to_string()))?;
+	let name = mapping.get(&Value::from("name")).and_then(|n| n.as_str()).map(|n| n.to_string());
+	let map_entity = MapEntity{ class: class.to_string(), name: name };
+
+	let eid = construct(world, class, mapping).map_err(|e| (Some(class.to_string()), e))?;
+
+	world.run(|entities: EntitiesViewMut, mut map_entities: ViewMut<MapEntity>| {
+		entities.add_component(eid, &mut map_entities, map_entity)
+	}).map_err(|e| (Some(class.to_string()), e.to_string()))?;
+
+	Ok(eid)
+}
+
+// Create a camera from the first of the entities with a `MapCamera` that has a fixed target, if there is one.
+fn create_first_camera(world: &World, render_ctx: &mut RenderContext, entities: &[EntityId])
+	-> Result<Option<Camera>, EngineError>
+{
+	let first_camera = world.run(|map_cameras: View<MapCamera>| {
+		entities.iter().find_map(|eid| match map_cameras.get(*eid) {
+			Ok(MapCamera{ pos, target: CameraTarget::Position(target) }) => Some((*pos, *target)),
+			_ => None
+		})
+	}).map_err(|e| EngineError::ecs("MapCamera", e))?;
+	match first_camera {
+		Some((pos, target)) => Ok(Some(Camera::new(render_ctx, pos, target)?)),
+		None => Ok(None)
+	}
+}
+
+// Unload the current map, if any, and make `loaded_map` the current one.
+fn replace_loaded_map(world: &mut World, loaded_map: LoadedMap) -> Result<(), EngineError>
+{
+	unload_map(world);
+	world.add_unique(loaded_map).map_err(|e| EngineError::ecs("LoadedMap", e))
+}
+
+fn set_active_camera(world: &mut World, camera: Camera) -> Result<(), EngineError>
+{
+	// wrap the camera in an `Option` so that it can be added as a unique after the failed borrow has been dropped, if
+	// the world doesn't have a camera yet
+	let mut camera_opt = Some(camera);
+	match world.borrow::<UniqueViewMut<Camera>>() {
+		Ok(mut active) => *active = camera_opt.take().unwrap(),
+		Err(_) => ()
+	}
+	match camera_opt {
+		Some(c) => world.add_unique(c).map_err(|e| EngineError::ecs("Camera", e))?,
+		None => ()
+	}
+	Ok(())
+}
+
+/// Deserialize the fields of a map entity into `T`. Entity class constructors can use this to read the fields they need,
+/// ignoring any others like `class` and `name`.
+pub fn parse_fields<T: DeserializeOwned>(mapping: &Mapping) -> Result<T, serde_yaml::Error>
+{
+	serde_yaml::from_value(Value::Mapping(mapping.clone()))
+}
+
+/// Error that occurred while loading a map, along with the index and class of the offending entity if the error was
+/// caused by one.
+#[derive(Debug)]
+pub struct MapError
+{
+	path: PathBuf,
+	entity_index: Option<usize>,
+	class: Option<String>,
+	message: String
+}
+impl MapError
+{
+	fn new(path: &Path, message: String) -> MapError
+	{
+		MapError{ path: path.to_path_buf(), entity_index: None, class: None, message: message }
+	}
+
+	pub fn entity_index(&self) -> Option<usize>
+	{
+		self.entity_index
+	}
+}
+impl std::error::Error for MapError {}
+impl std::fmt::Display for MapError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "failed to load map '{}'", self.path.display())?;
+		match (self.entity_index, &self.class) {
+			(Some(i), Some(c)) => write!(f, ": entity {} (class '{}')", i, c)?,
+			(Some(i), None) => write!(f, ": entity {}", i)?,
+			_ => ()
+		}
+		write!(f, ": {}", self.message)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use shipyard::iter::IntoIter;
+	use super::*;
+
+	// Deleting the most recently added component trips an out-of-bounds check inside shipyard 0.5 on newer compilers,
+	// which aborts the test run, so the maps in these tests are laid out so that it never happens.
+
+	struct Marker;
+
+	fn entity(class: &str) -> Value
+	{
+		let mut mapping = Mapping::new();
+		mapping.insert(Value::from("class"), Value::from(class));
+		Value::Mapping(mapping)
+	}
+
+	// constructs "marker" entities, and fails for any other class like an unregistered one would
+	fn construct(world: &mut World, class: &str, _: &Mapping) -> Result<EntityId, String>
+	{
+		match class {
+			"marker" => Ok(world.add_entity((Marker,))),
+			_ => Err(format!("unknown entity class '{}'", class))
+		}
+	}
+
+	fn load_test_map(world: &mut World, entity_values: &[Value]) -> Result<Vec<EntityId>, (usize, Option<String>, String)>
+	{
+		let spawned = spawn_entities(world, entity_values, construct)?;
+		let loaded_map = LoadedMap{ path: PathBuf::from("maps/test.yaml"), skybox: None, entities: spawned.clone() };
+		replace_loaded_map(world, loaded_map).unwrap();
+		Ok(spawned)
+	}
+
+	fn marker_count(world: &World) -> usize
+	{
+		world.borrow::<View<Marker>>().unwrap().iter().count()
+	}
+
+	#[test]
+	fn reload_replaces_entities()
+	{
+		let mut world = World::new();
+		let old_entities = load_test_map(&mut world, &[ entity("marker"), entity("marker") ]).unwrap();
+		let new_entities = load_test_map(&mut world, &[ entity("marker"), entity("marker") ]).unwrap();
+
+		assert_eq!(marker_count(&world), 2);
+		assert_eq!(world.borrow::<UniqueView<LoadedMap>>().unwrap().entities(), &new_entities[..]);
+		let markers = world.borrow::<View<Marker>>().unwrap();
+		assert!(old_entities.iter().all(|eid| markers.get(*eid).is_err()));
+	}
+
+	#[test]
+	fn failed_reload_keeps_current_map()
+	{
+		let mut world = World::new();
+		let entities = load_test_map(&mut world, &[ entity("marker"), entity("marker") ]).unwrap();
+
+		let (index, class, _) = load_test_map(&mut world, &[ entity("typo"), entity("marker") ]).unwrap_err();
+		assert_eq!(index, 0);
+		assert_eq!(class.as_deref(), Some("typo"));
+
+		assert_eq!(marker_count(&world), 2);
+		assert_eq!(world.borrow::<UniqueView<LoadedMap>>().unwrap().entities(), &entities[..]);
+		let markers = world.borrow::<View<Marker>>().unwrap();
+		assert!(entities.iter().all(|eid| markers.get(*eid).is_ok()));
+	}
+}
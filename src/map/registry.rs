@@ -0,0 +1,81 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::sync::Arc;
+use std::collections::BTreeMap;
+use serde_yaml::Mapping;
+use shipyard::{ EntityId, World };
+use crate::render::RenderContext;
+
+/// Function that spawns an entity of a specific class into the world, given the entity's fields from the map file.
+pub type EntityConstructor = Arc<
+	dyn Fn(&mut World, &mut RenderContext, &Mapping) -> Result<EntityId, Box<dyn std::error::Error>> + Send + Sync
+>;
+
+/// Registry of constructors for each entity class that can be used in map files, available as a unique.
+/// Games can register their own classes in here, or replace the ones built into the engine.
+#[derive(Clone)]
+pub struct EntityClassRegistry
+{
+	constructors: BTreeMap<String, EntityConstructor>
+}
+impl EntityClassRegistry
+{
+	/// Create an empty registry, without even the classes built into the engine.
+	pub fn new() -> EntityClassRegistry
+	{
+		EntityClassRegistry{ constructors: BTreeMap::new() }
+	}
+
+	/// Create a registry with the classes built into the engine already registered.
+	pub fn with_engine_classes() -> EntityClassRegistry
+	{
+		let mut registry = Self::new();
+		super::engine_classes::register_engine_classes(&mut registry);
+		registry
+	}
+
+	/// Register the constructor for the given class name, replacing the existing one if the class was already
+	/// registered.
+	pub fn register<F>(&mut self, class: &str, constructor: F)
+		where F: Fn(&mut World, &mut RenderContext, &Mapping) -> Result<EntityId, Box<dyn std::error::Error>> 
+			+ Send + Sync + 'static
+	{
+		if self.constructors.insert(class.to_string(), Arc::new(constructor)).is_some() {
+			log::debug!("Replaced constructor for entity class '{}'", class);
+		}
+	}
+
+	pub fn unregister(&mut self, class: &str)
+	{
+		self.constructors.remove(class);
+	}
+
+	pub fn contains(&self, class: &str) -> bool
+	{
+		self.constructors.contains_key(class)
+	}
+
+	/// Get the names of all registered classes, in alphabetical order.
+	pub fn classes(&self) -> impl Iterator<Item = &str>
+	{
+		self.constructors.keys().map(|c| c.as_str())
+	}
+
+	/// Spawn an entity of the given class.
+	pub fn construct(&self, class: &str, world: &mut World, render_ctx: &mut RenderContext, mapping: &Mapping)
+		-> Result<EntityId, Box<dyn std::error::Error>>
+	{
+		let constructor = self.constructors.get(class).ok_or(format!("unknown entity class '{}'", class))?;
+		constructor(world, render_ctx, mapping)
+	}
+}
+impl Default for EntityClassRegistry
+{
+	fn default() -> Self
+	{
+		Self::new()
+	}
+}
@@ -0,0 +1,42 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::path::Path;
+use crate::error::EngineError;
+use crate::asset::Asset;
+use crate::vfs::Vfs;
+
+/// A font loaded from a TrueType or OpenType file, for rasterizing text.
+pub struct Font
+{
+	font: rusttype::Font<'static>,
+	data_size: u64
+}
+impl Font
+{
+	/// Load a font from the file at the logical path in the virtual filesystem.
+	pub fn new(vfs: &Vfs, path: &Path) -> Result<Font, EngineError>
+	{
+		let font_data = vfs.read(path)?;
+		let data_size = font_data.len() as u64;
+		let font = rusttype::Font::try_from_vec(font_data)
+			.ok_or_else(|| EngineError::decode(path, "error constructing font"))?;
+
+		Ok(Font{ font: font, data_size: data_size })
+	}
+
+	pub fn font(&self) -> &rusttype::Font<'static>
+	{
+		&self.font
+	}
+}
+impl Asset for Font
+{
+	// the size of the font file, which is kept in memory for as long as the font is loaded
+	fn memory_size(&self) -> u64
+	{
+		self.data_size
+	}
+}
@@ -4,17 +4,23 @@
 	Copyright (c) 2021-2022, daigennki (@daigennki)
 ----------------------------------------------------------------------------- */
 mod swapchain;
+mod offscreen;
+mod render_pass;
+pub mod window;
 pub mod pipeline;
 pub mod texture;
+pub mod font;
+pub mod uniform_ring;
 
 use std::rc::Rc;
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::sync::mpsc::{ Sender, Receiver };
 use vulkano_win::VkSurfaceBuild;
 use winit::window::WindowBuilder;
 use vulkano::device::physical::{ PhysicalDeviceType, PhysicalDevice, QueueFamily };
-use vulkano::device::{ DeviceCreationError, Queue };
-use vulkano::command_buffer::{ AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, DrawError };
+use vulkano::device::Queue;
+use vulkano::command_buffer::{ AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer };
 use vulkano::command_buffer::{ SubpassContents };
 use vulkano::pipeline::PipelineBindPoint;
 use vulkano::pipeline::graphics::vertex_input::VertexBuffersCollection;
@@ -24,14 +30,31 @@ use vulkano::descriptor_set::{
 };
 use vulkano::format::{ Format };
 use vulkano::buffer::{ ImmutableBuffer, BufferUsage, TypedBufferAccess, cpu_access::CpuAccessibleBuffer };
-use vulkano::memory::DeviceMemoryAllocationError;
 use vulkano::sync::{ GpuFuture };
 use vulkano::image::{ ImageDimensions, MipmapsCount };
+use vulkano::render_pass::{ RenderPass, Framebuffer };
+use crate::error::EngineError;
+use crate::vfs::Vfs;
+use crate::asset::{ AssetCache, AssetInfo, AssetState, Handle, LoadingProgress, WorkerPool };
+use crate::hot_reload::FileWatcher;
+use crate::settings::{ AntiAliasing, VideoSettings };
+
+/// How many frames the CPU may get ahead of the GPU. Resources that get written every frame, such as uniform buffers
+/// in `uniform_ring::UniformRing`, need a separate copy for each frame in flight.
+pub const FRAMES_IN_FLIGHT: usize = 2;
 
 pub struct RenderContext
 {
 	vk_dev: Arc<vulkano::device::Device>,
-	swapchain: swapchain::Swapchain,
+	target: RenderTarget,
+
+	// which of the `FRAMES_IN_FLIGHT` frames is currently being recorded
+	frame_index: usize,
+
+	// Resources that were replaced while a frame in flight might still be using them, kept for each frame until it has
+	// finished. This must be after `target` so that the frames get waited on before these get dropped.
+	deferred_destruction: Vec<Vec<Box<dyn std::any::Any>>>,
+
 	dev_queue: Arc<vulkano::device::Queue>,
 	cur_cb: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
 
@@ -44,66 +67,203 @@ pub struct RenderContext
 	material_pipelines: HashMap<String, Rc<pipeline::Pipeline>>,
 
 	bound_pipeline: std::rc::Weak<pipeline::Pipeline>,
+
+	depth_format: Format,
+
+	// number of samples per pixel for multisample anti-aliasing, or 1 if it's disabled
+	samples: u32,
+
+	vfs: Vfs,
+
+	textures: AssetCache<texture::Texture>,
+	fonts: AssetCache<font::Font>,
+
+	// textures being decoded in the background are sent back through this channel to be uploaded on the main thread
+	asset_workers: WorkerPool,
+	decoded_sender: Sender<DecodedTexture>,
+	decoded_receiver: Receiver<DecodedTexture>,
+	loading_progress: LoadingProgress,
+
+	// watches the files of loaded assets so they can be reloaded when they change; only used in debug builds
+	file_watcher: Option<FileWatcher>,
+
+	// bound in place of textures that are still loading or failed to load
+	placeholder_texture: Arc<texture::Texture>,
 	
 	// TODO: put non-material shaders (shadow filtering, post processing) into different containers
 }
 impl RenderContext
 {
-	pub fn new(game_name: &str, event_loop: &winit::event_loop::EventLoop<()>) 
-		-> Result<RenderContext, Box<dyn std::error::Error>>
+	pub fn new(
+		game_name: &str, 
+		window_title: &str, 
+		video: &VideoSettings, 
+		device_selection: &DeviceSelection,
+		event_loop: &winit::event_loop::EventLoop<()>,
+		vfs: Vfs
+	) 
+		-> Result<RenderContext, EngineError>
 	{
-		let vkinst = create_vulkan_instance(game_name)?;
-		let (physical_device, q_fam) = get_physical_device(&vkinst)?;
-		let (vk_dev, mut queues) = create_vk_logical_device(physical_device, [(q_fam, 0.5)])?;
-		let dev_queue = queues.next().ok_or("No queues are available!")?;
+		let vkinst = create_vulkan_instance(game_name, vulkano_win::required_extensions())?;
+		let dev_extensions = vulkano::device::DeviceExtensions{
+			khr_swapchain: true,
+			..vulkano::device::DeviceExtensions::none()
+		};
+		let (physical_device, q_fam) = get_physical_device(&vkinst, device_selection, &dev_extensions)?;
+		let (vk_dev, mut queues) = create_vk_logical_device(physical_device, [(q_fam, 0.5)], dev_extensions)?;
+		let dev_queue = queues.next().ok_or(EngineError::vulkan("getting device queue", "no queues are available"))?;
 
-		// create window
+		// create window, which is only shown after it's been set to the window mode in the settings
 		let window_surface = WindowBuilder::new()
-			.with_inner_size(winit::dpi::PhysicalSize::new(1280, 720))
-			.with_title(game_name)
-			.with_resizable(false)
-			.build_vk_surface(&event_loop, vk_dev.instance().clone())?;
+			.with_inner_size(winit::dpi::PhysicalSize::new(video.resolution[0], video.resolution[1]))
+			.with_title(window_title)
+			.with_resizable(true)
+			.with_visible(false)
+			.build_vk_surface(&event_loop, vk_dev.instance().clone())
+			.map_err(|e| EngineError::vulkan("creating window surface", e))?;
+		window::apply_video_settings(window_surface.window(), video);
+		window_surface.window().set_visible(true);
 
 		// create swapchain
-		let swapchain = swapchain::Swapchain::new(vk_dev.clone(), window_surface)?;
-		let dim = swapchain.dimensions();
+		let depth_format = select_depth_format(vk_dev.physical_device())?;
+		let samples = select_sample_count(vk_dev.physical_device(), video.antialiasing);
+		let swapchain = swapchain::Swapchain::new(
+			vk_dev.clone(), window_surface, depth_format, samples, video.present_mode, video.swapchain_images
+		)?;
+		
+		Self::new_with_target(vk_dev, dev_queue, RenderTarget::Swapchain(swapchain), depth_format, samples, vfs)
+	}
+
+	/// Create a render context that renders into an image in memory instead of a window, for use on machines without a
+	/// display. The image has the size of the resolution in the video settings. It can be read back with
+	/// `read_offscreen_pixels` or `save_offscreen_png`.
+	pub fn new_offscreen(game_name: &str, video: &VideoSettings, device_selection: &DeviceSelection, vfs: Vfs) 
+		-> Result<RenderContext, EngineError>
+	{
+		let vkinst = create_vulkan_instance(game_name, vulkano::instance::InstanceExtensions::none())?;
+		let dev_extensions = vulkano::device::DeviceExtensions::none();
+		let (physical_device, q_fam) = get_physical_device(&vkinst, device_selection, &dev_extensions)?;
+		let (vk_dev, mut queues) = create_vk_logical_device(physical_device, [(q_fam, 0.5)], dev_extensions)?;
+		let dev_queue = queues.next().ok_or(EngineError::vulkan("getting device queue", "no queues are available"))?;
+
+		let depth_format = select_depth_format(vk_dev.physical_device())?;
+		let samples = select_sample_count(vk_dev.physical_device(), video.antialiasing);
+		let offscreen = offscreen::Offscreen::new(vk_dev.clone(), video.resolution, depth_format, samples)?;
+
+		Self::new_with_target(vk_dev, dev_queue, RenderTarget::Offscreen(offscreen), depth_format, samples, vfs)
+	}
+
+	fn new_with_target(
+		vk_dev: Arc<vulkano::device::Device>, 
+		dev_queue: Arc<Queue>, 
+		target: RenderTarget, 
+		depth_format: Format,
+		samples: u32,
+		vfs: Vfs
+	) -> Result<RenderContext, EngineError>
+	{
+		let dim = target.dimensions();
 		
 		let mut material_pipelines = HashMap::new();
 
 		// create UI pipeline
 		material_pipelines.insert(
 			"UI".to_string(),
-			Rc::new(pipeline::Pipeline::new_from_yaml("ui.yaml", swapchain.render_pass(), dim[0], dim[1])?)
+			Rc::new(pipeline::Pipeline::new_from_yaml("ui.yaml", target.render_pass(), dim[0], dim[1], &vfs)?)
 		);
 
 		// create 3D pipeline
 		material_pipelines.insert(
 			"World".to_string(),
-			Rc::new(pipeline::Pipeline::new_from_yaml("world.yaml", swapchain.render_pass(), dim[0], dim[1])?)
+			Rc::new(pipeline::Pipeline::new_from_yaml("world.yaml", target.render_pass(), dim[0], dim[1], &vfs)?)
 		);
 
-		let cur_cb = AutoCommandBufferBuilder::primary(vk_dev.clone(), q_fam, CommandBufferUsage::OneTimeSubmit)?;
+		let q_fam = dev_queue.family();
+		let cur_cb = AutoCommandBufferBuilder::primary(vk_dev.clone(), q_fam, CommandBufferUsage::OneTimeSubmit)
+			.map_err(|e| EngineError::vulkan("creating command buffer", e))?;
+
+		// grey and dark grey checkerboard, so that it's noticeable when a texture is missing
+		let (placeholder_texture, placeholder_future) = texture::Texture::new_from_iter(
+			dev_queue.clone(),
+			[ 128u8, 128, 128, 255, 64, 64, 64, 255, 64, 64, 64, 255, 128, 128, 128, 255 ],
+			Format::R8G8B8A8_SRGB,
+			ImageDimensions::Dim2d{ width: 2, height: 2, array_layers: 1 },
+			MipmapsCount::One
+		)?;
+
+		let (decoded_sender, decoded_receiver) = std::sync::mpsc::channel();
+
+		let file_watcher = match cfg!(debug_assertions) {
+			true => {
+				let mut watcher = FileWatcher::new(vfs.clone());
+				for source_file in material_pipelines.values().flat_map(|pl| pl.source_files()) {
+					watcher.watch(source_file);
+				}
+				Some(watcher)
+			}
+			false => None
+		};
 			
 		Ok(RenderContext{
 			vk_dev: vk_dev,
-			swapchain: swapchain,
+			target: target,
+			frame_index: 0,
+			deferred_destruction: (0..FRAMES_IN_FLIGHT).map(|_| Vec::new()).collect(),
 			dev_queue: dev_queue,
 			cur_cb: cur_cb,
-			upload_futures: None,
-			upload_futures_count: 0,
+			upload_futures: Some(placeholder_future.boxed()),
+			upload_futures_count: 1,
 			material_pipelines: material_pipelines,
-			bound_pipeline: std::rc::Weak::new()
+			bound_pipeline: std::rc::Weak::new(),
+			depth_format: depth_format,
+			samples: samples,
+			vfs: vfs,
+			textures: AssetCache::new("texture"),
+			fonts: AssetCache::new("font"),
+			asset_workers: WorkerPool::new(MAX_ASSET_WORKERS),
+			decoded_sender: decoded_sender,
+			decoded_receiver: decoded_receiver,
+			loading_progress: LoadingProgress::default(),
+			file_watcher: file_watcher,
+			placeholder_texture: Arc::new(placeholder_texture)
 		})
 	}
 
+	/// Wait for the GPU to finish the frame that was last recorded with the current frame index, so that its
+	/// per-frame resources can be reused, then destroy the resources that were waiting on it. This must be called
+	/// once every frame before anything gets drawn.
+	pub fn begin_frame(&mut self) -> Result<(), EngineError>
+	{
+		match &mut self.target {
+			RenderTarget::Swapchain(swapchain) => swapchain.wait_for_frame(self.frame_index)?,
+			RenderTarget::Offscreen(_) => ()	// offscreen rendering already waits for each frame upon submission
+		}
+		self.deferred_destruction[self.frame_index].clear();
+		Ok(())
+	}
+
+	/// The index of the frame currently being recorded, from 0 to `FRAMES_IN_FLIGHT - 1`, for choosing which copy of a
+	/// per-frame resource to use.
+	pub fn frame_index(&self) -> usize
+	{
+		self.frame_index
+	}
+
+	/// Keep the resource alive until the GPU has finished the frame currently being recorded, such as a buffer or
+	/// descriptor set that has been replaced but may already be used by commands in this frame.
+	pub fn destroy_after_frame<T: 'static>(&mut self, resource: T)
+	{
+		self.deferred_destruction[self.frame_index].push(Box::new(resource));
+	}
+
 	/// THIS MAY UNBIND THE CURRENTLY BOUND PIPELINE!!! (if there is a viewport resize)
 	/// Do not bind pipelines before this function for use after it!
-	pub fn begin_main_render_pass(&mut self) -> Result<(), Box<dyn std::error::Error>>
+	pub fn begin_main_render_pass(&mut self) -> Result<(), EngineError>
 	{
-		let (next_img_fb, resize_viewports) = self.swapchain.get_next_image()?;
+		let (next_img_fb, resize_viewports) = self.target.get_next_image()?;
 
 		if resize_viewports {
-			let new_dimensions = self.swapchain.dimensions();
+			let new_dimensions = self.target.dimensions();
 			log::debug!("Recreating pipelines with new viewport...");
 			
 			// destroy the existing "weak" to the bound pipeline since it will become invalid with the viewport resize,
@@ -112,33 +272,35 @@ impl RenderContext
 			// accessing it at the same time anyways.
 			self.bound_pipeline = std::rc::Weak::new();	
 
-			for (_, pl) in &mut self.material_pipelines {
+			for (name, pl) in &mut self.material_pipelines {
 				// TODO: there might be a better time than when the render pass begins to do the pipeline viewports resizing...
-				Rc::get_mut(pl).ok_or("a pipeline whose viewport is being resized is in use!")?
+				Rc::get_mut(pl).ok_or_else(|| EngineError::pipeline(name, "pipeline is in use while its viewport is resized"))?
 					.resize_viewport(new_dimensions[0], new_dimensions[1])?;
 			}
 		}
 		
 		let mut rp_begin_info = vulkano::command_buffer::RenderPassBeginInfo::framebuffer(next_img_fb);
-		rp_begin_info.clear_values = vec![Some([0.1, 0.1, 0.1, 1.0].into())];
+		rp_begin_info.clear_values = render_pass::clear_values(self.depth_format, self.samples);
 
-		self.cur_cb.begin_render_pass(rp_begin_info, SubpassContents::Inline)?;
+		self.cur_cb.begin_render_pass(rp_begin_info, SubpassContents::Inline)
+			.map_err(|e| EngineError::vulkan("beginning render pass", e))?;
 		Ok(())
 	}
 
-	pub fn end_render_pass(&mut self) -> Result<(), Box<dyn std::error::Error>>
+	pub fn end_render_pass(&mut self) -> Result<(), EngineError>
 	{
-		self.cur_cb.end_render_pass()?;
+		self.cur_cb.end_render_pass().map_err(|e| EngineError::vulkan("ending render pass", e))?;
 		Ok(())
 	}
 
-	pub fn submit_commands(&mut self) -> Result<(), Box<dyn std::error::Error>>
+	pub fn submit_commands(&mut self) -> Result<(), EngineError>
 	{
 		let q_fam = self.vk_dev.active_queue_families().next()
-			.ok_or("There are no active queue families in the logical device!")?;
+			.ok_or(EngineError::vulkan("submitting commands", "there are no active queue families in the logical device"))?;
 
 		// Leave a new command buffer builder in place of the one we're about to take to build and submit.
-		let mut swap_cb = AutoCommandBufferBuilder::primary(self.vk_dev.clone(), q_fam, CommandBufferUsage::OneTimeSubmit)?;
+		let mut swap_cb = AutoCommandBufferBuilder::primary(self.vk_dev.clone(), q_fam, CommandBufferUsage::OneTimeSubmit)
+			.map_err(|e| EngineError::vulkan("creating command buffer", e))?;
 		std::mem::swap(&mut swap_cb, &mut self.cur_cb);
 
 		let submit_futures = self.upload_futures.take();	// consume the futures to join them upon submission
@@ -146,20 +308,321 @@ impl RenderContext
 			log::debug!("Joining a future of {} futures.", self.upload_futures_count);
 		}
 		self.upload_futures_count = 0;
-		self.swapchain.submit_commands(swap_cb.build()?, self.dev_queue.clone(), submit_futures)
+		match &mut self.target {
+			RenderTarget::Swapchain(swapchain) => {
+				let cb = swap_cb.build().map_err(|e| EngineError::vulkan("building command buffer", e))?;
+				swapchain.submit_commands(cb, self.dev_queue.clone(), submit_futures, self.frame_index)?
+			}
+			RenderTarget::Offscreen(offscreen) => offscreen.submit_commands(swap_cb, self.dev_queue.clone(), submit_futures)?
+		}
+		self.frame_index = (self.frame_index + 1) % FRAMES_IN_FLIGHT;
+		Ok(())
+	}
+
+	/// Apply changed video settings.
+	pub fn apply_video_settings(&mut self, video: &VideoSettings) -> Result<(), EngineError>
+	{
+		log::info!("Applying video settings: {:?}", video);
+		match &mut self.target {
+			RenderTarget::Swapchain(swapchain) => {
+				window::apply_video_settings(swapchain.window(), video);
+				swapchain.window_resized();
+				swapchain.set_present_mode(video.present_mode, video.swapchain_images)?;
+			}
+			RenderTarget::Offscreen(_) => ()
+		}
+		self.set_antialiasing(video.antialiasing)
+	}
+
+	/// Change the anti-aliasing mode, recreating the render pass and pipelines to match. If the device doesn't support
+	/// the mode's sample count, fewer samples are used instead.
+	pub fn set_antialiasing(&mut self, antialiasing: AntiAliasing) -> Result<(), EngineError>
+	{
+		let samples = select_sample_count(self.vk_dev.physical_device(), antialiasing);
+		if samples == self.samples {
+			return Ok(())
+		}
+		log::info!("Changing to {}x multisampling...", samples);
+
+		match &mut self.target {
+			RenderTarget::Swapchain(swapchain) => swapchain.set_samples(samples)?,
+			RenderTarget::Offscreen(offscreen) => offscreen.set_samples(samples)?
+		}
+		self.samples = samples;
+
+		// descriptor sets created for the old pipelines stay valid, since only the render pass changes
+		let render_pass = self.target.render_pass();
+		let dim = self.target.dimensions();
+		self.bound_pipeline = std::rc::Weak::new();
+		for (name, pl) in &mut self.material_pipelines {
+			Rc::get_mut(pl).ok_or_else(|| EngineError::pipeline(name, "pipeline is in use while its render pass is changed"))?
+				.set_render_pass(render_pass.clone(), dim[0], dim[1])?;
+		}
+		Ok(())
+	}
+
+	/// Get the pixels of the last frame rendered offscreen, tightly packed as 8-bit RGBA.
+	/// This will fail if this render context renders to a window.
+	pub fn read_offscreen_pixels(&self) -> Result<Vec<u8>, EngineError>
+	{
+		match &self.target {
+			RenderTarget::Offscreen(offscreen) => offscreen.read_pixels(),
+			RenderTarget::Swapchain(_) => {
+				Err(EngineError::vulkan("reading back pixels", "cannot read back pixels from a window render target"))
+			}
+		}
+	}
+
+	/// Write the last frame rendered offscreen into a PNG file.
+	/// This will fail if this render context renders to a window.
+	pub fn save_offscreen_png(&self, path: &std::path::Path) -> Result<(), EngineError>
+	{
+		match &self.target {
+			RenderTarget::Offscreen(offscreen) => offscreen.save_png(path),
+			RenderTarget::Swapchain(_) => {
+				Err(EngineError::vulkan("reading back pixels", "cannot save a PNG from a window render target"))
+			}
+		}
+	}
+
+	/// Load a texture from the file at the logical path in the virtual filesystem, or get the one that was already
+	/// loaded from the same path.
+	pub fn new_texture(&mut self, path: &std::path::Path) -> Result<Handle<texture::Texture>, EngineError>
+	{
+		let logical_path = crate::vfs::normalize(path)?;
+		let mut upload_future = None;
+		let tex = self.textures.get_or_load(&logical_path, || {
+			let (tex, f) = texture::Texture::new(self.dev_queue.clone(), &self.vfs, path)?;
+			upload_future = Some(f);
+			Ok(tex)
+		})?;
+
+		match upload_future {
+			Some(f) => {
+				self.add_upload_future(f);
+				self.watch_file(&logical_path);
+			}
+			None => ()
+		}
+
+		Ok(tex)
+	}
+
+	/// Load a texture from the file at the logical path in the virtual filesystem in the background, or get the one
+	/// that was already loaded from the same path. The returned handle will be pending until the texture has been
+	/// decoded on a worker thread and uploaded in `update_async_loads`. This only fails if the path is invalid; errors
+	/// while loading are logged, and set the handle's state to failed.
+	pub fn load_texture_async(&mut self, path: &std::path::Path) -> Result<Handle<texture::Texture>, EngineError>
+	{
+		let (handle, needs_loading) = self.textures.get_or_insert_pending(&crate::vfs::normalize(path)?);
+		if needs_loading {
+			self.loading_progress.add_request();
+			self.spawn_texture_decode(handle.clone(), false);
+		}
+		Ok(handle)
 	}
 
-	pub fn new_texture(&mut self, path: &std::path::Path) -> Result<texture::Texture, Box<dyn std::error::Error>>
+	// Decode the texture for the handle on a worker thread, then send it back to be uploaded in `update_async_loads`.
+	fn spawn_texture_decode(&self, handle: Handle<texture::Texture>, reload: bool)
 	{
-		let (tex, upload_future) = texture::Texture::new(self.dev_queue.clone(), path)?;
+		let vfs = self.vfs.clone();
+		let sender = self.decoded_sender.clone();
+		self.asset_workers.spawn(move || {
+			let path = std::path::PathBuf::from(handle.path().unwrap_or_default());
+			let result = texture::DecodedImage::load(&vfs, &path);
+
+			// the receiver only gets dropped along with the render context, when the result isn't needed anymore
+			sender.send(DecodedTexture{ handle: handle, result: result, reload: reload }).ok();
+		});
+	}
+
+	/// Upload the textures that have finished decoding in the background, updating the state of their handles. This
+	/// gets called once every frame.
+	pub fn update_async_loads(&mut self)
+	{
+		while let Ok(decoded) = self.decoded_receiver.try_recv() {
+			self.finish_async_load(decoded);
+		}
+	}
+
+	/// Block until all of the textures requested with `load_texture_async` have been loaded, such as when rendering
+	/// headless, where the output shouldn't depend on how fast textures get loaded.
+	pub fn wait_for_async_loads(&mut self)
+	{
+		self.update_async_loads();
+		while !self.loading_progress.is_done() {
+			match self.decoded_receiver.recv() {
+				Ok(decoded) => self.finish_async_load(decoded),
+				Err(_) => break
+			}
+		}
+	}
+
+	fn finish_async_load(&mut self, decoded: DecodedTexture)
+	{
+		let DecodedTexture{ handle, result, reload } = decoded;
+		let upload_result = result.and_then(|image| {
+			let (tex, upload_future) = texture::Texture::from_decoded(self.dev_queue.clone(), image)?;
+			self.add_upload_future(upload_future);
+			Ok(tex)
+		});
+		let path = handle.path().unwrap_or_default().to_string();
+		if !reload {
+			// also watched if it failed to load, so that it can be fixed without restarting
+			self.watch_file(&path);
+		}
+		match (upload_result, reload) {
+			(Ok(tex), false) => {
+				log::debug!("Loaded texture '{}' in the background", path);
+				handle.set_state(AssetState::Loaded(Arc::new(tex)));
+				self.loading_progress.add_finished(false);
+			}
+			(Err(e), false) => {
+				log::error!("Failed to load texture '{}': {}", path, e);
+				handle.set_state(AssetState::Failed(e.to_string()));
+				self.loading_progress.add_finished(true);
+			}
+			(Ok(tex), true) => {
+				// descriptor sets still referencing the old texture keep it alive until they get recreated
+				log::info!("Reloaded texture '{}'", path);
+				handle.set_state(AssetState::Loaded(Arc::new(tex)));
+			}
+			(Err(e), true) => log::error!("Failed to reload texture '{}', keeping the old one: {}", path, e)
+		}
+	}
+
+	/// Watch the file at the logical path for changes, to be reported by `poll_file_changes`. Does nothing in release
+	/// builds.
+	pub fn watch_file(&mut self, logical_path: &str)
+	{
+		match &mut self.file_watcher {
+			Some(w) => w.watch(logical_path),
+			None => ()
+		}
+	}
+
+	/// Reload the pipelines and textures whose files have changed since the last call. Textures are replaced behind
+	/// their existing handles once they've been decoded in the background. Changed files that aren't pipelines or
+	/// textures, such as maps, are returned for the caller to reload. Does nothing in release builds. This gets called
+	/// once every frame.
+	pub fn poll_file_changes(&mut self) -> Vec<String>
+	{
+		let changed = match &mut self.file_watcher {
+			Some(w) => w.poll(),
+			None => return Vec::new()
+		};
+
+		let mut others = Vec::new();
+		for logical_path in changed {
+			let changed_pipelines: Vec<String> = self.material_pipelines.iter()
+				.filter(|(_, pl)| pl.source_files().contains(&logical_path))
+				.map(|(name, _)| name.clone())
+				.collect();
+			if !changed_pipelines.is_empty() {
+				log::info!("'{}' changed, reloading pipelines...", logical_path);
+				for name in changed_pipelines {
+					self.reload_pipeline(&name);
+				}
+				continue
+			}
+
+			match self.textures.get(&logical_path) {
+				// keep showing the old texture until the new one is ready, instead of going back to the placeholder
+				Some(handle) if handle.is_loaded() => {
+					log::info!("'{}' changed, reloading texture...", logical_path);
+					self.spawn_texture_decode(handle, true);
+				}
+				Some(handle) if handle.is_pending() => (),
+				Some(handle) => {
+					// try again from scratch if it failed to load before
+					log::info!("'{}' changed, loading texture...", logical_path);
+					handle.set_state(AssetState::Pending);
+					self.loading_progress.add_request();
+					self.spawn_texture_decode(handle, false);
+				}
+				None => others.push(logical_path)
+			}
+		}
+		others
+	}
+
+	// Replace the pipeline with one created again from its files, or keep the old one if that fails.
+	fn reload_pipeline(&mut self, name: &str)
+	{
+		let dim = self.target.dimensions();
+		let reload_result = match self.material_pipelines.get(name) {
+			Some(pl) => pl.reload(dim[0], dim[1], &self.vfs),
+			None => return
+		};
+		match reload_result {
+			Ok(new_pipeline) => {
+				// the yaml file may now refer to different shaders
+				for source_file in new_pipeline.source_files() {
+					self.watch_file(source_file);
+				}
+
+				// descriptor sets created for the old pipeline stay valid, since the layouts are compatible
+				self.bound_pipeline = std::rc::Weak::new();
+				match self.material_pipelines.insert(name.to_string(), Rc::new(new_pipeline)) {
+					Some(old_pipeline) => self.destroy_after_frame(old_pipeline),
+					None => ()
+				}
+				log::info!("Reloaded pipeline '{}'", name);
+			}
+			Err(e) => log::error!("Failed to reload pipeline '{}', keeping the old one: {}", name, e)
+		}
+	}
+
+	/// Progress of the textures requested with `load_texture_async`, for loading screens.
+	pub fn loading_progress(&self) -> LoadingProgress
+	{
+		self.loading_progress
+	}
+
+	/// The texture to use in place of one that's still loading or failed to load.
+	pub fn placeholder_texture(&self) -> Arc<texture::Texture>
+	{
+		self.placeholder_texture.clone()
+	}
 
+	/// Get the texture if it has been loaded, or the placeholder texture otherwise.
+	pub fn texture_or_placeholder(&self, handle: &Handle<texture::Texture>) -> Arc<texture::Texture>
+	{
+		handle.get().unwrap_or_else(|| self.placeholder_texture())
+	}
+
+	// Join the future with the other uploads, so that they get waited on when the commands are submitted.
+	fn add_upload_future(&mut self, upload_future: impl GpuFuture + 'static)
+	{
 		self.upload_futures = Some(match self.upload_futures.take() {
 			Some(f) => upload_future.join(f).boxed(),
 			None => upload_future.boxed()
 		});
 		self.upload_futures_count += 1;
+	}
 
-		Ok(tex)
+	/// Load a font from the file at the logical path in the virtual filesystem, or get the one that was already loaded
+	/// from the same path.
+	pub fn load_font(&self, path: &std::path::Path) -> Result<Handle<font::Font>, EngineError>
+	{
+		self.fonts.get_or_load(&crate::vfs::normalize(path)?, || font::Font::new(&self.vfs, path))
+	}
+
+	/// Remove textures and fonts that aren't used anymore from the cache.
+	pub fn evict_unused_assets(&self)
+	{
+		let evicted = self.textures.evict_unused() + self.fonts.evict_unused();
+		if evicted > 0 {
+			log::info!("Evicted {} unused asset(s)", evicted);
+		}
+	}
+
+	/// List the loaded textures and fonts with their memory sizes. Use `asset::log_report` to write it to the log.
+	pub fn asset_report(&self) -> Vec<AssetInfo>
+	{
+		let mut report = self.textures.report();
+		report.extend(self.fonts.report());
+		report
 	}
 
 	pub fn new_texture_from_iter<Px, I>(&mut self,	
@@ -168,7 +631,7 @@ impl RenderContext
 		dimensions: ImageDimensions,
 		mip: MipmapsCount
 	) 
-	-> Result<texture::Texture, Box<dyn std::error::Error>>
+	-> Result<texture::Texture, EngineError>
 	where
 		[Px]: vulkano::buffer::BufferContents,
 		I: IntoIterator<Item = Px>,
@@ -182,69 +645,69 @@ impl RenderContext
 			mip
 		)?;
 
-		self.upload_futures = Some(match self.upload_futures.take() {
-			Some(f) => upload_future.join(f).boxed(),
-			None => upload_future.boxed()
-		});
-		self.upload_futures_count += 1;
+		self.add_upload_future(upload_future);
 
 		Ok(tex)
 	}
 
 	/// Create an immutable buffer, initialized with `data` for `usage`.
 	pub fn new_buffer<D,T>(&mut self, data: D, usage: BufferUsage) 
-		-> Result<Arc<ImmutableBuffer<[T]>>, Box<dyn std::error::Error>>
+		-> Result<Arc<ImmutableBuffer<[T]>>, EngineError>
 		where
 			D: IntoIterator<Item = T>,
 			D::IntoIter: ExactSizeIterator,
 			[T]: vulkano::buffer::BufferContents, 
 	{
-		let (buf, upload_future) = ImmutableBuffer::from_iter(data, usage, self.dev_queue.clone())?;
+		let (buf, upload_future) = ImmutableBuffer::from_iter(data, usage, self.dev_queue.clone())
+			.map_err(|e| EngineError::vulkan("creating immutable buffer", e))?;
 		
-		self.upload_futures = Some(match self.upload_futures.take() {
-			Some(f) => upload_future.join(f).boxed(),
-			None => upload_future.boxed()
-		});
-		self.upload_futures_count += 1;
+		self.add_upload_future(upload_future);
 
 		Ok(buf)
 	}
 
 	/// Create a new CPU-accessible buffer, initialized with `data` for `usage`.
 	pub fn new_cpu_buffer<I, T>(&mut self, data: I, usage: BufferUsage)
-		-> Result<Arc<CpuAccessibleBuffer<[T]>>, DeviceMemoryAllocationError>
+		-> Result<Arc<CpuAccessibleBuffer<[T]>>, EngineError>
 		where
 			I: IntoIterator<Item = T>,
 			I::IntoIter: ExactSizeIterator,
 			[T]: vulkano::buffer::BufferContents
 	{
 		CpuAccessibleBuffer::from_iter(self.vk_dev.clone(), usage, false, data)
+			.map_err(|e| EngineError::vulkan("creating CPU-accessible buffer", e))
 	}
 
 	pub fn bind_pipeline(&mut self, pipeline_name: &str)
-		-> Result<(), PipelineNotLoaded>
+		-> Result<(), EngineError>
 	{
-		let pipeline_to_bind = self.material_pipelines.get(pipeline_name).ok_or(PipelineNotLoaded)?;
+		let pipeline_to_bind = self.get_pipeline(pipeline_name)?.clone();
 		pipeline_to_bind.bind(&mut self.cur_cb);
-		self.bound_pipeline = Rc::downgrade(pipeline_to_bind);
+		self.bound_pipeline = Rc::downgrade(&pipeline_to_bind);
 		Ok(())
 	}
 
 	pub fn new_descriptor_set(&self, pipeline_name: &str, set: usize, writes: impl IntoIterator<Item = WriteDescriptorSet>)
-		-> Result<Arc<PersistentDescriptorSet>, Box<dyn std::error::Error>>
+		-> Result<Arc<PersistentDescriptorSet>, EngineError>
 	{
-		self.material_pipelines.get(pipeline_name).ok_or(PipelineNotLoaded)?.new_descriptor_set(set, writes)
+		self.get_pipeline(pipeline_name)?.new_descriptor_set(set, writes)
+	}
+
+	fn get_pipeline(&self, pipeline_name: &str) -> Result<&Rc<pipeline::Pipeline>, EngineError>
+	{
+		self.material_pipelines.get(pipeline_name)
+			.ok_or_else(|| EngineError::PipelineNotLoaded{ name: pipeline_name.to_string() })
 	}
 
 	/// Bind the given descriptor sets to the currently bound pipeline.
 	/// This will fail if there is no pipeline currently bound.
 	pub fn bind_descriptor_set<S>(&mut self, first_set: u32, descriptor_sets: S) 
-		-> Result<(), PipelineNotLoaded>
+		-> Result<(), EngineError>
 		where S: DescriptorSetsCollection
 	{
 		self.cur_cb.bind_descriptor_sets(
 			PipelineBindPoint::Graphics,
-			self.bound_pipeline.upgrade().ok_or(PipelineNotLoaded)?.layout(), first_set, descriptor_sets
+			self.bound_pipeline.upgrade().ok_or(EngineError::NoPipelineBound)?.layout(), first_set, descriptor_sets
 		);
 		Ok(())
 	}
@@ -264,38 +727,112 @@ impl RenderContext
 	}
 
 	pub fn draw(&mut self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32)
-		-> Result<(), DrawError>
+		-> Result<(), EngineError>
 	{
-		self.cur_cb.draw(vertex_count, instance_count, first_vertex, first_instance)?;
+		self.cur_cb.draw(vertex_count, instance_count, first_vertex, first_instance)
+			.map_err(|e| EngineError::vulkan("recording draw command", e))?;
 		Ok(())
 	}
 
-	/*
+	/// Recreate the swapchain to match the new size of the window. This gets called when the window gets resized.
+	pub fn window_resized(&mut self)
+	{
+		match &mut self.target {
+			RenderTarget::Swapchain(swapchain) => swapchain.window_resized(),
+			RenderTarget::Offscreen(_) => ()
+		}
+	}
+
+	/// Check if there's nowhere to render to, such as when the window is minimized. Nothing should be drawn while this
+	/// is true, since the swapchain can't be recreated with a size of zero.
+	pub fn is_minimized(&self) -> bool
+	{
+		match &self.target {
+			RenderTarget::Swapchain(swapchain) => window::is_minimized(swapchain.window()),
+			RenderTarget::Offscreen(_) => false
+		}
+	}
+
+	/// Get the monitors that the window can be placed on, for choosing one in a settings menu. This is empty when
+	/// rendering offscreen.
+	pub fn monitors(&self) -> Vec<window::MonitorInfo>
+	{
+		match &self.target {
+			RenderTarget::Swapchain(swapchain) => window::monitors(swapchain.window()),
+			RenderTarget::Offscreen(_) => Vec::new()
+		}
+	}
+
 	pub fn swapchain_dimensions(&self) -> [u32; 2]
 	{
-		self.swapchain.dimensions()
+		self.target.dimensions()
+	}
+
+	/// The virtual filesystem that assets are loaded through.
+	pub fn vfs(&self) -> &Vfs
+	{
+		&self.vfs
 	}
 
-	pub fn wait_for_fence(&self) -> Result<(), FlushError>
+	/// Describe the physical device being rendered with, including its driver version, as listed in the log upon
+	/// startup.
+	pub fn physical_device_description(&self) -> String
 	{
-		self.swapchain.wait_for_fence()
-	}*/
+		describe_physical_device(self.vk_dev.physical_device())
+	}
 }
 
-#[derive(Debug)]
-pub struct PipelineNotLoaded;
-impl std::error::Error for PipelineNotLoaded {}
-impl std::fmt::Display for PipelineNotLoaded {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "the specified pipeline is not loaded")
-    }
+/// Maximum number of threads used to decode textures loaded in the background.
+const MAX_ASSET_WORKERS: usize = 4;
+
+// A texture decoded on a worker thread, to be uploaded on the main thread.
+struct DecodedTexture
+{
+	handle: Handle<texture::Texture>,
+	result: Result<texture::DecodedImage, EngineError>,
+
+	// whether this replaces a texture that was already loaded, rather than being loaded for the first time
+	reload: bool
 }
 
-fn create_vulkan_instance(game_name: &str) -> Result<Arc<vulkano::instance::Instance>, Box<dyn std::error::Error>>
+/// The image that the main render pass renders to; either a window swapchain, or an image in memory.
+enum RenderTarget
+{
+	Swapchain(swapchain::Swapchain),
+	Offscreen(offscreen::Offscreen)
+}
+impl RenderTarget
+{
+	fn get_next_image(&mut self) -> Result<(Arc<Framebuffer>, bool), EngineError>
+	{
+		match self {
+			RenderTarget::Swapchain(swapchain) => swapchain.get_next_image(),
+			RenderTarget::Offscreen(offscreen) => Ok((offscreen.get_next_image(), false))
+		}
+	}
+
+	fn render_pass(&self) -> Arc<RenderPass>
+	{
+		match self {
+			RenderTarget::Swapchain(swapchain) => swapchain.render_pass(),
+			RenderTarget::Offscreen(offscreen) => offscreen.render_pass()
+		}
+	}
+
+	fn dimensions(&self) -> [u32; 2]
+	{
+		match self {
+			RenderTarget::Swapchain(swapchain) => swapchain.dimensions(),
+			RenderTarget::Offscreen(offscreen) => offscreen.dimensions()
+		}
+	}
+}
+
+fn create_vulkan_instance(game_name: &str, vk_ext: vulkano::instance::InstanceExtensions)
+	-> Result<Arc<vulkano::instance::Instance>, EngineError>
 {
 	// we'll need to enable the `enumerate_portability` extension if we want to use devices with non-conformant Vulkan
 	// implementations like MoltenVK. for now, we can go without it.
-	let vk_ext = vulkano_win::required_extensions();
 	
 	// only use the validation layer in debug builds
 	#[cfg(debug_assertions)]
@@ -311,7 +848,7 @@ fn create_vulkan_instance(game_name: &str) -> Result<Arc<vulkano::instance::Inst
 	inst_create_info.enabled_layers = vk_layers;
 	inst_create_info.max_api_version = Some(vulkano::Version::V1_2);
 	
-	Ok(vulkano::instance::Instance::new(inst_create_info)?)
+	vulkano::instance::Instance::new(inst_create_info).map_err(|e| EngineError::vulkan("creating instance", e))
 }
 
 fn decode_driver_version(version: u32, vendor_id: u32) -> (u32, u32, u32, u32)
@@ -337,28 +874,33 @@ fn decode_driver_version(version: u32, vendor_id: u32) -> (u32, u32, u32, u32)
 	(version >> 12) & 0x3ff,
 	version & 0xfff, 0)
 }
+/// Describe the physical device with its index, name, type, driver, and Vulkan version, in a single line.
+fn describe_physical_device(pd: PhysicalDevice) -> String
+{
+	let pd_type_str = match pd.properties().device_type {
+		PhysicalDeviceType::IntegratedGpu => "Integrated GPU",
+		PhysicalDeviceType::DiscreteGpu => "Discrete GPU",
+		PhysicalDeviceType::VirtualGpu => "Virtual GPU",
+		PhysicalDeviceType::Cpu => "CPU",
+		PhysicalDeviceType::Other => "Other",
+	};
+	let driver_ver = decode_driver_version(pd.properties().driver_version, pd.properties().vendor_id);
+	let api_ver = pd.properties().api_version;
+	
+	format!("{}: {} ({}), driver '{}' version {}.{}.{}.{} (Vulkan {}.{}.{})", 
+		pd.index(), 
+		pd.properties().device_name, 
+		pd_type_str,
+		pd.properties().driver_name.clone().unwrap_or("unknown driver".to_string()), 
+		driver_ver.0, driver_ver.1, driver_ver.2, driver_ver.3,
+		api_ver.major, api_ver.minor, api_ver.patch
+	)
+}
 fn print_physical_devices<'a>(vkinst: &'a Arc<vulkano::instance::Instance>)
 {
 	log::info!("Available Vulkan physical devices:");
 	for pd in PhysicalDevice::enumerate(&vkinst) {
-		let pd_type_str = match pd.properties().device_type {
-			PhysicalDeviceType::IntegratedGpu => "Integrated GPU",
-			PhysicalDeviceType::DiscreteGpu => "Discrete GPU",
-			PhysicalDeviceType::VirtualGpu => "Virtual GPU",
-			PhysicalDeviceType::Cpu => "CPU",
-			PhysicalDeviceType::Other => "Other",
-		};
-		let driver_ver = decode_driver_version(pd.properties().driver_version, pd.properties().vendor_id);
-		let api_ver = pd.properties().api_version;
-		
-		log::info!("{}: {} ({}), driver '{}' version {}.{}.{}.{} (Vulkan {}.{}.{})", 
-			pd.index(), 
-			pd.properties().device_name, 
-			pd_type_str,
-			pd.properties().driver_name.clone().unwrap_or("unknown driver".to_string()), 
-			driver_ver.0, driver_ver.1, driver_ver.2, driver_ver.3,
-			api_ver.major, api_ver.minor, api_ver.patch
-		);
+		log::info!("{}", describe_physical_device(pd));
 	}
 }
 fn print_queue_families<'a>(queue_families: impl ExactSizeIterator<Item = QueueFamily<'a>>)
@@ -381,51 +923,223 @@ fn print_queue_families<'a>(queue_families: impl ExactSizeIterator<Item = QueueF
 	}
 }
 
-/// Get the most appropriate GPU, along with a graphics queue family.
-fn get_physical_device<'a>(vkinst: &'a Arc<vulkano::instance::Instance>) 
-	-> Result<(PhysicalDevice<'a>, QueueFamily<'a>), Box<dyn std::error::Error>>
+/// How the physical device to render with gets chosen.
+#[derive(Clone, Debug)]
+pub enum DeviceSelection
+{
+	/// Pick the suitable device with the highest score, preferring discrete GPUs over integrated GPUs, virtual GPUs,
+	/// and CPU (software) implementations, in that order.
+	Auto,
+
+	/// Use the device with the given index, as listed in the log upon startup.
+	Index(usize),
+
+	/// Use the first suitable device whose name contains the given string, ignoring case.
+	Name(String)
+}
+impl DeviceSelection
+{
+	/// Parse a device selection string; a number selects a device by index, "auto" or an empty string selects
+	/// automatically, and anything else selects by name.
+	pub fn parse(selection_str: &str) -> DeviceSelection
+	{
+		let trimmed = selection_str.trim();
+		if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("auto") {
+			DeviceSelection::Auto
+		} else {
+			match trimmed.parse::<usize>() {
+				Ok(i) => DeviceSelection::Index(i),
+				Err(_) => DeviceSelection::Name(trimmed.to_string())
+			}
+		}
+	}
+
+	/// Use the selection in the `MITHRIL_DEVICE` environment variable if it's set, or `self` otherwise.
+	pub fn or_env_override(self) -> DeviceSelection
+	{
+		match std::env::var("MITHRIL_DEVICE") {
+			Ok(env_str) => {
+				log::info!("Using physical device selection '{}' from MITHRIL_DEVICE environment variable", env_str);
+				DeviceSelection::parse(&env_str)
+			}
+			Err(_) => self
+		}
+	}
+}
+impl Default for DeviceSelection
+{
+	fn default() -> Self
+	{
+		DeviceSelection::Auto
+	}
+}
+
+/// Score the physical device for how appropriate it is for rendering. Returns an error describing why the device is
+/// unsuitable if it doesn't support what the engine requires.
+fn score_physical_device(pd: PhysicalDevice, dev_extensions: &vulkano::device::DeviceExtensions) -> Result<u32, String>
+{
+	let required_features = required_device_features();
+	if !pd.supported_features().is_superset_of(&required_features) {
+		return Err(format!(
+			"missing required features {:?}", required_features.difference(pd.supported_features())
+		))
+	}
+	if !pd.supported_extensions().is_superset_of(dev_extensions) {
+		return Err(format!(
+			"missing required extensions {:?}", dev_extensions.difference(pd.supported_extensions())
+		))
+	}
+	if pd.queue_families().find(|q| q.supports_graphics()).is_none() {
+		return Err("no queue family supports graphics".to_string())
+	}
+
+	let type_score = match pd.properties().device_type {
+		PhysicalDeviceType::DiscreteGpu => 1000,
+		PhysicalDeviceType::IntegratedGpu => 500,
+		PhysicalDeviceType::VirtualGpu => 250,
+		PhysicalDeviceType::Cpu => 100,
+		PhysicalDeviceType::Other => 50,
+	};
+
+	// slightly prefer devices with newer Vulkan versions among devices of the same type
+	let api_score = match pd.api_version() >= vulkano::Version::V1_2 {
+		true => 10,
+		false => 0
+	};
+
+	Ok(type_score + api_score)
+}
+
+const SELECTING_DEVICE: &str = "selecting physical device";
+
+/// Get the most appropriate physical device according to `selection`, along with a graphics queue family.
+fn get_physical_device<'a>(
+	vkinst: &'a Arc<vulkano::instance::Instance>, 
+	selection: &DeviceSelection,
+	dev_extensions: &vulkano::device::DeviceExtensions
+) 
+	-> Result<(PhysicalDevice<'a>, QueueFamily<'a>), EngineError>
 {	
 	print_physical_devices(vkinst);
 
-	// Look for a discrete GPU.
-	let dgpu = PhysicalDevice::enumerate(&vkinst)
-		.find(|pd| pd.properties().device_type == PhysicalDeviceType::DiscreteGpu);
-	let physical_device = match dgpu {
-		Some(g) => g,
+	let mut scored = Vec::new();
+	for pd in PhysicalDevice::enumerate(&vkinst) {
+		match score_physical_device(pd, dev_extensions) {
+			Ok(score) => {
+				log::info!("{}: score {}", pd.index(), score);
+				scored.push((pd, score));
+			}
+			Err(e) => log::info!("{}: unsuitable, {}", pd.index(), e)
+		}
+	}
 
-		// If there is no discrete GPU, try to look for an integrated GPU instead.
-		None => PhysicalDevice::enumerate(&vkinst)
-			.find(|pd| pd.properties().device_type == PhysicalDeviceType::IntegratedGpu)
-			.ok_or("No GPUs were found!")?
+	let physical_device = match selection {
+		DeviceSelection::Auto => {
+			scored.iter()
+				.max_by_key(|(pd, score)| (*score, std::cmp::Reverse(pd.index())))
+				.map(|(pd, _)| *pd)
+				.ok_or(EngineError::vulkan(SELECTING_DEVICE, "no suitable physical devices were found"))?
+		}
+		DeviceSelection::Index(i) => {
+			let pd = PhysicalDevice::from_index(&vkinst, *i)
+				.ok_or_else(|| EngineError::vulkan(SELECTING_DEVICE, format!("physical device index {} is out of range", i)))?;
+			score_physical_device(pd, dev_extensions).map_err(|e| {
+				EngineError::vulkan(SELECTING_DEVICE, format!("selected physical device {} is unsuitable: {}", i, e))
+			})?;
+			pd
+		}
+		DeviceSelection::Name(name) => {
+			// skip unsuitable devices, so that a usable device is still found if several have matching names
+			let name_lowercase = name.to_lowercase();
+			PhysicalDevice::enumerate(&vkinst)
+				.filter(|pd| pd.properties().device_name.to_lowercase().contains(&name_lowercase))
+				.find(|pd| match scored.iter().any(|(suitable, _)| suitable.index() == pd.index()) {
+					true => true,
+					false => {
+						log::warn!(
+							"Skipping physical device {} ({}), which matches '{}' but is unsuitable",
+							pd.index(), pd.properties().device_name, name
+						);
+						false
+					}
+				})
+				.ok_or_else(|| EngineError::vulkan(
+					SELECTING_DEVICE, format!("no suitable physical device with a name containing '{}'", name)
+				))?
+		}
 	};
-	log::info!("Using physical device: {}", physical_device.properties().device_name);
+	log::info!(
+		"Using physical device {}: {} (selection: {:?})", 
+		physical_device.index(), physical_device.properties().device_name, selection
+	);
 
 	// get queue family that supports graphics
 	print_queue_families(physical_device.queue_families());
 	let q_fam = physical_device.queue_families().find(|q| q.supports_graphics())
-			.ok_or("No appropriate queue family found!")?;
+			.ok_or(EngineError::vulkan(SELECTING_DEVICE, "no appropriate queue family found"))?;
 
 	Ok((physical_device, q_fam))
 }
 
-fn create_vk_logical_device<'a, I>(physical_device: PhysicalDevice, queue_families: I) 
-	-> Result<(Arc<vulkano::device::Device>, impl ExactSizeIterator<Item = Arc<Queue>>), DeviceCreationError>
-	where I: IntoIterator<Item = (QueueFamily<'a>, f32)>
+/// Pick the depth buffer format to use, preferring ones with a stencil aspect. At least one of these is guaranteed to be
+/// supported by the Vulkan specification.
+fn select_depth_format(pd: PhysicalDevice) -> Result<Format, EngineError>
 {
-	// Select features and extensions.
-	// The ones chosen here are practically universally supported by any device with Vulkan support.
-	let dev_features = vulkano::device::Features{
+	let candidates = [ Format::D24_UNORM_S8_UINT, Format::D32_SFLOAT_S8_UINT, Format::D32_SFLOAT, Format::D16_UNORM ];
+	let depth_format = candidates.into_iter()
+		.find(|f| pd.format_properties(*f).optimal_tiling_features.depth_stencil_attachment)
+		.ok_or_else(|| EngineError::vulkan("selecting depth buffer format", "no supported depth buffer format"))?;
+	log::info!("Using depth buffer format {:?}", depth_format);
+	Ok(depth_format)
+}
+
+/// Get the highest number of samples per pixel for the anti-aliasing mode that the device supports for both color and
+/// depth attachments, falling back to fewer samples if the mode's sample count isn't supported.
+fn select_sample_count(pd: PhysicalDevice, antialiasing: AntiAliasing) -> u32
+{
+	let requested = antialiasing.sample_count();
+	let color_counts = pd.properties().framebuffer_color_sample_counts;
+	let depth_counts = pd.properties().framebuffer_depth_sample_counts;
+	let supports = |counts: &vulkano::image::SampleCounts, samples: u32| match samples {
+		1 => counts.sample1,
+		2 => counts.sample2,
+		4 => counts.sample4,
+		8 => counts.sample8,
+		_ => false
+	};
+	let samples = [ 8, 4, 2 ].into_iter()
+		.filter(|s| *s <= requested)
+		.find(|s| supports(&color_counts, *s) && supports(&depth_counts, *s))
+		.unwrap_or(1);
+	if samples < requested {
+		log::warn!("{}x multisampling isn't supported by the device, using {}x instead", requested, samples);
+	}
+	samples
+}
+
+/// Get the device features required by the engine.
+/// The ones chosen here are practically universally supported by any device with Vulkan support.
+fn required_device_features() -> vulkano::device::Features
+{
+	vulkano::device::Features{
 		image_cube_array: true,
 		independent_blend: true,
 		sampler_anisotropy: true,
 		texture_compression_bc: true,	// change this to ASTC or ETC2 if we want to support mobile platforms
 		geometry_shader: true,
 		..vulkano::device::Features::none()
-	};
-	let dev_extensions = vulkano::device::DeviceExtensions{
-		khr_swapchain: true,
-		..vulkano::device::DeviceExtensions::none()
-	};
+	}
+}
+
+fn create_vk_logical_device<'a, I>(
+	physical_device: PhysicalDevice, 
+	queue_families: I, 
+	dev_extensions: vulkano::device::DeviceExtensions
+) 
+	-> Result<(Arc<vulkano::device::Device>, impl ExactSizeIterator<Item = Arc<Queue>>), EngineError>
+	where I: IntoIterator<Item = (QueueFamily<'a>, f32)>
+{
+	let dev_features = required_device_features();
 
 	let mut queue_create_infos: Vec<vulkano::device::QueueCreateInfo<'a>> = Vec::new();
 	for qf in queue_families {
@@ -440,5 +1154,6 @@ fn create_vk_logical_device<'a, I>(physical_device: PhysicalDevice, queue_famili
 	};
 
 	vulkano::device::Device::new(physical_device, dev_create_info)
+		.map_err(|e| EngineError::vulkan("creating logical device", e))
 }
 
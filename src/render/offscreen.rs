@@ -0,0 +1,159 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::sync::Arc;
+use std::path::Path;
+use vulkano::device::Queue;
+use vulkano::command_buffer::{ AutoCommandBufferBuilder, PrimaryAutoCommandBuffer, CopyImageToBufferInfo };
+use vulkano::format::Format;
+use vulkano::image::{ AttachmentImage, ImageUsage };
+use vulkano::buffer::{ BufferUsage, cpu_access::CpuAccessibleBuffer };
+use vulkano::render_pass::{ RenderPass, Framebuffer };
+use vulkano::device::DeviceOwned;
+use vulkano::sync::GpuFuture;
+use crate::error::EngineError;
+use super::render_pass::{ self, TransientAttachments };
+
+/// RGBA rather than the swapchain's BGRA, so that the pixels can be read back and saved without swizzling.
+const COLOR_FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+/// Render target used in place of a swapchain when there is no window to render to, such as when running headless.
+/// The rendered image gets copied into CPU-accessible memory after every frame, so it can be read back or saved.
+pub struct Offscreen
+{
+	vk_dev: Arc<vulkano::device::Device>,
+	image: Arc<AttachmentImage>,
+	render_pass: Arc<RenderPass>,
+	framebuffer: Arc<Framebuffer>,
+	readback_buf: Arc<CpuAccessibleBuffer<[u8]>>,
+	dimensions: [u32; 2],
+	depth_format: Format
+}
+impl Offscreen
+{
+	pub fn new(vk_dev: Arc<vulkano::device::Device>, dimensions: [u32; 2], depth_format: Format, samples: u32)
+		-> Result<Offscreen, EngineError>
+	{
+		let image_usage = ImageUsage {
+			color_attachment: true,
+			transfer_src: true,
+			..ImageUsage::none()
+		};
+		let image = AttachmentImage::with_usage(vk_dev.clone(), dimensions, COLOR_FORMAT, image_usage)
+			.map_err(|e| EngineError::vulkan("creating offscreen image", e))?;
+
+		// the render pass has the same attachments as the swapchain's, but isn't compatible with it since the color format
+		// differs, so pipelines must be created with this render pass instead
+		let render_pass = render_pass::create_render_pass(vk_dev.clone(), COLOR_FORMAT, depth_format, samples)?;
+		let framebuffer = create_framebuffer(&image, dimensions, render_pass.clone(), depth_format, samples)?;
+
+		let readback_len = (dimensions[0] * dimensions[1] * 4) as usize;
+		let readback_buf = CpuAccessibleBuffer::from_iter(
+			vk_dev.clone(), BufferUsage::transfer_dst(), true, (0..readback_len).map(|_| 0u8)
+		).map_err(|e| EngineError::vulkan("creating readback buffer", e))?;
+
+		Ok(Offscreen{
+			vk_dev: vk_dev,
+			image: image,
+			render_pass: render_pass,
+			framebuffer: framebuffer,
+			readback_buf: readback_buf,
+			dimensions: dimensions,
+			depth_format: depth_format
+		})
+	}
+
+	/// Change the number of samples per pixel, creating a new render pass and framebuffer. Pipelines must be recreated
+	/// with the new render pass afterwards.
+	pub fn set_samples(&mut self, samples: u32) -> Result<(), EngineError>
+	{
+		let new_rp = render_pass::create_render_pass(self.vk_dev.clone(), COLOR_FORMAT, self.depth_format, samples)?;
+		self.framebuffer = create_framebuffer(&self.image, self.dimensions, new_rp.clone(), self.depth_format, samples)?;
+		self.render_pass = new_rp;
+		Ok(())
+	}
+
+	/// Get the framebuffer to render to. The image dimensions never change, so this doesn't return a bool unlike
+	/// `Swapchain::get_next_image`.
+	pub fn get_next_image(&self) -> Arc<Framebuffer>
+	{
+		self.framebuffer.clone()
+	}
+
+	/// Submit the commands, along with a copy of the rendered image into the readback buffer, then wait for them to
+	/// finish executing.
+	pub fn submit_commands(
+		&mut self,
+		mut cb: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+		queue: Arc<Queue>,
+		futures: Option<Box<dyn GpuFuture>>
+	)
+		-> Result<(), EngineError>
+	{
+		cb.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(self.image.clone(), self.readback_buf.clone()))
+			.map_err(|e| EngineError::vulkan("copying offscreen image", e))?;
+
+		let mut joined_future = vulkano::sync::now(self.vk_dev.clone()).boxed();
+
+		// join the joined futures from images and buffers being uploaded
+		match futures {
+			Some(f) => joined_future = joined_future.join(f).boxed(),
+			None => ()
+		}
+
+		let built_cb = cb.build().map_err(|e| EngineError::vulkan("building command buffer", e))?;
+		joined_future
+			.then_execute(queue, built_cb)
+			.map_err(|e| EngineError::vulkan("executing command buffer", e))?
+			.then_signal_fence_and_flush()
+			.and_then(|f| f.wait(None))
+			.map_err(|e| EngineError::vulkan("waiting for offscreen rendering", e))?;
+
+		Ok(())
+	}
+
+	/// Get the pixels of the last rendered image, tightly packed as 8-bit RGBA.
+	pub fn read_pixels(&self) -> Result<Vec<u8>, EngineError>
+	{
+		let pixels = self.readback_buf.read().map_err(|e| EngineError::vulkan("reading back pixels", e))?;
+		Ok(pixels.to_vec())
+	}
+
+	/// Write the last rendered image into a PNG file.
+	pub fn save_png(&self, path: &Path) -> Result<(), EngineError>
+	{
+		let pixels = self.read_pixels()?;
+		image::save_buffer(path, &pixels, self.dimensions[0], self.dimensions[1], image::ColorType::Rgba8)
+			.map_err(|e| EngineError::decode(path, e))?;
+		Ok(())
+	}
+
+	pub fn render_pass(&self) -> Arc<RenderPass>
+	{
+		self.render_pass.clone()
+	}
+
+	pub fn dimensions(&self) -> [u32; 2]
+	{
+		self.dimensions
+	}
+}
+
+fn create_framebuffer(
+	image: &Arc<AttachmentImage>,
+	dimensions: [u32; 2],
+	render_pass: Arc<RenderPass>,
+	depth_format: Format,
+	samples: u32
+) -> Result<Arc<Framebuffer>, EngineError>
+{
+	let transient_attachments = TransientAttachments::new(
+		render_pass.device().clone(), dimensions, COLOR_FORMAT, depth_format, samples
+	)?;
+	let view_create_info = vulkano::image::view::ImageViewCreateInfo::from_image(image);
+	let view = vulkano::image::view::ImageView::new(image.clone(), view_create_info)
+		.map_err(|e| EngineError::vulkan("creating offscreen image view", e))?;
+	transient_attachments.create_framebuffer(render_pass, view)
+}
@@ -14,6 +14,10 @@ use vulkano::pipeline::graphics::vertex_input::{ VertexInputState, VertexInputRa
 use vulkano::pipeline::graphics::vertex_input::VertexInputAttributeDescription;
 use vulkano::pipeline::graphics::input_assembly::{ InputAssemblyState, PrimitiveTopology };
 use vulkano::pipeline::graphics::color_blend::{ ColorBlendState, AttachmentBlend };
+use vulkano::pipeline::graphics::depth_stencil::{ DepthStencilState, DepthState, CompareOp };
+use vulkano::pipeline::graphics::multisample::MultisampleState;
+use vulkano::image::SampleCount;
+use vulkano::pipeline::StateMode;
 use vulkano::format::Format;
 use vulkano::command_buffer::{ AutoCommandBufferBuilder, PrimaryAutoCommandBuffer };
 use vulkano::sampler::Sampler;
@@ -23,14 +27,24 @@ use vulkano::descriptor_set::{
 use vulkano::device::DeviceOwned;
 use spirv_reflect::types::image::ReflectFormat;
 use serde::{Serialize, Deserialize};
+use crate::error::EngineError;
+use crate::vfs::Vfs;
+use crate::asset::Asset;
 
 pub struct Pipeline
 {
+	name: String,
 	vs: Arc<ShaderModule>,
 	fs: Option<Arc<ShaderModule>>,
 	samplers: Vec<(usize, u32, Arc<Sampler>)>,
 	pipeline: Arc<GraphicsPipeline>,
-	subpass: Subpass
+	subpass: Subpass,
+
+	// the pipeline definition file this was created from, if any, so that it can be reloaded
+	yaml_filename: Option<String>,
+
+	// logical paths of the files this was created from, to watch for changes
+	source_files: Vec<String>
 }
 impl Pipeline
 {
@@ -39,37 +53,48 @@ impl Pipeline
 		vs_filename: String,
 		fs_filename: Option<String>,
 		samplers: Vec<(usize, u32, Arc<Sampler>)>,	// set: usize, binding: u32, sampler: Arc<Sampler>
+		depth_stencil_state: Option<DepthStencilState>,
 		render_pass: Arc<RenderPass>, 
 		width: u32, height: u32,
-	) -> Result<Pipeline, Box<dyn std::error::Error>>
+		vfs: &Vfs
+	) -> Result<Pipeline, EngineError>
 	{
 		let vk_dev = render_pass.device().clone();
+		let mut source_files = Vec::new();
 
 		// load vertex shader
 		log::info!("Loading vertex shader {}...", vs_filename);
-		let (vs, vertex_input_state) = load_spirv_vertex(vk_dev.clone(), &Path::new("shaders").join(vs_filename))?;
+		let vs_path = Path::new("shaders").join(&vs_filename);
+		let (vs, vertex_input_state) = load_spirv_vertex(vk_dev.clone(), vfs, &vs_path)?;
+		source_files.push(crate::vfs::normalize(&vs_path)?);
 
 		// load fragment shader (optional)
 		let fs = match fs_filename {
 			Some(f) => {
 				log::info!("Loading fragment shader {}...", f);
-				Some(load_spirv(vk_dev.clone(), &Path::new("shaders").join(f))?)
+				let fs_path = Path::new("shaders").join(f);
+				let fs = load_spirv(vk_dev.clone(), vfs, &fs_path)?;
+				source_files.push(crate::vfs::normalize(&fs_path)?);
+				Some(fs)
 			}
 			None => None
 		};
 
-		let subpass = Subpass::from(render_pass.clone(), 0).ok_or("Subpass 0 for render pass doesn't exist!")?;
+		let subpass = Subpass::from(render_pass.clone(), 0)
+			.ok_or_else(|| EngineError::pipeline(&vs_filename, "subpass 0 for render pass doesn't exist"))?;
 		let input_assembly_state = InputAssemblyState::new().topology(primitive_topology);
 		let color_blend_state = color_blend_state_from_subpass(&subpass);
 
 		let pipeline_built = build_pipeline_common(
+			&vs_filename,
 			vk_dev.clone(), input_assembly_state, 
 			vertex_input_state, 
 			width, height,
 			vs.clone(), fs.clone(), 
 			subpass.clone(),
 			&samplers,
-			color_blend_state
+			color_blend_state,
+			depth_stencil_state
 		)?;
 
 		log::debug!("Built pipeline with descriptors:");
@@ -82,66 +107,155 @@ impl Pipeline
 		}
 			
 		Ok(Pipeline{
+			name: vs_filename,
 			vs: vs,
 			fs: fs,
 			samplers: samplers,
 			pipeline: pipeline_built,
 			subpass: subpass,
+			yaml_filename: None,
+			source_files: source_files
 		})
 	}
 
-	/// Create a pipeline from a YAML pipeline configuration file.
-	pub fn new_from_yaml(yaml_filename: &str, render_pass: Arc<RenderPass>, width: u32, height: u32)
-		-> Result<Pipeline, Box<dyn std::error::Error>>
+	/// Create a pipeline from a YAML pipeline configuration file in the "shaders" directory of the virtual filesystem.
+	pub fn new_from_yaml(yaml_filename: &str, render_pass: Arc<RenderPass>, width: u32, height: u32, vfs: &Vfs)
+		-> Result<Pipeline, EngineError>
 	{
-		log::info!("Loading pipeline definition file '{}'...", yaml_filename);
-
-		let yaml_string = String::from_utf8(std::fs::read(Path::new("shaders").join(yaml_filename))?)?;
-
-		let deserialized: PipelineConfig = serde_yaml::from_str(&yaml_string)?;
+		let config = load_config(yaml_filename, vfs)?;
+		Self::new_from_config(yaml_filename, config, render_pass, width, height, vfs)
+	}
 
+	fn new_from_config(
+		yaml_filename: &str,
+		deserialized: PipelineConfig,
+		render_pass: Arc<RenderPass>,
+		width: u32, height: u32,
+		vfs: &Vfs
+	) -> Result<Pipeline, EngineError>
+	{
+		let yaml_path = Path::new("shaders").join(yaml_filename);
 		let mut generated_samplers: Vec<(usize, u32, Arc<Sampler>)> = vec![];
 		match deserialized.samplers {
 			Some(sampler_configs) => for sampler_config in sampler_configs {
 				let mut sampler_create_info = vulkano::sampler::SamplerCreateInfo::default();
 				match sampler_config.mag_filter {
-					Some(f) => sampler_create_info.mag_filter = filter_str_to_enum(&f)?,
+					Some(f) => sampler_create_info.mag_filter = filter_str_to_enum(&f)
+						.ok_or_else(|| EngineError::decode(&yaml_path, format!("invalid sampler filter '{}'", f)))?,
 					None => ()
 				}
 				match sampler_config.min_filter {
-					Some(f) => sampler_create_info.min_filter = filter_str_to_enum(&f)?,
+					Some(f) => sampler_create_info.min_filter = filter_str_to_enum(&f)
+						.ok_or_else(|| EngineError::decode(&yaml_path, format!("invalid sampler filter '{}'", f)))?,
 					None => ()
 				}
 
-				let new_sampler = Sampler::new(render_pass.device().clone(), sampler_create_info)?;
+				let new_sampler = Sampler::new(render_pass.device().clone(), sampler_create_info)
+					.map_err(|e| EngineError::pipeline(yaml_filename, e))?;
 				generated_samplers.push((sampler_config.set, sampler_config.binding, new_sampler));
 			},
 			None => ()
 		}
 
-		Pipeline::new(
+		let depth_stencil_state = match deserialized.depth_test {
+			true => Some(DepthStencilState{
+				depth: Some(DepthState{
+					enable_dynamic: false,
+					write_enable: StateMode::Fixed(deserialized.depth_write.unwrap_or(true)),
+					compare_op: StateMode::Fixed(deserialized.depth_compare_op)
+				}),
+				..DepthStencilState::disabled()
+			}),
+			false => None
+		};
+
+		let mut pipeline = Pipeline::new(
 			deserialized.primitive_topology, 
 			deserialized.vertex_shader, 
 			deserialized.fragment_shader, 
-			generated_samplers, render_pass, width, height
-		)
+			generated_samplers, depth_stencil_state, render_pass, width, height,
+			vfs
+		)?;
+		pipeline.name = yaml_filename.to_string();
+		pipeline.yaml_filename = Some(yaml_filename.to_string());
+		pipeline.source_files.push(crate::vfs::normalize(&yaml_path)?);
+		Ok(pipeline)
 	}
 
-	pub fn resize_viewport(&mut self, width: u32, height: u32) -> Result<(), Box<dyn std::error::Error>>
+	/// Create this pipeline again from its pipeline definition file and shaders, such as after they were modified.
+	/// This fails if the descriptor set layouts would change, since descriptor sets created for this pipeline are
+	/// only valid with the new one if they stay the same. If the shaders fail to load or link, their reflection info
+	/// gets logged to help find the problem.
+	pub fn reload(&self, width: u32, height: u32, vfs: &Vfs) -> Result<Pipeline, EngineError>
+	{
+		let yaml_filename = self.yaml_filename.as_deref()
+			.ok_or_else(|| EngineError::pipeline(&self.name, "not created from a pipeline definition file"))?;
+		let config = load_config(yaml_filename, vfs)?;
+		let shader_files: Vec<String> = std::iter::once(config.vertex_shader.clone())
+			.chain(config.fragment_shader.clone())
+			.collect();
+
+		let reloaded = Self::new_from_config(yaml_filename, config, self.subpass.render_pass().clone(), width, height, vfs)
+			.and_then(|new_pipeline| {
+				let old_layout = self.layout();
+				let new_layout = new_pipeline.layout();
+				let set_count = old_layout.set_layouts().len();
+				if new_layout.set_layouts().len() != set_count 
+					|| !old_layout.is_compatible_with(&new_layout, set_count as u32) {
+					return Err(EngineError::pipeline(
+						yaml_filename, "descriptor set layouts changed, so the game must be restarted to apply the changes"
+					))
+				}
+				Ok(new_pipeline)
+			});
+		match &reloaded {
+			Ok(_) => (),
+			Err(_) => for shader_file in &shader_files {
+				log_reflection_info(vfs, &Path::new("shaders").join(shader_file));
+			}
+		}
+		reloaded
+	}
+
+	/// Logical paths of the pipeline definition file and shaders that this pipeline was created from.
+	pub fn source_files(&self) -> &[String]
+	{
+		&self.source_files
+	}
+
+	/// The name of the pipeline definition file this pipeline was created from, or the name of the vertex shader file
+	/// if it was created with `new`.
+	pub fn name(&self) -> &str
+	{
+		&self.name
+	}
+
+	pub fn resize_viewport(&mut self, width: u32, height: u32) -> Result<(), EngineError>
 	{
 		self.pipeline = build_pipeline_common(
+			&self.name,
 			self.pipeline.device().clone(), 
 			self.pipeline.input_assembly_state().clone(),
 			self.pipeline.vertex_input_state().clone(), width, height,
 			self.vs.clone(), self.fs.clone(), 
 			self.subpass.clone(),
 			&self.samplers,
-			self.pipeline.color_blend_state().cloned()
+			self.pipeline.color_blend_state().cloned(),
+			self.pipeline.depth_stencil_state().cloned()
 		)?;
 
 		Ok(())
 	}
 
+	/// Recreate the pipeline for use with a different render pass, such as one with a different sample count. The render
+	/// pass must have the same attachment formats as the previous one.
+	pub fn set_render_pass(&mut self, render_pass: Arc<RenderPass>, width: u32, height: u32) -> Result<(), EngineError>
+	{
+		self.subpass = Subpass::from(render_pass, 0)
+			.ok_or_else(|| EngineError::pipeline(&self.name, "subpass 0 for render pass doesn't exist"))?;
+		self.resize_viewport(width, height)
+	}
+
 	pub fn bind(&self, command_buffer: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) 
 	{
 		command_buffer.bind_pipeline_graphics(self.pipeline.clone());
@@ -156,13 +270,22 @@ impl Pipeline
 	/// Create a new persistent descriptor set for use with the descriptor set slot at `set_number`, writing `writes`
 	/// into the descriptor set.
 	pub fn new_descriptor_set(&self, set_number: usize, writes: impl IntoIterator<Item = WriteDescriptorSet>)
-		-> Result<Arc<PersistentDescriptorSet>, Box<dyn std::error::Error>>
+		-> Result<Arc<PersistentDescriptorSet>, EngineError>
 	{
 		let pipeline_ref: &dyn vulkano::pipeline::Pipeline = self.pipeline.as_ref();
 		let set_layout = pipeline_ref.layout().set_layouts().get(set_number)
-			.ok_or("Pipeline::new_descriptor_set: invalid descriptor set index")?
+			.ok_or_else(|| EngineError::pipeline(&self.name, format!("invalid descriptor set index {}", set_number)))?
 			.clone();
-		Ok(PersistentDescriptorSet::new(set_layout, writes)?)
+		PersistentDescriptorSet::new(set_layout, writes).map_err(|e| EngineError::vulkan("creating descriptor set", e))
+	}
+}
+
+impl Asset for Pipeline
+{
+	// the memory used by pipelines and shader modules is owned by the driver, and can't be queried
+	fn memory_size(&self) -> u64
+	{
+		0
 	}
 }
 
@@ -181,7 +304,23 @@ struct PipelineConfig {
 	#[serde(with = "PrimitiveTopologyDef")]
 	primitive_topology: PrimitiveTopology,
 
-	samplers: Option<Vec<PipelineSamplerConfig>>
+	samplers: Option<Vec<PipelineSamplerConfig>>,
+
+	// whether fragments get tested against the depth buffer; when this is false, they're always drawn and the depth
+	// buffer isn't written to
+	#[serde(default)]
+	depth_test: bool,
+
+	// whether fragments that pass the depth test get written to the depth buffer; defaults to true if `depth_test` is
+	// enabled
+	depth_write: Option<bool>,
+
+	#[serde(default = "default_compare_op", with = "CompareOpDef")]
+	depth_compare_op: CompareOp
+}
+fn default_compare_op() -> CompareOp
+{
+	CompareOp::Less
 }
 
 // copy of `vulkano::pipeline::graphics::input_assembly::PrimitiveTopology` so we can more directly (de)serialize it
@@ -201,34 +340,60 @@ enum PrimitiveTopologyDef {
     PatchList,
 }
 
-fn filter_str_to_enum(filter_str: &str) -> Result<vulkano::sampler::Filter, Box<dyn std::error::Error>>
+fn load_config(yaml_filename: &str, vfs: &Vfs) -> Result<PipelineConfig, EngineError>
 {
-	Ok(match filter_str {
-		"Nearest" => vulkano::sampler::Filter::Nearest,
-		"Linear" => vulkano::sampler::Filter::Linear,
-		_ => return Err("Invalid sampler filter".into())
-	})
+	log::info!("Loading pipeline definition file '{}'...", yaml_filename);
+
+	let yaml_path = Path::new("shaders").join(yaml_filename);
+	let yaml_string = vfs.read_to_string(&yaml_path)?;
+	serde_yaml::from_str(&yaml_string).map_err(|e| EngineError::decode(&yaml_path, e))
+}
+
+// copy of `vulkano::pipeline::graphics::depth_stencil::CompareOp` so we can more directly (de)serialize it
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CompareOp")]
+enum CompareOpDef {
+    Never,
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
+
+fn filter_str_to_enum(filter_str: &str) -> Option<vulkano::sampler::Filter>
+{
+	match filter_str {
+		"Nearest" => Some(vulkano::sampler::Filter::Nearest),
+		"Linear" => Some(vulkano::sampler::Filter::Linear),
+		_ => None
+	}
 }
 
-fn load_spirv(device: Arc<vulkano::device::Device>, path: &Path) 
-	-> Result<Arc<vulkano::shader::ShaderModule>, Box<dyn std::error::Error>>
+fn load_spirv(device: Arc<vulkano::device::Device>, vfs: &Vfs, path: &Path) 
+	-> Result<Arc<vulkano::shader::ShaderModule>, EngineError>
 {
-	let spv_data = std::fs::read(path)?;
-	Ok(unsafe { vulkano::shader::ShaderModule::from_bytes(device, &spv_data) }?)
+	let spv_data = vfs.read(path)?;
+	unsafe { vulkano::shader::ShaderModule::from_bytes(device, &spv_data) }
+		.map_err(|e| EngineError::pipeline(&path.to_string_lossy(), e))
 }
 
 /// Load the SPIR-V file, and also automatically determine the given vertex shader's vertex inputs using information from the SPIR-V file.
-fn load_spirv_vertex(device: Arc<vulkano::device::Device>, path: &Path)
-	-> Result<(Arc<vulkano::shader::ShaderModule>, VertexInputState), Box<dyn std::error::Error>>
+fn load_spirv_vertex(device: Arc<vulkano::device::Device>, vfs: &Vfs, path: &Path)
+	-> Result<(Arc<vulkano::shader::ShaderModule>, VertexInputState), EngineError>
 {
-	let spv_data = std::fs::read(path)?;
-	let shader_module = spirv_reflect::ShaderModule::load_u8_data(&spv_data)?;
-	let input_variables = shader_module.enumerate_input_variables(Some("main"))?;
+	let spv_data = vfs.read(path)?;
+	let shader_module = spirv_reflect::ShaderModule::load_u8_data(&spv_data).map_err(|e| EngineError::decode(path, e))?;
+	let input_variables = shader_module.enumerate_input_variables(Some("main")).map_err(|e| EngineError::decode(path, e))?;
 
 	let mut i: u32 = 0;
 	let mut vertex_input_state = VertexInputState::new();
 	for input_var in &input_variables {
-		let vertex_format = reflect_format_to_vulkano_format(input_var.format)?;
+		let vertex_format = reflect_format_to_vulkano_format(input_var.format).ok_or_else(|| {
+			EngineError::decode(path, format!("unsupported vertex input format {:?} for '{}'", input_var.format, input_var.name))
+		})?;
 		let stride = vertex_format.components().iter().fold(0, |acc, c| acc + (*c as u32)) / 8;
 
 		vertex_input_state = vertex_input_state
@@ -237,21 +402,50 @@ fn load_spirv_vertex(device: Arc<vulkano::device::Device>, path: &Path)
 		i += 1;
 	}
 
-	Ok((unsafe { vulkano::shader::ShaderModule::from_bytes(device, &spv_data) }?, vertex_input_state))
+	let vs = unsafe { vulkano::shader::ShaderModule::from_bytes(device, &spv_data) }
+		.map_err(|e| EngineError::pipeline(&path.to_string_lossy(), e))?;
+	Ok((vs, vertex_input_state))
 }
 
-#[derive(Debug)]
-pub struct UnsupportedVertexInputFormat;
-impl std::error::Error for UnsupportedVertexInputFormat {}
-impl std::fmt::Display for UnsupportedVertexInputFormat {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "unsupported vertex input format")
-    }
+/// Log the inputs, outputs, and descriptor bindings of the shader, to help find why it failed to load or link.
+fn log_reflection_info(vfs: &Vfs, path: &Path)
+{
+	let reflected = vfs.read(path)
+		.map_err(|e| e.to_string())
+		.and_then(|spv_data| spirv_reflect::ShaderModule::load_u8_data(&spv_data).map_err(|e| e.to_string()));
+	let shader_module = match reflected {
+		Ok(m) => m,
+		Err(e) => {
+			log::error!("Could not get reflection info for shader '{}': {}", path.display(), e);
+			return
+		}
+	};
+
+	let mut info = format!("Reflection info for shader '{}' ({:?}):", path.display(), shader_module.get_shader_stage());
+	match shader_module.enumerate_input_variables(Some("main")) {
+		Ok(vars) => for var in vars.iter().filter(|v| v.location != u32::MAX) {
+			info += &format!("\n  input location {}: '{}' {:?}", var.location, var.name, var.format);
+		},
+		Err(e) => info += &format!("\n  (could not get inputs: {})", e)
+	}
+	match shader_module.enumerate_output_variables(Some("main")) {
+		Ok(vars) => for var in vars.iter().filter(|v| v.location != u32::MAX) {
+			info += &format!("\n  output location {}: '{}' {:?}", var.location, var.name, var.format);
+		},
+		Err(e) => info += &format!("\n  (could not get outputs: {})", e)
+	}
+	match shader_module.enumerate_descriptor_bindings(Some("main")) {
+		Ok(bindings) => for b in bindings {
+			info += &format!("\n  set {}, binding {}: '{}' {}x {:?}", b.set, b.binding, b.name, b.count, b.descriptor_type);
+		},
+		Err(e) => info += &format!("\n  (could not get descriptor bindings: {})", e)
+	}
+	log::error!("{}", info);
 }
-fn reflect_format_to_vulkano_format(reflect_format: spirv_reflect::types::image::ReflectFormat) 
-	-> Result<Format, UnsupportedVertexInputFormat>
+
+fn reflect_format_to_vulkano_format(reflect_format: spirv_reflect::types::image::ReflectFormat) -> Option<Format>
 {
-	Ok(match reflect_format {
+	Some(match reflect_format {
 		ReflectFormat::R32_UINT => Format::R32_UINT,
 		ReflectFormat::R32_SINT => Format::R32_UINT,
 		ReflectFormat::R32_SFLOAT => Format::R32_SFLOAT,
@@ -264,7 +458,7 @@ fn reflect_format_to_vulkano_format(reflect_format: spirv_reflect::types::image:
 		ReflectFormat::R32G32B32A32_UINT => Format::R32G32B32A32_UINT,
 		ReflectFormat::R32G32B32A32_SINT => Format::R32G32B32A32_SINT,
 		ReflectFormat::R32G32B32A32_SFLOAT => Format::R32G32B32A32_SFLOAT,
-		_ => return Err(UnsupportedVertexInputFormat)
+		_ => return None
 	})
 }
 
@@ -284,6 +478,7 @@ fn color_blend_state_from_subpass(subpass: &Subpass) -> Option<ColorBlendState>
 }
 
 fn build_pipeline_common(
+	name: &str,
 	vk_dev: Arc<vulkano::device::Device>, 
 	input_assembly_state: InputAssemblyState,
 	vertex_input_state: VertexInputState,
@@ -292,35 +487,49 @@ fn build_pipeline_common(
 	fs: Option<Arc<ShaderModule>>,
 	subpass: Subpass,
 	samplers: &Vec<(usize, u32, Arc<Sampler>)>,
-	color_blend_state: Option<ColorBlendState>
-) -> Result<Arc<GraphicsPipeline>, Box<dyn std::error::Error>>
+	color_blend_state: Option<ColorBlendState>,
+	depth_stencil_state: Option<DepthStencilState>
+) -> Result<Arc<GraphicsPipeline>, EngineError>
 {
 	let viewport = Viewport{ 
 		origin: [ 0.0, 0.0 ],
 		dimensions: [ width as f32, height as f32 ],
 		depth_range: (0.0..1.0)
 	};
+
+	// the sample count must match the attachments of the subpass
+	let multisample_state = MultisampleState{
+		rasterization_samples: subpass.num_samples().unwrap_or(SampleCount::Sample1),
+		..MultisampleState::new()
+	};
 	
 	// do some building
 	let mut pipeline_builder = GraphicsPipeline::start()
 		.input_assembly_state(input_assembly_state)
 		.vertex_input_state(vertex_input_state)
 		.viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
-		.render_pass(subpass);
+		.render_pass(subpass)
+		.multisample_state(multisample_state);
 
 	match color_blend_state {
 		Some(c) => pipeline_builder = pipeline_builder.color_blend_state(c),
 		None => ()
 	}
+	match depth_stencil_state {
+		Some(d) => pipeline_builder = pipeline_builder.depth_stencil_state(d),
+		None => ()
+	}
 	
-	let vs_entry = vs.entry_point("main").ok_or("No valid 'main' entry point in SPIR-V module!")?;
+	let vs_entry = vs.entry_point("main")
+		.ok_or_else(|| EngineError::pipeline(name, "no valid 'main' entry point in vertex shader"))?;
 	pipeline_builder = pipeline_builder.vertex_shader(vs_entry, ());
 
 	let fs_moved;
 	match fs {
 		Some(fs_exists) => {
 			fs_moved = fs_exists;
-			let fs_entry = fs_moved.entry_point("main").ok_or("No valid 'main' entry point in SPIR-V module!")?;
+			let fs_entry = fs_moved.entry_point("main")
+				.ok_or_else(|| EngineError::pipeline(name, "no valid 'main' entry point in fragment shader"))?;
 			pipeline_builder = pipeline_builder.fragment_shader(fs_entry, ());
 		}
 		None => ()
@@ -341,7 +550,7 @@ fn build_pipeline_common(
 				}
 			}
 		}
-	})?;
+	}).map_err(|e| EngineError::pipeline(name, e))?;
 
 	Ok(pipeline)
 }
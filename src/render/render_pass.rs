@@ -0,0 +1,143 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::sync::Arc;
+use vulkano::format::{ Format, ClearValue };
+use vulkano::image::{ AttachmentImage, SampleCount };
+use vulkano::image::view::{ ImageView, ImageViewAbstract, ImageViewCreateInfo };
+use vulkano::render_pass::{ RenderPass, Framebuffer, FramebufferCreateInfo };
+use crate::error::EngineError;
+
+/// Create the main render pass, which renders into a color image with `color_format`. If `samples` is more than 1, the
+/// color and depth attachments are multisampled, and the color attachment gets resolved into a third attachment, which
+/// is the final image.
+pub fn create_render_pass(vk_dev: Arc<vulkano::device::Device>, color_format: Format, depth_format: Format, samples: u32)
+	-> Result<Arc<RenderPass>, EngineError>
+{
+	let render_pass = match samples {
+		1 => vulkano::single_pass_renderpass!(vk_dev,
+			attachments: {
+				color: {
+					load: Clear,
+					store: Store,
+					format: color_format,
+					samples: 1,
+				},
+				depth: {
+					load: Clear,
+					store: DontCare,
+					format: depth_format,
+					samples: 1,
+				}
+			},
+			pass: {
+				color: [color],
+				depth_stencil: {depth}
+			}
+		),
+		_ => vulkano::single_pass_renderpass!(vk_dev,
+			attachments: {
+				color: {
+					load: Clear,
+					store: DontCare,
+					format: color_format,
+					samples: samples,
+				},
+				depth: {
+					load: Clear,
+					store: DontCare,
+					format: depth_format,
+					samples: samples,
+				},
+				resolve: {
+					load: DontCare,
+					store: Store,
+					format: color_format,
+					samples: 1,
+				}
+			},
+			pass: {
+				color: [color],
+				depth_stencil: {depth},
+				resolve: [resolve]
+			}
+		)
+	};
+	render_pass.map_err(|e| EngineError::vulkan("creating render pass", e))
+}
+
+/// The values to clear the attachments of the main render pass to at the start of the render pass.
+pub fn clear_values(depth_format: Format, samples: u32) -> Vec<Option<ClearValue>>
+{
+	let depth_clear_value = match depth_format.aspects().stencil {
+		true => ClearValue::DepthStencil((1.0, 0)),
+		false => ClearValue::Depth(1.0)
+	};
+	let mut clear_values = vec![ Some([0.1, 0.1, 0.1, 1.0].into()), Some(depth_clear_value) ];
+	if samples > 1 {
+		// the resolve attachment doesn't get cleared, since it gets completely overwritten
+		clear_values.push(None);
+	}
+	clear_values
+}
+
+/// Images used by the main render pass besides the final image. Their contents aren't kept between frames, so they can
+/// be shared between all of the framebuffers for a swapchain.
+pub struct TransientAttachments
+{
+	depth: Arc<ImageView<AttachmentImage>>,
+
+	// the multisampled color image that gets resolved into the final image, if multisampling is enabled
+	color: Option<Arc<ImageView<AttachmentImage>>>
+}
+impl TransientAttachments
+{
+	pub fn new(
+		vk_dev: Arc<vulkano::device::Device>,
+		dimensions: [u32; 2],
+		color_format: Format,
+		depth_format: Format,
+		samples: u32
+	) -> Result<TransientAttachments, EngineError>
+	{
+		let sample_count = SampleCount::try_from(samples)
+			.map_err(|_| EngineError::vulkan("creating depth buffer", format!("invalid sample count {}", samples)))?;
+		let depth_image = AttachmentImage::transient_multisampled(vk_dev.clone(), dimensions, sample_count, depth_format)
+			.map_err(|e| EngineError::vulkan("creating depth buffer", e))?;
+		let depth_view_create_info = ImageViewCreateInfo::from_image(&depth_image);
+		let depth = ImageView::new(depth_image, depth_view_create_info)
+			.map_err(|e| EngineError::vulkan("creating depth buffer image view", e))?;
+
+		let color = match samples {
+			1 => None,
+			_ => {
+				let color_image = AttachmentImage::transient_multisampled(vk_dev, dimensions, sample_count, color_format)
+					.map_err(|e| EngineError::vulkan("creating multisampled color image", e))?;
+				let color_view_create_info = ImageViewCreateInfo::from_image(&color_image);
+				let color_view = ImageView::new(color_image, color_view_create_info)
+					.map_err(|e| EngineError::vulkan("creating multisampled color image view", e))?;
+				Some(color_view)
+			}
+		};
+
+		Ok(TransientAttachments{ depth: depth, color: color })
+	}
+
+	/// Create a framebuffer for the render pass from `create_render_pass` that renders into `final_image`.
+	pub fn create_framebuffer(&self, render_pass: Arc<RenderPass>, final_image: Arc<dyn ImageViewAbstract>)
+		-> Result<Arc<Framebuffer>, EngineError>
+	{
+		// the attachments must be in the same order as in the render pass
+		let attachments: Vec<Arc<dyn ImageViewAbstract>> = match &self.color {
+			Some(color) => vec![ color.clone(), self.depth.clone(), final_image ],
+			None => vec![ final_image, self.depth.clone() ]
+		};
+		let fb_create_info = FramebufferCreateInfo {
+			attachments: attachments,
+			..Default::default()
+		};
+		Framebuffer::new(render_pass, fb_create_info).map_err(|e| EngineError::vulkan("creating framebuffer", e))
+	}
+}
@@ -8,95 +8,104 @@ use winit::window::Window;
 use vulkano::device::Queue;
 use vulkano::command_buffer::{ PrimaryAutoCommandBuffer, CommandBufferExecFuture };
 use vulkano::format::Format;
+use vulkano::image::swapchain::SwapchainImage;
 use vulkano::render_pass::{ RenderPass, Framebuffer };
+use vulkano::device::DeviceOwned;
+use vulkano::device::physical::PhysicalDevice;
 use vulkano::sync::{ FlushError, GpuFuture, FenceSignalFuture};
-use vulkano::swapchain::{ Surface, AcquireError, SwapchainAcquireFuture, PresentFuture };
+use vulkano::swapchain::{ Surface, SurfaceCapabilities, AcquireError, SwapchainAcquireFuture, PresentFuture };
+use crate::error::{ EngineError, SwapchainError };
+use crate::settings::PresentMode;
+use super::render_pass::{ self, TransientAttachments };
+use super::FRAMES_IN_FLIGHT;
 
 pub struct Swapchain
 {
 	swapchain: Arc<vulkano::swapchain::Swapchain<Window>>,
+	images: Vec<Arc<SwapchainImage<Window>>>,
 	swapchain_rp: Arc<RenderPass>,
 	framebuffers: Vec<Arc<Framebuffer>>,
 	cur_image_num: usize,
 	acquire_future: Option<SwapchainAcquireFuture<Window>>,
-	fence_signal_future: Option<
+
+	// the fence for the last submission with each frame index, shared so that the next submission can wait on it too
+	frame_futures: Vec<Option<Arc<
 		FenceSignalFuture<PresentFuture<CommandBufferExecFuture<Box<dyn GpuFuture>, PrimaryAutoCommandBuffer>, Window>>
-	>,	// sheesh, that's a mouthful
+	>>>,	// sheesh, that's a mouthful
+
 	need_new_swapchain: bool,
-	create_info: vulkano::swapchain::SwapchainCreateInfo
+	create_info: vulkano::swapchain::SwapchainCreateInfo,
+	depth_format: Format,
+	samples: u32
 }
 impl Swapchain
 {
-	pub fn new(vk_dev: Arc<vulkano::device::Device>, window_surface: Arc<Surface<Window>>) 
-		-> Result<Swapchain, Box<dyn std::error::Error>>
+	pub fn new(
+		vk_dev: Arc<vulkano::device::Device>, 
+		window_surface: Arc<Surface<Window>>, 
+		depth_format: Format, 
+		samples: u32,
+		present_mode: PresentMode,
+		image_count: Option<u32>
+	) -> Result<Swapchain, EngineError>
 	{
 		// query surface capabilities
 		let surf_caps = vk_dev.physical_device().surface_capabilities(
 			&window_surface, vulkano::swapchain::SurfaceInfo::default()
-		)?;
+		).map_err(EngineError::swapchain)?;
 
 		let swapchain_create_info = vulkano::swapchain::SwapchainCreateInfo {
-			min_image_count: surf_caps.min_image_count,
+			min_image_count: select_image_count(&surf_caps, image_count),
+			present_mode: select_present_mode(vk_dev.physical_device(), &window_surface, present_mode)?,
 			image_format: Some(Format::B8G8R8A8_SRGB),
+			image_extent: window_surface.window().inner_size().into(),
 			image_usage: vulkano::image::ImageUsage::color_attachment(),
 			..vulkano::swapchain::SwapchainCreateInfo::default()
 		};
 		// TODO: sharing mode using `&queue`?
 		let (swapchain, swapchain_images) = vulkano::swapchain::Swapchain::new(
 			vk_dev.clone(), window_surface.clone(), swapchain_create_info.clone()
-		)?;
-
-		// create render pass
-		let swapchain_rp = vulkano::single_pass_renderpass!(vk_dev.clone(),
-			attachments: {
-				color: {
-					load: Clear,
-					store: Store,
-					format: swapchain.image_format(),
-					samples: 1,
-				}
-			}, 
-			pass: {
-				color: [color],
-				depth_stencil: {}
-			}
-		)?;
+		).map_err(EngineError::swapchain)?;
 
-		let framebuffers = create_framebuffers(swapchain_images, swapchain_rp.clone())?;
+		let swapchain_rp = render_pass::create_render_pass(vk_dev, swapchain.image_format(), depth_format, samples)?;
+		let framebuffers = create_framebuffers(&swapchain, &swapchain_images, swapchain_rp.clone(), depth_format, samples)?;
 
 		Ok(Swapchain{
 			swapchain: swapchain,
+			images: swapchain_images,
 			swapchain_rp: swapchain_rp,
 			framebuffers: framebuffers,
 			cur_image_num: 0,
 			acquire_future: None,
-			fence_signal_future: None,
+			frame_futures: (0..FRAMES_IN_FLIGHT).map(|_| None).collect(),
 			need_new_swapchain: false,
-			create_info: swapchain_create_info
+			create_info: swapchain_create_info,
+			depth_format: depth_format,
+			samples: samples
 		})
 	}
 
 	/// Get the next swapchain image.
 	/// Returns the corresponding framebuffer, and a bool indicating if the image dimensions changed.
-	pub fn get_next_image(&mut self) -> Result<(Arc<vulkano::render_pass::Framebuffer>, bool), Box<dyn std::error::Error>>
+	pub fn get_next_image(&mut self) -> Result<(Arc<vulkano::render_pass::Framebuffer>, bool), EngineError>
 	{
 		// Recreate the swapchain if needed.
 		let dimensions_changed = match self.need_new_swapchain {
 			true => {
 				let prev_dimensions = self.swapchain.image_extent();
-				let (new_swapchain, new_images) = self.swapchain.recreate(self.create_info.clone())?;
+				self.create_info.image_extent = self.window().inner_size().into();
+				let (new_swapchain, new_images) = self.swapchain.recreate(self.create_info.clone())
+					.map_err(EngineError::swapchain)?;
 				self.swapchain = new_swapchain;
-				self.framebuffers = create_framebuffers(new_images, self.swapchain_rp.clone())?;
+				self.framebuffers = create_framebuffers(
+					&self.swapchain, &new_images, self.swapchain_rp.clone(), self.depth_format, self.samples
+				)?;
+				self.images = new_images;
 				self.swapchain.image_extent() != prev_dimensions
 			}
 			false => false
 		};
 		self.need_new_swapchain = false;
-		
-		match self.fence_signal_future.as_mut() {
-			Some(p) => p.cleanup_finished(),
-			None => ()
-		}
 
 		let (image_num, suboptimal, acquire_future) =
 			match vulkano::swapchain::acquire_next_image(self.swapchain.clone(), None) {
@@ -106,7 +115,7 @@ impl Swapchain
 					self.need_new_swapchain = true;
 					return self.get_next_image();	// recreate the swapchain then try again
 				}
-				Err(e) => return Err(Box::new(e))
+				Err(e) => return Err(EngineError::swapchain(e))
 			};
 
 		if suboptimal {
@@ -123,21 +132,31 @@ impl Swapchain
 	/*pub fn get_current_image(&self) -> Arc<vulkano::render_pass::Framebuffer>
 	{
 		self.framebuffers[self.cur_image_num].clone()
-	}
+	}*/
 
-	pub fn wait_for_fence(&self) -> Result<(), FlushError>
+	/// Wait for the last submission with the frame index to finish, if it hasn't already.
+	pub fn wait_for_frame(&mut self, frame_index: usize) -> Result<(), EngineError>
 	{
-		match self.fence_signal_future.as_ref() {
-			Some(f) => f.wait(None),
+		match self.frame_futures[frame_index].take() {
+			Some(f) => f.wait(None).map_err(|e| EngineError::vulkan("waiting for frame", e)),
 			None => Ok(())
 		}
-	}*/
+	}
 
-	pub fn submit_commands(&mut self, cb: PrimaryAutoCommandBuffer, queue: Arc<Queue>, futures: Option<Box<dyn GpuFuture>>)
-		-> Result<(), Box<dyn std::error::Error>>
+	pub fn submit_commands(
+		&mut self, 
+		cb: PrimaryAutoCommandBuffer, 
+		queue: Arc<Queue>, 
+		futures: Option<Box<dyn GpuFuture>>,
+		frame_index: usize
+	) -> Result<(), EngineError>
 	{
-		let acquire_future = self.acquire_future.take().ok_or(ImageNotAcquired)?;
-		let mut joined_future = match self.fence_signal_future.take() {
+		let acquire_future = self.acquire_future.take()
+			.ok_or_else(|| EngineError::swapchain(SwapchainError::NoImageAcquired))?;
+
+		// wait on the previous frame too, which may still be in flight
+		let prev_index = (frame_index + FRAMES_IN_FLIGHT - 1) % FRAMES_IN_FLIGHT;
+		let mut joined_future = match self.frame_futures[prev_index].clone() {
 			Some(f) => f.join(acquire_future).boxed(),
 			None => acquire_future.boxed()
 		};
@@ -149,16 +168,60 @@ impl Swapchain
 		}
 
 		let future_result = joined_future
-			.then_execute(queue.clone(), cb)?
+			.then_execute(queue.clone(), cb)
+			.map_err(|e| EngineError::vulkan("executing command buffer", e))?
 			.then_swapchain_present(queue, self.swapchain.clone(), self.cur_image_num)
 			.then_signal_fence_and_flush();
 
 		match future_result {
-			Ok(future) => self.fence_signal_future = Some(future),
+			Ok(future) => self.frame_futures[frame_index] = Some(Arc::new(future)),
 			Err(FlushError::OutOfDate) => self.need_new_swapchain = true,
-			Err(e) => return Err(Box::new(e))
+			Err(e) => return Err(EngineError::swapchain(e))
+		}
+
+		Ok(())
+	}
+
+	pub fn window(&self) -> &Window
+	{
+		self.swapchain.surface().window()
+	}
+
+	/// Recreate the swapchain in the next frame to match the new size of the window. This should be called when the
+	/// window gets resized, or when its window mode is changed.
+	pub fn window_resized(&mut self)
+	{
+		self.need_new_swapchain = true;
+	}
+
+	/// Change the present mode and the number of swapchain images, recreating the swapchain in the next frame if they
+	/// differ from the current ones.
+	pub fn set_present_mode(&mut self, present_mode: PresentMode, image_count: Option<u32>) -> Result<(), EngineError>
+	{
+		let surface = self.swapchain.surface().clone();
+		let pd = self.swapchain.device().physical_device();
+		let surf_caps = pd.surface_capabilities(&surface, vulkano::swapchain::SurfaceInfo::default())
+			.map_err(EngineError::swapchain)?;
+		let new_present_mode = select_present_mode(pd, &surface, present_mode)?;
+		let new_image_count = select_image_count(&surf_caps, image_count);
+
+		if new_present_mode != self.create_info.present_mode || new_image_count != self.create_info.min_image_count {
+			self.create_info.present_mode = new_present_mode;
+			self.create_info.min_image_count = new_image_count;
+			self.need_new_swapchain = true;
 		}
+		Ok(())
+	}
 
+	/// Change the number of samples per pixel, creating a new render pass and framebuffers. Pipelines must be recreated
+	/// with the new render pass afterwards.
+	pub fn set_samples(&mut self, samples: u32) -> Result<(), EngineError>
+	{
+		let vk_dev = self.swapchain.device().clone();
+		let new_rp = render_pass::create_render_pass(vk_dev, self.swapchain.image_format(), self.depth_format, samples)?;
+		self.framebuffers = create_framebuffers(&self.swapchain, &self.images, new_rp.clone(), self.depth_format, samples)?;
+		self.swapchain_rp = new_rp;
+		self.samples = samples;
 		Ok(())
 	}
 
@@ -173,31 +236,63 @@ impl Swapchain
 	}
 }
 
+/// Get the present mode to use for the one in the settings, falling back to the closest supported one. FIFO is always
+/// supported, so that's the last resort.
+fn select_present_mode(pd: PhysicalDevice, surface: &Surface<Window>, requested: PresentMode)
+	-> Result<vulkano::swapchain::PresentMode, EngineError>
+{
+	use vulkano::swapchain::PresentMode as VkPresentMode;
+
+	let candidates: &[VkPresentMode] = match requested {
+		PresentMode::Fifo => &[ VkPresentMode::Fifo ],
+		PresentMode::FifoRelaxed => &[ VkPresentMode::FifoRelaxed, VkPresentMode::Fifo ],
+		PresentMode::Mailbox => &[ VkPresentMode::Mailbox, VkPresentMode::Fifo ],
+		PresentMode::Immediate => &[ VkPresentMode::Immediate, VkPresentMode::Mailbox, VkPresentMode::Fifo ]
+	};
+	let supported: Vec<VkPresentMode> = pd.surface_present_modes(surface).map_err(EngineError::swapchain)?.collect();
+	let present_mode = candidates.iter()
+		.copied()
+		.find(|m| supported.contains(m))
+		.unwrap_or(VkPresentMode::Fifo);
+	if present_mode != candidates[0] {
+		log::warn!("Present mode {:?} isn't supported, using {:?} instead", requested, present_mode);
+	}
+	Ok(present_mode)
+}
+
+/// Get the number of swapchain images to use, clamped to what the surface supports.
+fn select_image_count(surf_caps: &SurfaceCapabilities, requested: Option<u32>) -> u32
+{
+	let max_image_count = surf_caps.max_image_count.unwrap_or(u32::MAX);
+	let image_count = requested.unwrap_or(surf_caps.min_image_count).clamp(surf_caps.min_image_count, max_image_count);
+	if requested.map_or(false, |r| r != image_count) {
+		log::warn!(
+			"{} swapchain images aren't supported, using {} instead (the device supports {} to {})",
+			requested.unwrap_or_default(), image_count, surf_caps.min_image_count, max_image_count
+		);
+	}
+	image_count
+}
+
 fn create_framebuffers(
-	images: Vec<Arc<vulkano::image::swapchain::SwapchainImage<Window>>>, 
-	render_pass: Arc<vulkano::render_pass::RenderPass>
-) -> Result<Vec::<Arc<Framebuffer>>, Box<dyn std::error::Error>>
+	swapchain: &vulkano::swapchain::Swapchain<Window>,
+	images: &Vec<Arc<SwapchainImage<Window>>>, 
+	render_pass: Arc<vulkano::render_pass::RenderPass>,
+	depth_format: Format,
+	samples: u32
+) -> Result<Vec::<Arc<Framebuffer>>, EngineError>
 {
+	let transient_attachments = TransientAttachments::new(
+		swapchain.device().clone(), swapchain.image_extent(), swapchain.image_format(), depth_format, samples
+	)?;
+
 	let mut framebuffers = Vec::<Arc<Framebuffer>>::with_capacity(images.len());
 	for img in images {
-		let view_create_info = vulkano::image::view::ImageViewCreateInfo::from_image(&img);
-		let view = vulkano::image::view::ImageView::new(img, view_create_info)?;
-		// TODO: add depth buffers
-		let fb_create_info = vulkano::render_pass::FramebufferCreateInfo {
-			attachments: vec![ view ],
-			..Default::default()
-		};
-		framebuffers.push(Framebuffer::new(render_pass.clone(), fb_create_info)?);
+		let view_create_info = vulkano::image::view::ImageViewCreateInfo::from_image(img);
+		let view = vulkano::image::view::ImageView::new(img.clone(), view_create_info)
+			.map_err(|e| EngineError::vulkan("creating swapchain image view", e))?;
+		framebuffers.push(transient_attachments.create_framebuffer(render_pass.clone(), view)?);
 	}
 	
 	Ok(framebuffers)
 }
-
-#[derive(Debug)]
-struct ImageNotAcquired;
-impl std::error::Error for ImageNotAcquired {}
-impl std::fmt::Display for ImageNotAcquired {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "An attempt was made to submit a primary command buffer when no image was acquired!")
-    }
-}
@@ -10,25 +10,56 @@ use vulkano::format::{ Format };
 use vulkano::command_buffer::{ CommandBufferExecFuture, PrimaryAutoCommandBuffer };
 use vulkano::sync::NowFuture;
 use ddsfile::DxgiFormat;
+use crate::error::EngineError;
+use crate::asset::Asset;
+use crate::vfs::Vfs;
 
-pub struct Texture
+/// Image data decoded from a file, ready to be uploaded into a `Texture`. Decoding doesn't need the GPU, so it can be
+/// done on another thread.
+pub struct DecodedImage
 {
-	view: Arc<ImageView<ImmutableImage>>,
-	dimensions: ImageDimensions
+	pub format: Format,
+	pub dimensions: ImageDimensions,
+	pub mip: MipmapsCount,
+	pub data: Vec<u8>
 }
-impl Texture
+impl DecodedImage
 {
-	pub fn new(queue: Arc<vulkano::device::Queue>, path: &Path) 
-		-> Result<(Texture, CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>), Box<dyn std::error::Error>>
+	/// Read and decode the image file at the logical path in the virtual filesystem.
+	pub fn load(vfs: &Vfs, path: &Path) -> Result<DecodedImage, EngineError>
 	{
 		// TODO: animated textures using APNG or multi-layer DDS
-		let file_ext = path.extension().ok_or("Could not determine texture file extension!")?.to_str();
+		let data = vfs.read(path)?;
+		let file_ext = path.extension().and_then(|e| e.to_str());
 		let (vk_fmt, dim, mip, img_raw) = match file_ext {
-			Some("dds") => load_dds(path)?,
-			_ => load_other_format(path)?
+			Some("dds") => load_dds(path, &data)?,
+			_ => load_other_format(path, &data)?
 		};
 
-		Self::new_from_iter(queue, img_raw, vk_fmt, dim, mip)
+		Ok(DecodedImage{ format: vk_fmt, dimensions: dim, mip: mip, data: img_raw })
+	}
+}
+
+pub struct Texture
+{
+	view: Arc<ImageView<ImmutableImage>>,
+	dimensions: ImageDimensions,
+	memory_size: u64
+}
+impl Texture
+{
+	/// Load a texture from the file at the logical path in the virtual filesystem.
+	pub fn new(queue: Arc<vulkano::device::Queue>, vfs: &Vfs, path: &Path) 
+		-> Result<(Texture, CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>), EngineError>
+	{
+		Self::from_decoded(queue, DecodedImage::load(vfs, path)?)
+	}
+
+	/// Upload the decoded image into a new texture.
+	pub fn from_decoded(queue: Arc<vulkano::device::Queue>, image: DecodedImage)
+		-> Result<(Texture, CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>), EngineError>
+	{
+		Self::new_from_iter(queue, image.data, image.format, image.dimensions, image.mip)
 	}
 
 	pub fn new_from_iter<Px, I>(
@@ -38,18 +69,23 @@ impl Texture
 		dimensions: ImageDimensions,
 		mip: MipmapsCount
 	) 
-		-> Result<(Texture, CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>), Box<dyn std::error::Error>>
+		-> Result<(Texture, CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>), EngineError>
 	where
 		[Px]: vulkano::buffer::BufferContents,
 		I: IntoIterator<Item = Px>,
 		I::IntoIter: ExactSizeIterator,
 	{
-		let (vk_img, upload_future) = ImmutableImage::from_iter(iter, dimensions, mip, vk_fmt, queue)?;
+		let iter = iter.into_iter();
+		let memory_size = (iter.len() * std::mem::size_of::<Px>()) as u64;
+		let (vk_img, upload_future) = ImmutableImage::from_iter(iter, dimensions, mip, vk_fmt, queue)
+			.map_err(|e| EngineError::vulkan("creating texture image", e))?;
 		let view_create_info = vulkano::image::view::ImageViewCreateInfo::from_image(&vk_img);
 		Ok((
 			Texture{
-				view: ImageView::new(vk_img, view_create_info)?,
-				dimensions: dimensions
+				view: ImageView::new(vk_img, view_create_info)
+					.map_err(|e| EngineError::vulkan("creating texture image view", e))?,
+				dimensions: dimensions,
+				memory_size: memory_size
 			},
 			upload_future
 		))
@@ -65,17 +101,23 @@ impl Texture
 		self.dimensions
 	}
 }
-
-fn load_dds(path: &Path) -> Result<(Format, ImageDimensions, MipmapsCount, Vec<u8>), Box<dyn std::error::Error>>
+impl Asset for Texture
 {
-	let dds_file = std::fs::File::open(path)
-		.or_else(|e| Err(format!("Could not open '{}': {}", path.display(), e)))?;
+	// the size of the data uploaded to the image, including all mip levels
+	fn memory_size(&self) -> u64
+	{
+		self.memory_size
+	}
+}
 
-	let dds = ddsfile::Dds::read(dds_file)?;
+fn load_dds(path: &Path, data: &[u8]) -> Result<(Format, ImageDimensions, MipmapsCount, Vec<u8>), EngineError>
+{
+	let dds = ddsfile::Dds::read(data).map_err(|e| EngineError::decode(path, e))?;
 	let dds_format = dds.get_dxgi_format()
-		.ok_or("Could not determine DDS image format! Make sure it's in DXGI format.")?;
+		.ok_or_else(|| EngineError::decode(path, "could not determine DDS image format, make sure it's in DXGI format"))?;
 
-	let vk_fmt = dxgi_to_vulkan_format(dds_format)?;
+	let vk_fmt = dxgi_to_vulkan_format(dds_format)
+		.ok_or_else(|| EngineError::decode(path, format!("unsupported DDS format {:?}", dds_format)))?;
 	let dim = ImageDimensions::Dim2d{ width: dds.get_width(), height: dds.get_height(), array_layers: 1 };
 	let mip = MipmapsCount::Specific(dds.get_num_mipmap_levels());
 	let img_raw = dds.data;
@@ -83,9 +125,10 @@ fn load_dds(path: &Path) -> Result<(Format, ImageDimensions, MipmapsCount, Vec<u
 	Ok((vk_fmt, dim, mip, img_raw))
 }
 
-fn load_other_format(path: &Path) -> Result<(Format, ImageDimensions, MipmapsCount, Vec<u8>), Box<dyn std::error::Error>>
+fn load_other_format(path: &Path, data: &[u8]) -> Result<(Format, ImageDimensions, MipmapsCount, Vec<u8>), EngineError>
 {
-	let img = image::io::Reader::open(path)?.decode()?;
+	// the format is guessed from the contents, since the extension might not match (such as for files in mods)
+	let img = image::load_from_memory(data).map_err(|e| EngineError::decode(path, e))?;
 
 	let vk_fmt = Format::R8G8B8A8_SRGB;	// TODO: other formats such as greyscale
 	let dim = ImageDimensions::Dim2d{ width: img.width(), height: img.height(), array_layers: 1 };
@@ -95,15 +138,15 @@ fn load_other_format(path: &Path) -> Result<(Format, ImageDimensions, MipmapsCou
 	Ok((vk_fmt, dim, mip, img_raw))
 }
 
-fn dxgi_to_vulkan_format(dxgi_format: DxgiFormat) -> Result<Format, Box<dyn std::error::Error>>
+fn dxgi_to_vulkan_format(dxgi_format: DxgiFormat) -> Option<Format>
 {
-	Ok(match dxgi_format {
+	Some(match dxgi_format {
 		DxgiFormat::BC1_UNorm_sRGB => Format::BC1_RGBA_SRGB_BLOCK,
 		DxgiFormat::BC2_UNorm_sRGB => Format::BC2_SRGB_BLOCK,
 		DxgiFormat::BC3_UNorm_sRGB => Format::BC3_SRGB_BLOCK,
 		DxgiFormat::BC4_UNorm => Format::BC4_UNORM_BLOCK,
 		DxgiFormat::BC5_UNorm => Format::BC5_UNORM_BLOCK,
-		_ => return Err("Unsupported DDS format!".into())
+		_ => return None
 	})
 }
 
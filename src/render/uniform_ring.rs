@@ -0,0 +1,78 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::sync::Arc;
+use vulkano::buffer::{ BufferUsage, cpu_access::CpuAccessibleBuffer };
+use vulkano::descriptor_set::persistent::PersistentDescriptorSet;
+use vulkano::descriptor_set::WriteDescriptorSet;
+use crate::error::EngineError;
+use super::{ RenderContext, FRAMES_IN_FLIGHT };
+
+/// A uniform buffer with a separate copy for each frame in flight, so that it can be updated every frame without
+/// writing into a copy that the GPU may still be reading from for a previous frame.
+pub struct UniformRing
+{
+	// the latest data, which gets written into each copy the next time that copy gets bound
+	data: Vec<f32>,
+	buffers: Vec<Arc<CpuAccessibleBuffer<[f32]>>>,
+	descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+
+	// whether each copy has the latest data
+	up_to_date: Vec<bool>
+}
+impl UniformRing
+{
+	/// Create the copies of the buffer with the data, along with descriptor sets for them to be bound at `binding` in
+	/// the descriptor set slot at `set` of the pipeline.
+	pub fn new(render_ctx: &mut RenderContext, data: &[f32], pipeline_name: &str, set: usize, binding: u32)
+		-> Result<UniformRing, EngineError>
+	{
+		let mut buffers = Vec::with_capacity(FRAMES_IN_FLIGHT);
+		let mut descriptor_sets = Vec::with_capacity(FRAMES_IN_FLIGHT);
+		for _ in 0..FRAMES_IN_FLIGHT {
+			let buf = render_ctx.new_cpu_buffer(data.iter().copied(), BufferUsage::uniform_buffer())?;
+			descriptor_sets.push(render_ctx.new_descriptor_set(pipeline_name, set, [
+				WriteDescriptorSet::buffer(binding, buf.clone())
+			])?);
+			buffers.push(buf);
+		}
+
+		Ok(UniformRing{
+			data: data.to_vec(),
+			buffers: buffers,
+			descriptor_sets: descriptor_sets,
+			up_to_date: vec![ true; FRAMES_IN_FLIGHT ]
+		})
+	}
+
+	/// Change the data, which must be the same size as the data the buffer was created with. The copies get updated as
+	/// they get bound in the following frames.
+	pub fn set_data(&mut self, data: &[f32]) -> Result<(), EngineError>
+	{
+		if data.len() != self.data.len() {
+			return Err(EngineError::vulkan(
+				"updating uniform buffer", format!("expected {} values, got {}", self.data.len(), data.len())
+			))
+		}
+		self.data.copy_from_slice(data);
+		self.up_to_date.iter_mut().for_each(|u| *u = false);
+		Ok(())
+	}
+
+	/// Bind the copy for the current frame to the descriptor set slot at `set` of the currently bound pipeline, writing
+	/// the latest data into it first if needed.
+	pub fn bind(&mut self, render_ctx: &mut RenderContext, set: u32) -> Result<(), EngineError>
+	{
+		let frame_index = render_ctx.frame_index();
+		if !self.up_to_date[frame_index] {
+			// the GPU has finished the frame that last used this copy, since `RenderContext::begin_frame` waited for it
+			self.buffers[frame_index].write()
+				.map_err(|e| EngineError::vulkan("writing uniform buffer", e))?
+				.copy_from_slice(&self.data);
+			self.up_to_date[frame_index] = true;
+		}
+		render_ctx.bind_descriptor_set(set, self.descriptor_sets[frame_index].clone())
+	}
+}
@@ -0,0 +1,135 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use winit::window::{ Window, Fullscreen };
+use winit::monitor::{ MonitorHandle, VideoMode };
+use winit::dpi::PhysicalSize;
+use crate::settings::{ VideoSettings, WindowMode };
+
+/// A monitor connected to the system, for choosing `VideoSettings::monitor` in a settings menu.
+#[derive(Clone, Debug)]
+pub struct MonitorInfo
+{
+	/// Index of the monitor, as used in `VideoSettings::monitor`.
+	pub index: usize,
+
+	pub name: String,
+
+	/// The current resolution of the monitor, which is used in borderless mode.
+	pub resolution: [u32; 2],
+
+	/// The resolutions and refresh rates that can be used in fullscreen mode.
+	pub video_modes: Vec<VideoModeInfo>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VideoModeInfo
+{
+	pub resolution: [u32; 2],
+
+	/// Refresh rate in Hz.
+	pub refresh_rate: u16
+}
+
+/// Get the monitors that the window can be placed on.
+pub fn monitors(window: &Window) -> Vec<MonitorInfo>
+{
+	window.available_monitors()
+		.enumerate()
+		.map(|(i, monitor)| {
+			let mut video_modes: Vec<VideoModeInfo> = monitor.video_modes()
+				.map(|m| VideoModeInfo{ resolution: m.size().into(), refresh_rate: m.refresh_rate() })
+				.collect();
+			video_modes.sort_by(|a, b| b.resolution.cmp(&a.resolution).then(b.refresh_rate.cmp(&a.refresh_rate)));
+			video_modes.dedup();
+
+			MonitorInfo{
+				index: i,
+				name: monitor.name().unwrap_or_else(|| format!("Monitor {}", i + 1)),
+				resolution: monitor.size().into(),
+				video_modes: video_modes
+			}
+		})
+		.collect()
+}
+
+/// Set the window mode, monitor, and size of the window according to the video settings. If the settings can't be
+/// applied as they are, such as if the monitor isn't connected anymore, the closest available option is used instead.
+pub fn apply_video_settings(window: &Window, video: &VideoSettings)
+{
+	let monitor = select_monitor(window, video.monitor);
+	let fullscreen = match (video.window_mode, &monitor) {
+		(WindowMode::Windowed, _) => None,
+		(WindowMode::Borderless, _) => Some(Fullscreen::Borderless(monitor.clone())),
+		(WindowMode::Fullscreen, Some(m)) => match select_video_mode(m, video.resolution, video.refresh_rate) {
+			Some(video_mode) => {
+				log::info!(
+					"Using fullscreen video mode {}x{} @ {} Hz on '{}'",
+					video_mode.size().width, video_mode.size().height, video_mode.refresh_rate(),
+					m.name().unwrap_or_default()
+				);
+				Some(Fullscreen::Exclusive(video_mode))
+			}
+			None => {
+				log::warn!(
+					"No fullscreen video mode with resolution {}x{} is available, using borderless mode instead",
+					video.resolution[0], video.resolution[1]
+				);
+				Some(Fullscreen::Borderless(monitor.clone()))
+			}
+		},
+		(WindowMode::Fullscreen, None) => {
+			log::warn!("Could not find a monitor to go fullscreen on, using borderless mode instead");
+			Some(Fullscreen::Borderless(None))
+		}
+	};
+
+	let windowed = fullscreen.is_none();
+	window.set_fullscreen(fullscreen);
+	if windowed {
+		window.set_inner_size(PhysicalSize::new(video.resolution[0], video.resolution[1]));
+	}
+}
+
+/// Check if the window has no area to render to, such as when it's minimized.
+pub fn is_minimized(window: &Window) -> bool
+{
+	let size = window.inner_size();
+	size.width == 0 || size.height == 0
+}
+
+// Get the monitor at the index, or the monitor the window is currently on if the index is `None` or invalid.
+fn select_monitor(window: &Window, index: Option<usize>) -> Option<MonitorHandle>
+{
+	let current = || window.current_monitor().or_else(|| window.primary_monitor());
+	match index {
+		Some(i) => window.available_monitors().nth(i).or_else(|| {
+			log::warn!("Monitor {} isn't connected, using the current monitor instead", i);
+			current()
+		}),
+		None => current()
+	}
+}
+
+// Find the video mode with the resolution and refresh rate, or the highest refresh rate available at the resolution if
+// `refresh_rate` is `None` or unavailable.
+fn select_video_mode(monitor: &MonitorHandle, resolution: [u32; 2], refresh_rate: Option<u16>) -> Option<VideoMode>
+{
+	let mut candidates: Vec<VideoMode> = monitor.video_modes()
+		.filter(|m| <[u32; 2]>::from(m.size()) == resolution)
+		.collect();
+	candidates.sort_by(|a, b| b.refresh_rate().cmp(&a.refresh_rate()).then(b.bit_depth().cmp(&a.bit_depth())));
+
+	match refresh_rate {
+		Some(r) => match candidates.iter().position(|m| m.refresh_rate() == r) {
+			Some(i) => Some(candidates.swap_remove(i)),
+			None => {
+				log::warn!("Refresh rate {} Hz isn't available, using the highest available one instead", r);
+				candidates.into_iter().next()
+			}
+		},
+		None => candidates.into_iter().next()
+	}
+}
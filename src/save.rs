@@ -0,0 +1,383 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+const SAVE_MAGIC: &[u8; 4] = b"MESV";
+
+/// Version of the save file container format. This is separate from the version of the game's data inside it.
+const CONTAINER_VERSION: u32 = 1;
+
+/// magic (4 bytes) + container version (u32) + data version (u32) + payload length (u64) + payload CRC32 (u32)
+const HEADER_LEN: usize = 4 + 4 + 4 + 8 + 4;
+
+/// Versioned save slots stored in the "saves" directory under the preferences path. Available to systems as a unique.
+///
+/// Each save file has a header with the version of the game's data, and a checksum of the data to detect damaged
+/// files. Files are written to a temporary file first, then renamed over the old one, so that the old save doesn't get
+/// lost if the game crashes or the power goes out while saving.
+pub struct SaveManager
+{
+	dir: PathBuf,
+	data_version: u32
+}
+impl SaveManager
+{
+	/// `data_version` is the current version of the game's save data, which gets written into new saves. Increment it
+	/// whenever the structure of the save data changes, and handle older versions with `load_migrated`.
+	pub fn new(dir: PathBuf, data_version: u32) -> Result<SaveManager, SaveError>
+	{
+		std::fs::create_dir_all(&dir).map_err(|e| SaveError::Io{ path: dir.clone(), source: e })?;
+		Ok(SaveManager{ dir: dir, data_version: data_version })
+	}
+
+	pub fn slot_path(&self, slot: u32) -> PathBuf
+	{
+		self.dir.join(format!("slot{}.sav", slot))
+	}
+
+	pub fn slot_exists(&self, slot: u32) -> bool
+	{
+		self.slot_path(slot).exists()
+	}
+
+	/// Get the numbers of all slots that have a save file, in ascending order.
+	pub fn list_slots(&self) -> Result<Vec<u32>, SaveError>
+	{
+		let read_dir = std::fs::read_dir(&self.dir).map_err(|e| SaveError::Io{ path: self.dir.clone(), source: e })?;
+		let mut slots: Vec<u32> = read_dir
+			.filter_map(|entry| entry.ok())
+			.filter_map(|entry| {
+				let file_name = entry.file_name();
+				file_name.to_str()
+					.and_then(|n| n.strip_prefix("slot"))
+					.and_then(|n| n.strip_suffix(".sav"))
+					.and_then(|n| n.parse().ok())
+			})
+			.collect();
+		slots.sort_unstable();
+		Ok(slots)
+	}
+
+	/// Write `data` into the save slot, replacing the save that was already there.
+	pub fn save<T: Serialize>(&self, slot: u32, data: &T) -> Result<(), SaveError>
+	{
+		let path = self.slot_path(slot);
+		let payload = serde_yaml::to_string(data)
+			.map_err(|e| SaveError::Serialize{ path: path.clone(), message: e.to_string() })?
+			.into_bytes();
+
+		write_atomic(&path, &encode_save_file(self.data_version, &payload))?;
+		log::info!("Saved slot {} to '{}'", slot, path.display());
+		Ok(())
+	}
+
+	/// Load the data in the save slot. This fails with `SaveError::UnsupportedVersion` if the save was made with a
+	/// different data version; use `load_migrated` to load older saves.
+	pub fn load<T: DeserializeOwned>(&self, slot: u32) -> Result<T, SaveError>
+	{
+		match self.load_migrated(slot, |version, _| Err(format!("no migration from data version {}", version))) {
+			Err(SaveError::Migration{ path, version, .. }) => {
+				Err(SaveError::UnsupportedVersion{ path: path, version: version })
+			}
+			result => result
+		}
+	}
+
+	/// Load the data in the save slot, calling `migrate` to convert the data if it was saved with an older data
+	/// version. `migrate` receives the version the data was saved with along with the data, and should return the
+	/// data converted to the current version. If it returns an error, loading fails with `SaveError::Migration`.
+	pub fn load_migrated<T, F>(&self, slot: u32, migrate: F) -> Result<T, SaveError>
+		where
+			T: DeserializeOwned,
+			F: FnOnce(u32, serde_yaml::Value) -> Result<serde_yaml::Value, String>
+	{
+		let path = self.slot_path(slot);
+		let file_data = match std::fs::read(&path) {
+			Ok(d) => d,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(SaveError::NotFound{ slot: slot }),
+			Err(e) => return Err(SaveError::Io{ path: path, source: e })
+		};
+
+		let (data_version, payload) = parse_save_file(&file_data)
+			.map_err(|reason| SaveError::Damaged{ path: path.clone(), reason: reason })?;
+
+		let value: serde_yaml::Value = serde_yaml::from_slice(payload)
+			.map_err(|e| SaveError::Damaged{ path: path.clone(), reason: e.to_string() })?;
+
+		let current_value = if data_version == self.data_version {
+			value
+		} else if data_version < self.data_version {
+			log::info!("Migrating save slot {} from data version {} to {}", slot, data_version, self.data_version);
+			migrate(data_version, value)
+				.map_err(|e| SaveError::Migration{ path: path.clone(), version: data_version, message: e })?
+		} else {
+			return Err(SaveError::UnsupportedVersion{ path: path, version: data_version })
+		};
+
+		serde_yaml::from_value(current_value).map_err(|e| SaveError::Damaged{ path: path, reason: e.to_string() })
+	}
+
+	/// Delete the save in the slot. Does nothing if the slot is empty.
+	pub fn delete(&self, slot: u32) -> Result<(), SaveError>
+	{
+		let path = self.slot_path(slot);
+		match std::fs::remove_file(&path) {
+			Ok(()) => Ok(()),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+			Err(e) => Err(SaveError::Io{ path: path, source: e })
+		}
+	}
+}
+
+/// Put the header for the data version and payload in front of the payload.
+fn encode_save_file(data_version: u32, payload: &[u8]) -> Vec<u8>
+{
+	let mut file_data = Vec::with_capacity(HEADER_LEN + payload.len());
+	file_data.extend_from_slice(SAVE_MAGIC);
+	file_data.extend_from_slice(&CONTAINER_VERSION.to_le_bytes());
+	file_data.extend_from_slice(&data_version.to_le_bytes());
+	file_data.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+	file_data.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+	file_data.extend_from_slice(payload);
+	file_data
+}
+
+/// Check the header and checksum of the save file, and return the data version along with the payload.
+fn parse_save_file(file_data: &[u8]) -> Result<(u32, &[u8]), String>
+{
+	if file_data.len() < HEADER_LEN {
+		return Err("file is too short".to_string())
+	}
+	if &file_data[0..4] != SAVE_MAGIC {
+		return Err("not a save file".to_string())
+	}
+
+	let container_version = u32::from_le_bytes(file_data[4..8].try_into().unwrap());
+	if container_version != CONTAINER_VERSION {
+		return Err(format!("unknown container version {}", container_version))
+	}
+	let data_version = u32::from_le_bytes(file_data[8..12].try_into().unwrap());
+	let payload_len = u64::from_le_bytes(file_data[12..20].try_into().unwrap());
+	let checksum = u32::from_le_bytes(file_data[20..24].try_into().unwrap());
+
+	let payload = &file_data[HEADER_LEN..];
+	if payload.len() as u64 != payload_len {
+		return Err(format!("expected {} bytes of data, but found {}", payload_len, payload.len()))
+	}
+	if crc32fast::hash(payload) != checksum {
+		return Err("checksum mismatch".to_string())
+	}
+
+	Ok((data_version, payload))
+}
+
+/// Write `data` into a temporary file next to `path`, then rename it to `path`.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<(), SaveError>
+{
+	let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+	tmp_name.push(".tmp");
+	let tmp_path = path.with_file_name(tmp_name);
+
+	let write_result = std::fs::File::create(&tmp_path).and_then(|mut f| {
+		f.write_all(data)?;
+		f.sync_all()
+	});
+	match write_result {
+		Ok(()) => (),
+		Err(e) => {
+			std::fs::remove_file(&tmp_path).ok();
+			return Err(SaveError::Io{ path: tmp_path, source: e })
+		}
+	}
+
+	std::fs::rename(&tmp_path, path).map_err(|e| SaveError::Io{ path: path.to_path_buf(), source: e })
+}
+
+#[derive(Debug)]
+pub enum SaveError
+{
+	/// There is no save in the slot.
+	NotFound{ slot: u32 },
+
+	/// The save file couldn't be read or written.
+	Io{ path: PathBuf, source: std::io::Error },
+
+	/// The save file is damaged, either because the header is invalid, the checksum doesn't match, or the data can't
+	/// be read.
+	Damaged{ path: PathBuf, reason: String },
+
+	/// The save was made with a data version that can't be loaded, either because it's newer than the current
+	/// version, or because it's older and wasn't loaded with `load_migrated`.
+	UnsupportedVersion{ path: PathBuf, version: u32 },
+
+	/// The game's migration from an older data version failed, with the given message.
+	Migration{ path: PathBuf, version: u32, message: String },
+
+	/// The data couldn't be serialized.
+	Serialize{ path: PathBuf, message: String }
+}
+impl SaveError
+{
+	/// Get the key of the localized message to show the user for this error.
+	pub fn localization_key(&self) -> &'static str
+	{
+		match self {
+			SaveError::Damaged{ .. } | SaveError::UnsupportedVersion{ .. } | SaveError::Migration{ .. } => {
+				"error.save_file_damaged"
+			}
+			_ => "error.generic_error"
+		}
+	}
+}
+impl std::error::Error for SaveError
+{
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+	{
+		match self {
+			SaveError::Io{ source, .. } => Some(source),
+			_ => None
+		}
+	}
+}
+impl std::fmt::Display for SaveError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			SaveError::NotFound{ slot } => write!(f, "save slot {} is empty", slot),
+			SaveError::Io{ path, source } => write!(f, "I/O error on save file '{}': {}", path.display(), source),
+			SaveError::Damaged{ path, reason } => write!(f, "save file '{}' is damaged: {}", path.display(), reason),
+			SaveError::UnsupportedVersion{ path, version } => {
+				write!(f, "save file '{}' has unsupported data version {}", path.display(), version)
+			}
+			SaveError::Migration{ path, version, message } => {
+				write!(f, "failed to migrate save file '{}' from data version {}: {}", path.display(), version, message)
+			}
+			SaveError::Serialize{ path, message } => {
+				write!(f, "failed to serialize save data for '{}': {}", path.display(), message)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::collections::BTreeMap;
+
+	// an empty directory for the test to put save files in
+	fn test_dir(name: &str) -> PathBuf
+	{
+		let dir = std::env::temp_dir().join(format!("mithrilengine-save-test-{}-{}", std::process::id(), name));
+		std::fs::remove_dir_all(&dir).ok();
+		dir
+	}
+
+	fn test_data() -> BTreeMap<String, u32>
+	{
+		[ ("level".to_string(), 3), ("coins".to_string(), 120) ].into_iter().collect()
+	}
+
+	#[test]
+	fn file_round_trip()
+	{
+		let dir = test_dir("file_round_trip");
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("test.sav");
+		write_atomic(&path, &encode_save_file(7, b"payload")).unwrap();
+		assert!(!dir.join("test.sav.tmp").exists());
+
+		let file_data = std::fs::read(&path).unwrap();
+		assert_eq!(parse_save_file(&file_data), Ok((7, &b"payload"[..])));
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn slot_round_trip()
+	{
+		let dir = test_dir("slot_round_trip");
+		let saves = SaveManager::new(dir.clone(), 1).unwrap();
+		saves.save(2, &test_data()).unwrap();
+		assert_eq!(saves.list_slots().unwrap(), vec![ 2 ]);
+		assert_eq!(saves.load::<BTreeMap<String, u32>>(2).unwrap(), test_data());
+		assert!(matches!(saves.load::<BTreeMap<String, u32>>(3), Err(SaveError::NotFound{ slot: 3 })));
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn bad_magic()
+	{
+		let mut file_data = encode_save_file(1, b"payload");
+		file_data[0] = b'X';
+		assert_eq!(parse_save_file(&file_data), Err("not a save file".to_string()));
+		assert!(parse_save_file(&file_data[..HEADER_LEN - 1]).is_err());
+	}
+
+	#[test]
+	fn truncated_payload()
+	{
+		let file_data = encode_save_file(1, b"payload");
+		let err = parse_save_file(&file_data[..file_data.len() - 1]).unwrap_err();
+		assert!(err.contains("expected 7 bytes"), "{}", err);
+	}
+
+	#[test]
+	fn checksum_mismatch_is_damaged()
+	{
+		let dir = test_dir("checksum_mismatch");
+		let saves = SaveManager::new(dir.clone(), 1).unwrap();
+		saves.save(0, &test_data()).unwrap();
+
+		let mut file_data = std::fs::read(saves.slot_path(0)).unwrap();
+		*file_data.last_mut().unwrap() ^= 0xff;
+		assert_eq!(parse_save_file(&file_data), Err("checksum mismatch".to_string()));
+
+		std::fs::write(saves.slot_path(0), &file_data).unwrap();
+		assert!(matches!(saves.load::<BTreeMap<String, u32>>(0), Err(SaveError::Damaged{ .. })));
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn newer_data_version()
+	{
+		let dir = test_dir("newer_data_version");
+		SaveManager::new(dir.clone(), 2).unwrap().save(0, &test_data()).unwrap();
+
+		let saves = SaveManager::new(dir.clone(), 1).unwrap();
+		let result = saves.load_migrated::<BTreeMap<String, u32>, _>(0, |_, value| Ok(value));
+		assert!(matches!(result, Err(SaveError::UnsupportedVersion{ version: 2, .. })));
+		std::fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn migration()
+	{
+		let dir = test_dir("migration");
+		SaveManager::new(dir.clone(), 1).unwrap().save(0, &test_data()).unwrap();
+		let saves = SaveManager::new(dir.clone(), 2).unwrap();
+
+		assert!(matches!(saves.load::<BTreeMap<String, u32>>(0), Err(SaveError::UnsupportedVersion{ version: 1, .. })));
+
+		let migrated: BTreeMap<String, u32> = saves.load_migrated(0, |version, mut value| {
+			assert_eq!(version, 1);
+			value.as_mapping_mut().unwrap().insert("lives".into(), 3.into());
+			Ok(value)
+		}).unwrap();
+		assert_eq!(migrated.get("lives"), Some(&3));
+
+		let result = saves.load_migrated::<BTreeMap<String, u32>, _>(0, |_, _| Err("missing field".to_string()));
+		match result {
+			Err(SaveError::Migration{ version, message, .. }) => {
+				assert_eq!(version, 1);
+				assert_eq!(message, "missing field");
+			}
+			other => panic!("expected migration error, got {:?}", other)
+		}
+		std::fs::remove_dir_all(&dir).ok();
+	}
+}
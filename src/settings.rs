@@ -0,0 +1,389 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::collections::BTreeMap;
+use std::path::{ Path, PathBuf };
+use log::LevelFilter;
+use serde::{ Serialize, Deserialize };
+use serde::de::DeserializeOwned;
+use serde_yaml::{ Mapping, Value };
+use crate::error::EngineError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowMode
+{
+	Windowed,
+	Fullscreen,
+	Borderless
+}
+
+/// How finished frames are presented to the screen, which controls vsync.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresentMode
+{
+	/// Vsync on; the frame rate is limited to the refresh rate of the monitor.
+	Fifo,
+
+	/// Like `Fifo`, but frames that miss a refresh are presented immediately, which may cause tearing.
+	FifoRelaxed,
+
+	/// Vsync on, but without limiting the frame rate; the newest frame gets presented at each refresh.
+	Mailbox,
+
+	/// Vsync off; frames are presented immediately, which may cause tearing.
+	Immediate
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AntiAliasing
+{
+	Off,
+	Msaa2x,
+	Msaa4x,
+	Msaa8x
+}
+impl AntiAliasing
+{
+	/// The number of samples per pixel for this mode.
+	pub fn sample_count(&self) -> u32
+	{
+		match self {
+			AntiAliasing::Off => 1,
+			AntiAliasing::Msaa2x => 2,
+			AntiAliasing::Msaa4x => 4,
+			AntiAliasing::Msaa8x => 8
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VideoSettings
+{
+	/// Size of the window in windowed mode, or the resolution of the video mode in fullscreen mode. This is ignored in
+	/// borderless mode, where the window covers the whole monitor.
+	pub resolution: [u32; 2],
+	pub window_mode: WindowMode,
+
+	/// Index of the monitor to use in fullscreen and borderless modes, as listed by `RenderContext::monitors`. If this
+	/// is `None`, the monitor the window is currently on is used.
+	pub monitor: Option<usize>,
+
+	/// Refresh rate in Hz of the video mode to use in fullscreen mode. If this is `None`, the highest available one is
+	/// used.
+	pub refresh_rate: Option<u16>,
+
+	/// The present mode to use if the device supports it. If it doesn't, the closest supported one is used instead.
+	pub present_mode: PresentMode,
+
+	/// Number of images in the swapchain, which is clamped to what the device supports. If this is `None`, the minimum
+	/// number of images that the device supports is used.
+	pub swapchain_images: Option<u32>,
+
+	/// Maximum frame rate, enforced on the CPU by waiting before each frame. If this is `None`, the frame rate isn't
+	/// limited other than by the present mode.
+	pub frame_rate_limit: Option<u32>,
+	pub antialiasing: AntiAliasing,
+	pub ambient_occlusion: bool
+}
+impl VideoSettings
+{
+	/// Create video settings with the default values, using the given resolution.
+	pub fn with_resolution(resolution: [u32; 2]) -> VideoSettings
+	{
+		VideoSettings{
+			resolution: resolution,
+			window_mode: WindowMode::Windowed,
+			monitor: None,
+			refresh_rate: None,
+			present_mode: PresentMode::Fifo,
+			swapchain_images: None,
+			frame_rate_limit: None,
+			antialiasing: AntiAliasing::Off,
+			ambient_occlusion: true
+		}
+	}
+
+	fn read_from(&mut self, mapping: &Mapping)
+	{
+		let mut resolution = self.resolution;
+		read_field(mapping, "resolution", &mut resolution);
+		if resolution[0] == 0 || resolution[1] == 0 {
+			log::warn!("Invalid value for setting 'resolution', using default: width and height must not be zero");
+		} else {
+			self.resolution = resolution;
+		}
+		read_field(mapping, "window_mode", &mut self.window_mode);
+		read_field(mapping, "monitor", &mut self.monitor);
+		read_field(mapping, "refresh_rate", &mut self.refresh_rate);
+
+		// settings files from before present modes were selectable only have a vsync switch
+		let mut vsync = None;
+		read_field(mapping, "vsync", &mut vsync);
+		match vsync {
+			Some(true) => self.present_mode = PresentMode::Fifo,
+			Some(false) => self.present_mode = PresentMode::Immediate,
+			None => ()
+		}
+		read_field(mapping, "present_mode", &mut self.present_mode);
+		read_field(mapping, "swapchain_images", &mut self.swapchain_images);
+		let mut frame_rate_limit = self.frame_rate_limit;
+		read_field(mapping, "frame_rate_limit", &mut frame_rate_limit);
+		if frame_rate_limit == Some(0) {
+			log::warn!("Invalid value for setting 'frame_rate_limit', using default: the limit must not be zero");
+		} else {
+			self.frame_rate_limit = frame_rate_limit;
+		}
+		read_field(mapping, "antialiasing", &mut self.antialiasing);
+		read_field(mapping, "ambient_occlusion", &mut self.ambient_occlusion);
+	}
+}
+impl Default for VideoSettings
+{
+	fn default() -> Self
+	{
+		Self::with_resolution([ 1280, 720 ])
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogSettings
+{
+	/// Level of messages printed to the terminal.
+	pub terminal_level: LevelFilter,
+
+	/// Level of messages written to the log file.
+	pub file_level: LevelFilter,
+
+	/// Number of log files to keep in the "logs" directory, including the one for the current run.
+	pub keep_files: usize,
+
+	/// Levels for specific modules, such as `vulkano: warn` or `mithrilengine::render: trace`, which replace both the
+	/// terminal and file levels for messages from those modules and their submodules.
+	pub module_levels: BTreeMap<String, LevelFilter>
+}
+impl LogSettings
+{
+	fn read_from(&mut self, mapping: &Mapping)
+	{
+		read_field(mapping, "terminal_level", &mut self.terminal_level);
+		read_field(mapping, "file_level", &mut self.file_level);
+		read_field(mapping, "keep_files", &mut self.keep_files);
+		read_field(mapping, "module_levels", &mut self.module_levels);
+	}
+}
+impl Default for LogSettings
+{
+	fn default() -> Self
+	{
+		#[cfg(debug_assertions)]
+		let level = LevelFilter::Debug;
+		#[cfg(not(debug_assertions))]
+		let level = LevelFilter::Info;
+
+		LogSettings{ terminal_level: level, file_level: level, keep_files: 10, module_levels: BTreeMap::new() }
+	}
+}
+
+/// User settings, loaded from and saved to "settings.yaml" in the preferences directory. Available to systems as a
+/// unique. Changes made at runtime get applied to the engine at the end of the frame.
+#[derive(Serialize)]
+pub struct Settings
+{
+	#[serde(skip)]
+	path: PathBuf,
+
+	#[serde(skip)]
+	video_changed: bool,
+
+	// window mode given on the command line, used in place of the one in `video` for this run only
+	#[serde(skip)]
+	window_mode_override: Option<WindowMode>,
+
+	video: VideoSettings,
+
+	log: LogSettings,
+
+	/// Language code such as "en" or "ja". If this is `None`, the language will be determined from the system locale.
+	language: Option<String>,
+
+	/// Physical device to render with, as either a device index or part of a device name. If this is `None`, the
+	/// device selected by the game gets used.
+	physical_device: Option<String>
+}
+impl Settings
+{
+	/// Load the settings file from the preferences directory. Settings that are missing or invalid will be set to the
+	/// values in `defaults`. If the file doesn't exist, or can't be parsed at all, all of `defaults` will be used.
+	pub fn load(pref_path: &Path, defaults: VideoSettings) -> Settings
+	{
+		let path = pref_path.join("settings.yaml");
+		let mut video = defaults;
+		let mut log_settings = LogSettings::default();
+		let mut language = None;
+		let mut physical_device = None;
+
+		match read_root(&path) {
+			Some(root) => {
+				match root.get(&Value::from("video")).and_then(|v| v.as_mapping()) {
+					Some(video_mapping) => video.read_from(video_mapping),
+					None => ()
+				}
+				match root.get(&Value::from("log")).and_then(|v| v.as_mapping()) {
+					Some(log_mapping) => log_settings.read_from(log_mapping),
+					None => ()
+				}
+				read_field(&root, "language", &mut language);
+				read_field(&root, "physical_device", &mut physical_device);
+			}
+			None => ()
+		}
+
+		Settings{
+			path: path,
+			video_changed: false,
+			window_mode_override: None,
+			video: video,
+			log: log_settings,
+			language: language,
+			physical_device: physical_device
+		}
+	}
+
+	/// Load only the log settings from the settings file in the preferences directory. This is used to set up the
+	/// logger before the rest of the settings are loaded, so any problems with the file won't be logged here.
+	pub fn load_log_settings(pref_path: &Path) -> LogSettings
+	{
+		let mut log_settings = LogSettings::default();
+		match read_root(&pref_path.join("settings.yaml")) {
+			Some(root) => match root.get(&Value::from("log")).and_then(|v| v.as_mapping()) {
+				Some(log_mapping) => log_settings.read_from(log_mapping),
+				None => ()
+			},
+			None => ()
+		}
+		log_settings
+	}
+
+	/// Write the settings into the settings file.
+	pub fn save(&self) -> Result<(), EngineError>
+	{
+		let yaml_string = serde_yaml::to_string(self).map_err(|e| EngineError::decode(&self.path, e))?;
+		std::fs::write(&self.path, yaml_string).map_err(|e| EngineError::io(&self.path, e))?;
+		log::info!("Saved settings to '{}'", self.path.display());
+		Ok(())
+	}
+
+	/// The video settings as they are saved in the settings file, such as for showing in a settings menu. Use
+	/// `effective_video` for the settings that are actually in use.
+	pub fn video(&self) -> &VideoSettings
+	{
+		&self.video
+	}
+
+	/// The video settings with the window mode given on the command line, if any, in place of the saved one.
+	pub fn effective_video(&self) -> VideoSettings
+	{
+		let mut video = self.video.clone();
+		match self.window_mode_override {
+			Some(m) => video.window_mode = m,
+			None => ()
+		}
+		video
+	}
+
+	/// Change the video settings. The renderer will be notified of the change at the end of the frame, after which the
+	/// settings will be saved. Changing the window mode here replaces the one given on the command line.
+	pub fn set_video(&mut self, video: VideoSettings)
+	{
+		if video != self.video {
+			if video.window_mode != self.video.window_mode {
+				self.window_mode_override = None;
+			}
+			self.video = video;
+			self.video_changed = true;
+		}
+	}
+
+	/// The log settings. Changes to these take effect the next time the game is started, so there's no setter for
+	/// them; edit the settings file instead.
+	pub fn log(&self) -> &LogSettings
+	{
+		&self.log
+	}
+
+	pub fn language(&self) -> Option<&str>
+	{
+		self.language.as_deref()
+	}
+
+	/// Change the language. This takes effect the next time the game is started.
+	pub fn set_language(&mut self, language: Option<String>) -> Result<(), EngineError>
+	{
+		self.language = language;
+		self.save()
+	}
+
+	pub fn physical_device(&self) -> Option<&str>
+	{
+		self.physical_device.as_deref()
+	}
+
+	/// Change the physical device to render with. This takes effect the next time the game is started.
+	pub fn set_physical_device(&mut self, physical_device: Option<String>) -> Result<(), EngineError>
+	{
+		self.physical_device = physical_device;
+		self.save()
+	}
+
+	/// Override the window mode for this run only, without marking the video settings as changed. The override never
+	/// gets saved to the settings file.
+	pub(crate) fn override_window_mode(&mut self, window_mode: WindowMode)
+	{
+		self.window_mode_override = Some(window_mode);
+	}
+
+	/// Check if the video settings have changed since the last call to this function.
+	pub(crate) fn take_video_changed(&mut self) -> bool
+	{
+		std::mem::replace(&mut self.video_changed, false)
+	}
+}
+
+/// Read the settings file as a YAML mapping, or return `None` if it's missing or invalid.
+fn read_root(path: &Path) -> Option<Mapping>
+{
+	match std::fs::read_to_string(path) {
+		Ok(yaml_string) => match serde_yaml::from_str::<Value>(&yaml_string) {
+			Ok(Value::Mapping(root)) => Some(root),
+			Ok(_) => {
+				log::warn!("'{}' is not a YAML mapping, using default settings", path.display());
+				None
+			}
+			Err(e) => {
+				log::warn!("Failed to parse '{}', using default settings: {}", path.display(), e);
+				None
+			}
+		},
+		Err(e) => {
+			match e.kind() {
+				std::io::ErrorKind::NotFound => log::info!("No settings file found, using default settings"),
+				_ => log::warn!("Failed to read '{}', using default settings: {}", path.display(), e)
+			}
+			None
+		}
+	}
+}
+
+/// Deserialize the value at `key` into `target`, leaving `target` as it is if the value is missing or invalid.
+fn read_field<T: DeserializeOwned>(mapping: &Mapping, key: &str, target: &mut T)
+{
+	match mapping.get(&Value::from(key)) {
+		Some(v) => match serde_yaml::from_value(v.clone()) {
+			Ok(parsed) => *target = parsed,
+			Err(e) => log::warn!("Invalid value for setting '{}', using default: {}", key, e)
+		},
+		None => ()
+	}
+}
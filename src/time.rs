@@ -0,0 +1,322 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::time::{ Duration, Instant };
+
+/// Name of the workload that gets run at a fixed rate, zero or more times per frame. Systems in this workload should
+/// use `Time::fixed_delta` as the time step.
+pub const FIXED_UPDATE_WORKLOAD: &str = "FixedUpdate";
+
+/// Name of the workload that gets run once every frame before drawing. Systems in this workload should use
+/// `Time::delta` as the time step.
+pub const UPDATE_WORKLOAD: &str = "Update";
+
+/// Frames taking longer than this will be treated as if they took this long, so that a long stall (e.g. from loading,
+/// or from the window being dragged) doesn't make the fixed update stage run many times trying to catch up.
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Fixed timesteps shorter than this get raised to it. A timestep of zero would make the fixed update stage run
+/// forever, since the accumulated time would never drop below it.
+pub const MIN_FIXED_TIMESTEP: Duration = Duration::from_millis(1);
+
+/// Maximum number of fixed updates run in a single frame. If fixed updates take longer than the timestep, the time
+/// accumulated for them would keep growing every frame, so any time left over after this many updates is dropped.
+pub const MAX_FIXED_STEPS_PER_FRAME: u32 = 10;
+
+/// Timing information, available to systems as a unique.
+#[derive(Clone, Copy, Debug)]
+pub struct Time
+{
+	delta: f32,
+	fixed_delta: f32,
+	alpha: f32,
+	elapsed: f64,
+	frame_count: u64,
+	fixed_update_count: u64
+}
+impl Time
+{
+	pub(crate) fn new(fixed_timestep: Duration) -> Time
+	{
+		Time{
+			delta: 0.0,
+			fixed_delta: fixed_timestep.as_secs_f32(),
+			alpha: 0.0,
+			elapsed: 0.0,
+			frame_count: 0,
+			fixed_update_count: 0
+		}
+	}
+
+	/// Time in seconds that the previous frame took.
+	pub fn delta(&self) -> f32
+	{
+		self.delta
+	}
+
+	/// Time in seconds between each run of the fixed update workload.
+	pub fn fixed_delta(&self) -> f32
+	{
+		self.fixed_delta
+	}
+
+	/// How far in between the last fixed update and the next one the current frame is, from 0.0 to 1.0. Use this to
+	/// interpolate between states calculated in fixed updates when drawing.
+	pub fn alpha(&self) -> f32
+	{
+		self.alpha
+	}
+
+	/// Time in seconds since the game started running.
+	pub fn elapsed(&self) -> f64
+	{
+		self.elapsed
+	}
+
+	/// Number of frames that have been run so far, including the current frame.
+	pub fn frame_count(&self) -> u64
+	{
+		self.frame_count
+	}
+
+	/// Number of fixed updates that have been run so far, including the current one.
+	pub fn fixed_update_count(&self) -> u64
+	{
+		self.fixed_update_count
+	}
+
+	pub(crate) fn begin_frame(&mut self, delta: Duration)
+	{
+		self.delta = delta.as_secs_f32();
+		self.elapsed += delta.as_secs_f64();
+		self.frame_count += 1;
+	}
+
+	pub(crate) fn begin_fixed_update(&mut self)
+	{
+		self.fixed_update_count += 1;
+	}
+
+	pub(crate) fn set_alpha(&mut self, alpha: f32)
+	{
+		self.alpha = alpha;
+	}
+}
+
+/// Clock that measures frame times and accumulates them to determine when fixed updates should be run.
+pub struct FrameClock
+{
+	last_instant: Option<Instant>,
+	accumulator: Duration,
+	fixed_timestep: Duration,
+	steps_this_frame: u32
+}
+impl FrameClock
+{
+	/// Create a clock with the given fixed timestep, which is raised to `MIN_FIXED_TIMESTEP` if it's shorter.
+	pub fn new(fixed_timestep: Duration) -> FrameClock
+	{
+		FrameClock{
+			last_instant: None,
+			accumulator: Duration::ZERO,
+			fixed_timestep: fixed_timestep.max(MIN_FIXED_TIMESTEP),
+			steps_this_frame: 0
+		}
+	}
+
+	/// Measure the time since the previous call to this function, and accumulate it for fixed updates.
+	/// Returns the time the frame took, which will be zero upon the first call.
+	pub fn tick(&mut self) -> Duration
+	{
+		let now = Instant::now();
+		let frame_time = match self.last_instant {
+			Some(last) => now.duration_since(last),
+			None => Duration::ZERO
+		};
+		self.last_instant = Some(now);
+		self.advance(frame_time)
+	}
+
+	/// Accumulate the given frame time for fixed updates without measuring it, for use where the time should advance
+	/// deterministically such as when running headless. Returns the frame time after being clamped.
+	pub fn advance(&mut self, frame_time: Duration) -> Duration
+	{
+		let clamped = frame_time.min(MAX_FRAME_TIME);
+		self.accumulator += clamped;
+		self.steps_this_frame = 0;
+		clamped
+	}
+
+	/// Consume one fixed timestep from the accumulated time. Returns `true` if a fixed update should be run, in which
+	/// case this should be called again after running it. At most `MAX_FIXED_STEPS_PER_FRAME` steps are consumed per
+	/// frame, after which whole timesteps still left in the accumulated time are dropped.
+	pub fn consume_fixed_step(&mut self) -> bool
+	{
+		if self.accumulator < self.fixed_timestep {
+			false
+		} else if self.steps_this_frame >= MAX_FIXED_STEPS_PER_FRAME {
+			let leftover_nanos = self.accumulator.as_nanos() % self.fixed_timestep.as_nanos();
+			self.accumulator = Duration::from_nanos(leftover_nanos as u64);
+			false
+		} else {
+			self.accumulator -= self.fixed_timestep;
+			self.steps_this_frame += 1;
+			true
+		}
+	}
+
+	/// Get the interpolation alpha between the last fixed update and the next one.
+	pub fn alpha(&self) -> f32
+	{
+		self.accumulator.as_secs_f32() / self.fixed_timestep.as_secs_f32()
+	}
+
+	pub fn fixed_timestep(&self) -> Duration
+	{
+		self.fixed_timestep
+	}
+}
+
+/// How long before the start of the next frame the frame limiter stops sleeping and starts spinning, since sleeps can
+/// overshoot by about a millisecond depending on the OS scheduler.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// Limits the frame rate by waiting before each frame on the CPU. Frames are scheduled at fixed intervals rather than
+/// relative to when the previous one ended, so that the frame times stay even.
+pub struct FrameLimiter
+{
+	frame_interval: Option<Duration>,
+	next_frame: Option<Instant>
+}
+impl FrameLimiter
+{
+	/// Create a frame limiter with the given maximum frame rate, or one that doesn't limit if it's `None`.
+	pub fn new(frame_rate_limit: Option<u32>) -> FrameLimiter
+	{
+		let mut limiter = FrameLimiter{ frame_interval: None, next_frame: None };
+		limiter.set_limit(frame_rate_limit);
+		limiter
+	}
+
+	pub fn set_limit(&mut self, frame_rate_limit: Option<u32>)
+	{
+		self.frame_interval = frame_rate_limit.filter(|l| *l > 0).map(|l| Duration::from_secs(1) / l);
+		self.next_frame = None;
+	}
+
+	/// Wait until it's time to start the next frame. Returns immediately if the frame rate isn't limited, or if the
+	/// previous frame took longer than the frame interval.
+	pub fn wait(&mut self)
+	{
+		let frame_interval = match self.frame_interval {
+			Some(i) => i,
+			None => return
+		};
+		let now = Instant::now();
+		let next_frame = match self.next_frame {
+			Some(n) => n,
+			None => now
+		};
+
+		if next_frame > now {
+			let remaining = next_frame - now;
+			if remaining > SPIN_MARGIN {
+				std::thread::sleep(remaining - SPIN_MARGIN);
+			}
+			while Instant::now() < next_frame {
+				std::hint::spin_loop();
+			}
+		}
+
+		// if we fell behind by more than a frame, start over from now instead of rushing to catch up
+		let after_wait = Instant::now();
+		self.next_frame = Some(match next_frame + frame_interval {
+			n if n < after_wait => after_wait + frame_interval,
+			n => n
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn accumulator_carries_over()
+	{
+		let mut clock = FrameClock::new(Duration::from_millis(10));
+		clock.advance(Duration::from_millis(25));
+		assert!(clock.consume_fixed_step());
+		assert!(clock.consume_fixed_step());
+		assert!(!clock.consume_fixed_step());
+
+		// the leftover 5 ms count towards the next frame
+		clock.advance(Duration::from_millis(5));
+		assert!(clock.consume_fixed_step());
+		assert!(!clock.consume_fixed_step());
+	}
+
+	#[test]
+	fn long_frames_are_clamped()
+	{
+		let mut clock = FrameClock::new(Duration::from_millis(50));
+		assert_eq!(clock.advance(Duration::from_secs(5)), MAX_FRAME_TIME);
+
+		let mut steps = 0;
+		while clock.consume_fixed_step() {
+			steps += 1;
+		}
+		assert_eq!(steps, 5);
+	}
+
+	#[test]
+	fn fixed_steps_are_capped_per_frame()
+	{
+		let mut clock = FrameClock::new(MIN_FIXED_TIMESTEP);
+		clock.advance(MAX_FRAME_TIME + Duration::from_micros(500));
+
+		let mut steps = 0;
+		while clock.consume_fixed_step() {
+			steps += 1;
+		}
+		assert_eq!(steps, MAX_FIXED_STEPS_PER_FRAME);
+
+		// only the partial timestep is kept, so the next frame starts the count over without a backlog
+		assert!((0.0..1.0).contains(&clock.alpha()));
+		clock.advance(Duration::from_millis(2));
+		assert!(clock.consume_fixed_step());
+		assert!(clock.consume_fixed_step());
+		assert!(!clock.consume_fixed_step());
+	}
+
+	#[test]
+	fn alpha_stays_in_range()
+	{
+		let mut clock = FrameClock::new(Duration::from_millis(10));
+		assert_eq!(clock.alpha(), 0.0);
+		for frame_ms in [ 3, 7, 16, 1, 33, 9 ] {
+			clock.advance(Duration::from_millis(frame_ms));
+			while clock.consume_fixed_step() {}
+			let alpha = clock.alpha();
+			assert!((0.0..1.0).contains(&alpha), "alpha {} out of range", alpha);
+		}
+	}
+
+	#[test]
+	fn zero_timestep_is_raised()
+	{
+		let mut clock = FrameClock::new(Duration::ZERO);
+		assert_eq!(clock.fixed_timestep(), MIN_FIXED_TIMESTEP);
+
+		clock.advance(Duration::from_millis(5));
+		let mut steps = 0;
+		while clock.consume_fixed_step() {
+			steps += 1;
+		}
+		assert_eq!(steps, 5);
+		assert!(clock.alpha().is_finite());
+	}
+}
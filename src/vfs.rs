@@ -0,0 +1,234 @@
+/* -----------------------------------------------------------------------------
+	MithrilEngine Framework (MEF)
+
+	Copyright (c) 2021-2022, daigennki (@daigennki)
+----------------------------------------------------------------------------- */
+use std::path::{ Component, Path, PathBuf };
+use std::sync::{ Arc, RwLock };
+use crate::error::EngineError;
+use crate::archive::{ Archive, ARCHIVE_EXTENSION };
+
+/// Priority for the engine's own data, such as shaders.
+pub const PRIORITY_ENGINE: i32 = 0;
+
+/// Priority for the game's data, which can override the engine's data.
+pub const PRIORITY_GAME: i32 = 100;
+
+/// Priority for user mods, which can override both the engine's and the game's data.
+pub const PRIORITY_MOD: i32 = 200;
+
+/// Something that can be mounted into the virtual filesystem to provide files, such as a directory.
+pub trait VfsSource: Send + Sync
+{
+	/// Read the file at the logical path, which uses "/" as the separator and never contains "..".
+	/// Returns `None` if this source doesn't have the file.
+	fn read(&self, logical_path: &str) -> Option<std::io::Result<Vec<u8>>>;
+
+	/// Check if this source has the file at the logical path.
+	fn exists(&self, logical_path: &str) -> bool;
+
+	/// Get the path of the file on disk, if this source stores files as they are on disk.
+	fn real_path(&self, _logical_path: &str) -> Option<PathBuf>
+	{
+		None
+	}
+
+	/// Describe this source for log messages, such as with the path of the directory.
+	fn describe(&self) -> String;
+}
+
+/// A directory on disk mounted into the virtual filesystem.
+pub struct DirectorySource
+{
+	root: PathBuf
+}
+impl DirectorySource
+{
+	pub fn new(root: PathBuf) -> DirectorySource
+	{
+		DirectorySource{ root: root }
+	}
+}
+impl VfsSource for DirectorySource
+{
+	fn read(&self, logical_path: &str) -> Option<std::io::Result<Vec<u8>>>
+	{
+		match std::fs::read(self.root.join(logical_path)) {
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+			result => Some(result)
+		}
+	}
+
+	fn exists(&self, logical_path: &str) -> bool
+	{
+		self.root.join(logical_path).is_file()
+	}
+
+	fn real_path(&self, logical_path: &str) -> Option<PathBuf>
+	{
+		let path = self.root.join(logical_path);
+		path.is_file().then(|| path)
+	}
+
+	fn describe(&self) -> String
+	{
+		format!("directory '{}'", self.root.display())
+	}
+}
+
+struct Mount
+{
+	priority: i32,
+	source: Arc<dyn VfsSource>
+}
+
+/// Virtual filesystem which resolves logical asset paths, such as "shaders/ui.yaml", through the mounted sources in
+/// order of priority. Sources with a higher priority override files in sources with a lower priority, and among
+/// sources with the same priority, the one mounted last takes precedence. Available to systems as a unique.
+///
+/// Clones of a `Vfs` share the same mounts, so sources mounted at runtime (such as mods) are visible everywhere.
+#[derive(Clone)]
+pub struct Vfs
+{
+	mounts: Arc<RwLock<Vec<Mount>>>
+}
+impl Vfs
+{
+	/// Create a virtual filesystem with nothing mounted.
+	pub fn new() -> Vfs
+	{
+		Vfs{ mounts: Arc::new(RwLock::new(Vec::new())) }
+	}
+
+	/// Mount a source with the given priority, such as `PRIORITY_GAME`.
+	pub fn mount(&self, source: impl VfsSource + 'static, priority: i32)
+	{
+		log::info!("Mounting {} with priority {}", source.describe(), priority);
+		let mut mounts = self.mounts.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+		// keep the mounts sorted by descending priority, placing the new mount before others with the same priority
+		let index = mounts.iter().position(|m| m.priority <= priority).unwrap_or(mounts.len());
+		mounts.insert(index, Mount{ priority: priority, source: Arc::new(source) });
+	}
+
+	/// Mount a directory with the given priority. Relative paths are resolved against the directory containing the
+	/// executable, so that the game doesn't depend on the current working directory.
+	pub fn mount_dir(&self, root: &Path, priority: i32)
+	{
+		let root = match root.is_absolute() {
+			true => root.to_path_buf(),
+			false => exe_dir().join(root)
+		};
+		if !root.is_dir() {
+			log::warn!("Directory '{}' being mounted doesn't exist", root.display());
+		}
+		self.mount(DirectorySource::new(root), priority)
+	}
+
+	/// Open an asset archive and mount it with the given priority. Relative paths are resolved against the directory
+	/// containing the executable.
+	pub fn mount_archive(&self, path: &Path, priority: i32) -> Result<(), EngineError>
+	{
+		let path = match path.is_absolute() {
+			true => path.to_path_buf(),
+			false => exe_dir().join(path)
+		};
+		self.mount(Archive::open(&path)?, priority);
+		Ok(())
+	}
+
+	/// Mount an asset archive if the path has the archive file extension, or a directory otherwise.
+	pub fn mount_path(&self, path: &Path, priority: i32) -> Result<(), EngineError>
+	{
+		match is_archive(path) {
+			true => self.mount_archive(path, priority),
+			false => {
+				self.mount_dir(path, priority);
+				Ok(())
+			}
+		}
+	}
+
+	/// Read the whole file at the logical path from the source with the highest priority that has it.
+	pub fn read(&self, path: &Path) -> Result<Vec<u8>, EngineError>
+	{
+		let logical_path = normalize(path)?;
+		for source in self.sources() {
+			match source.read(&logical_path) {
+				Some(result) => return result.map_err(|e| EngineError::io(path, e)),
+				None => ()
+			}
+		}
+		Err(EngineError::io(path, std::io::Error::new(std::io::ErrorKind::NotFound, "not found in any mounted source")))
+	}
+
+	/// Read the whole file at the logical path as a UTF-8 string.
+	pub fn read_to_string(&self, path: &Path) -> Result<String, EngineError>
+	{
+		String::from_utf8(self.read(path)?).map_err(|e| EngineError::decode(path, e))
+	}
+
+	/// Check if any mounted source has the file at the logical path.
+	pub fn exists(&self, path: &Path) -> bool
+	{
+		match normalize(path) {
+			Ok(logical_path) => self.sources().iter().any(|s| s.exists(&logical_path)),
+			Err(_) => false
+		}
+	}
+
+	/// Get the path on disk of the file that the logical path resolves to, if it's stored as a file on disk.
+	pub fn real_path(&self, path: &Path) -> Option<PathBuf>
+	{
+		let logical_path = normalize(path).ok()?;
+		self.sources().iter()
+			.find(|s| s.exists(&logical_path))
+			.and_then(|s| s.real_path(&logical_path))
+	}
+
+	// Get the mounted sources in order of precedence, so the lock doesn't need to be held while reading files.
+	fn sources(&self) -> Vec<Arc<dyn VfsSource>>
+	{
+		let mounts = self.mounts.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+		mounts.iter().map(|m| m.source.clone()).collect()
+	}
+}
+impl Default for Vfs
+{
+	fn default() -> Self
+	{
+		Self::new()
+	}
+}
+
+/// Convert the path into a logical path with "/" as the separator. Paths that are absolute or contain ".." are
+/// rejected, so that assets can't be loaded from outside of the mounted sources.
+pub(crate) fn normalize(path: &Path) -> Result<String, EngineError>
+{
+	let mut components = Vec::new();
+	for component in path.components() {
+		match component {
+			Component::Normal(c) => match c.to_str() {
+				Some(s) => components.push(s),
+				None => return Err(EngineError::decode(path, "asset path is not valid UTF-8"))
+			},
+			Component::CurDir => (),
+			_ => return Err(EngineError::decode(path, "asset path must be relative, without \"..\""))
+		}
+	}
+	Ok(components.join("/"))
+}
+
+/// Check if the path has the file extension of asset archives.
+pub fn is_archive(path: &Path) -> bool
+{
+	path.extension().and_then(|e| e.to_str()) == Some(ARCHIVE_EXTENSION)
+}
+
+/// Get the directory containing the executable, or the current working directory if it can't be determined.
+pub fn exe_dir() -> PathBuf
+{
+	std::env::current_exe().ok()
+		.and_then(|exe| exe.parent().map(|p| p.to_path_buf()))
+		.unwrap_or_else(|| PathBuf::from("."))
+}